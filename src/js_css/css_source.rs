@@ -29,6 +29,21 @@ pub fn contents() -> &'static str {
     margin-bottom: 3px;
 }
 
+.script .highlight {
+    margin: 0;
+    white-space: pre;
+    overflow: auto;
+}
+.hl-comment { color: #6a737d; font-style: italic; }
+.hl-string { color: #032f62; }
+.hl-key { color: #005cc5; }
+.hl-keyword { color: #d73a49; font-weight: bold; }
+.hl-literal { color: #005cc5; }
+.hl-number { color: #005cc5; }
+.hl-type { color: #6f42c1; }
+.hl-macro { color: #6f42c1; }
+.hl-variable { color: #e36209; }
+
 .editSubsectionContent {
     width: 98%;
 }
@@ -42,6 +57,11 @@ pub fn contents() -> &'static str {
     border: 1px black solid;
     vertical-align: top;
 }
+.table .tableBorder th {
+    padding: 5px 10px 5px 10px;
+    border: 1px black solid;
+    background-color: #f0f0f0;
+}
 
 .tableNoBorder {
     border-collapse: collapse;
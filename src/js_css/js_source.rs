@@ -1421,6 +1421,12 @@ class Editor extends Blox {
 <label><input type="radio" name="{BXPF=contentType2}" value="html" class="{BXPF=contentTypeHtml}" />HTML</label>
 <label><input type="radio" name="{BXPF=contentType2}" value="text" class="{BXPF=contentTypeText}" />Text</label>
 <label><input type="radio" name="{BXPF=contentType2}" value="script" class="{BXPF=contentTypeScript}" />Script</label>
+<label><input type="radio" name="{BXPF=contentType2}" value="markdown" class="{BXPF=contentTypeMarkdown}" />Markdown</label>
+<label><input type="radio" name="{BXPF=contentType2}" value="table" class="{BXPF=contentTypeTable}" />Table</label>
+<label>lang <input type="text" size="8" class="{BXPF=contentLang}" placeholder="rust" /></label>
+<label><input type="checkbox" class="{BXPF=contentTableHeader}" />header</label>
+<label>align <input type="text" size="8" class="{BXPF=contentTableAlign}" placeholder="l,r,c" /></label>
+<label>attach <input type="file" class="{BXPF=contentAttach}" /></label>
 
 	  </td>
 	</tr>
@@ -1909,6 +1915,13 @@ class Menu extends Blox {
 	    // console.log("rev: " + pageJson["data"]["page"]["rev"]);
 	    this.changed(undefined);
 	    this.editorClose();
+
+	    // Html contents were sanitized on the server, reload to show what saved.
+	    if(res.sanitized && res.sanitized.length > 0){
+		const removed = res.sanitized.map((v) => v.removed.join(", ")).join("\n");
+		alert("Removed from html contents:\n" + removed);
+		location.reload();
+	    }
 	    return;
 	}
 	if (res) {
@@ -2421,7 +2434,9 @@ class MenuEditor extends Editor {
 	let req = "page_mainte";
 	let data = {"mainte_url" : mainteUrl};
 	let res = await fetchPost(req, data);
-	if (res && res.res == "maintained") {
+	// mainte runs in background, see wc_request "mainte_status" for the progress.
+	if (res && res.res == "mainte_started") {
+	    console.info("mainte job " + res.job.job_id + " started on " + res.job.mainte_url);
 	    delete this.currentStatus().editType;
 	    // super: class Editor this class extends on.
 	    super.editorEnter();
@@ -4093,6 +4108,8 @@ class Content extends Blox {
 	if(type == "html"){ ele = this.eleHtml(); }
 	if(type == "script"){ ele = this.eleScript(); }
 	if(type == "text"){ ele = this.eleText(); }
+	if(type == "markdown"){ ele = this.eleMarkdown(); }
+	if(type == "table"){ ele = this.eleTable(); }
 	
 	// editor target
 	const eleEditor = document.createElement("div");
@@ -4121,10 +4138,25 @@ class Content extends Blox {
 
 	let eleScript = document.createElement('div');
 	eleScript.setAttribute('class', "script");
-	eleScript.innerHTML = this.dataToText(this.data().value);
+	if(this.data().lang){
+	    // Code, shown as it is until the server highlights it.
+	    eleScript.textContent = this.data().value;
+	}else{
+	    eleScript.innerHTML = this.dataToText(this.data().value);
+	}
 
 	ele.appendChild(eleScript);
 
+	// Highlighted by the server if lang is set.
+	if(this.data().lang){
+	    const data = {"type" : "script", "lang" : this.data().lang, "value" : this.data().value};
+	    contentRender(data).then((html) => {
+		if(html){
+		    eleScript.outerHTML = html;
+		}
+	    });
+	}
+
 	return ele;
 	
     } // end of class Content eleScript 
@@ -4143,6 +4175,79 @@ class Content extends Blox {
 	
     } // end of class Content eleText 
 
+    eleMarkdown() {
+
+	let ele = document.createElement('div');
+	ele.setAttribute('class', "html subsectionContent");
+
+	let eleMarkdown = document.createElement('div');
+	eleMarkdown.setAttribute('class', "markdown");
+	// Shown as it is until the server renders it.
+	eleMarkdown.textContent = this.data().value;
+	ele.appendChild(eleMarkdown);
+
+	const data = {"type" : "markdown", "value" : this.data().value};
+	contentRender(data).then((html) => {
+	    if(html){
+		eleMarkdown.outerHTML = html;
+	    }
+	});
+
+	return ele;
+	
+    } // end of class Content eleMarkdown 
+
+    eleTable() {
+
+	let ele = document.createElement('div');
+	ele.setAttribute('class', "html subsectionContent");
+
+	let eleTable = document.createElement('div');
+	eleTable.setAttribute('class', "table");
+	// Shown as it is until the server renders it.
+	eleTable.textContent = this.data().value;
+	ele.appendChild(eleTable);
+
+	// <div class="table"><table>...</table></div>
+	contentRender(this.data()).then((html) => {
+	    if(!html){ return; }
+	    const eleRendered = document.createElement("div");
+	    eleRendered.innerHTML = html;
+	    eleTable.innerHTML = eleRendered.firstElementChild.innerHTML;
+	    this.eleTableSortSet(eleTable, {});
+	});
+
+	return ele;
+	
+    } // end of class Content eleTable 
+
+    // query: {"sort" : column, "desc" : true}
+    // Sorted on the view only, the value is not changed.
+    eleTableRender(eleTable, query) {
+
+	const data = Object.assign({"content" : this.data()}, query);
+	fetchPost("table_query", data).then((res) => {
+	    if(!res || res.res != "table"){ return; }
+	    eleTable.innerHTML = res.html;
+	    this.eleTableSortSet(eleTable, query);
+	});
+	
+    } // end of class Content eleTableRender 
+
+    // click the header to sort by the column
+    eleTableSortSet(eleTable, query) {
+
+	eleTable.querySelectorAll("th").forEach((eleTh, column) => {
+	    eleTh.style.cursor = "pointer";
+	    eleTh.addEventListener("click", (event) => {
+		event.stopPropagation();
+		const desc = query.sort == column && !query.desc;
+		this.eleTableRender(eleTable, {"sort" : column, "desc" : desc});
+	    });
+	});
+	
+    } // end of class Content eleTableSortSet 
+
     dataToText(data) {
 
 	// let data = this.data()["value"];
@@ -4264,6 +4369,7 @@ class ContentEditor extends Editor {
 	let ele = this.eleFromHtml(html);
 	
 	this.dataSet(ele);
+	this.attachListenerSet(ele);
 	
 	this.eleVisibleSet(ele, {"editorNewPage" : 0});
 	
@@ -4275,11 +4381,43 @@ class ContentEditor extends Editor {
 	
     } // end of class ContentEditor eleDrawInst 
 
+    // Upload the file chosen and put a link to it in the textarea.
+    attachListenerSet(ele) {
+
+	const eleAttach = this.querySelectorBx(ele, "contentAttach");
+	eleAttach.addEventListener("change", async (event) => {
+	    const file = eleAttach.files[0];
+	    if(!file){ return; }
+
+	    const res = await fetchPostFile("attachment_upload", file);
+	    eleAttach.value = "";
+	    if(!res || res.res != "attachment_upload"){
+		alert("Failed to attach " + file.name);
+		return;
+	    }
+
+	    const checked = ele.querySelector('input[name='+this.bloxPrefixEscaped("contentType2")+']:checked');
+	    const isImage = file.type.startsWith("image/");
+	    let link;
+	    if(checked && checked.value == "markdown"){
+		link = (isImage ? "!" : "") + "[" + res.name + "](" + res.href + ")";
+	    } else if(isImage){
+		link = '<img src="' + res.href + '" alt="' + res.name + '">';
+	    } else {
+		link = '<a href="' + res.href + '">' + res.name + '</a>';
+	    }
+
+	    const eleTextarea = this.querySelectorBx(ele, "editorContent");
+	    eleTextarea.setRangeText(link, eleTextarea.selectionStart, eleTextarea.selectionEnd, "end");
+	});
+
+    } // end of class ContentEditor attachListenerSet 
+
     dataSet(ele) {
 	// this.log("dataSet()");
 
 	// type
-	for(let name of ["html", "text", "script"]){
+	for(let name of ["html", "text", "script", "markdown", "table"]){
 	    if(name == this.content().data().type){
 		const opName = "contentType" + firstUpper(name);
 		const contentEle = this.querySelectorBx(ele, opName);
@@ -4289,6 +4427,16 @@ class ContentEditor extends Editor {
 	    }
 	}
 
+	// lang of script
+	const eleLang = this.querySelectorBx(ele, "contentLang");
+	eleLang.value = this.content().data().lang || "";
+
+	// header and align of table
+	const eleHeader = this.querySelectorBx(ele, "contentTableHeader");
+	eleHeader.checked = this.content().data().header !== false;
+	const eleAlign = this.querySelectorBx(ele, "contentTableAlign");
+	eleAlign.value = (this.content().data().align || []).join(",");
+
 	// value
 	const eleTgt = this.querySelectorBx(ele, "editorContent");
 	eleTgt.textContent = this.content().data().value;
@@ -4334,6 +4482,41 @@ class ContentEditor extends Editor {
 	    this.result("changed", true);
 	}
 
+	// lang is only for script
+	const langEnter = data.type == "script" ? this.querySelectorBx(this.ele(), "contentLang").value.trim() : "";
+	if((data.lang || "") != langEnter){
+	    if(langEnter.length == 0){
+		delete data.lang;
+	    } else {
+		data.lang = langEnter;
+	    }
+	    this.result("changed", true);
+	}
+
+	// header and align are only for table
+	if(data.type == "table"){
+	    const headerEnter = this.querySelectorBx(this.ele(), "contentTableHeader").checked;
+	    if(data.header !== headerEnter){
+		data.header = headerEnter;
+		this.result("changed", true);
+	    }
+	    const alignEnter = this.querySelectorBx(this.ele(), "contentTableAlign").value
+		  .split(",").map((v) => v.trim());
+	    const alignJoin = alignEnter.join(",");
+	    if((data.align || []).join(",") != alignJoin){
+		if(alignJoin.length == 0){
+		    delete data.align;
+		} else {
+		    data.align = alignEnter;
+		}
+		this.result("changed", true);
+	    }
+	} else if(data.header !== undefined || data.align !== undefined){
+	    delete data.header;
+	    delete data.align;
+	    this.result("changed", true);
+	}
+
 	const eleValue = this.querySelectorBx(this.ele(), "editorContent");
 	const valueEnter = eleValue.value;
 
@@ -4621,5 +4804,58 @@ async function fetchPost (req, json) {
 	console.error("fatchPost: " + e);
     }
 } // end of function fetchPost
+
+// Contents drawn at once are rendered by the server in one request.
+// [[data, resolve], ...]
+let contentRenderQueue = [];
+
+// Resolve with html of the content, undefined if failed.
+function contentRender (data) {
+    return new Promise((resolve) => {
+	contentRenderQueue.push([data, resolve]);
+	// Sent after the draw queued all contents.
+	if(contentRenderQueue.length == 1){
+	    setTimeout(contentRenderFlush, 0);
+	}
+    });
+} // end of function contentRender
+
+async function contentRenderFlush () {
+    const queue = contentRenderQueue;
+    contentRenderQueue = [];
+
+    const contents = queue.map(([data, resolve]) => data);
+    const res = await fetchPost("content_render", {"contents" : contents});
+    const rendered = res && res.res == "rendered";
+    queue.forEach(([data, resolve], i) => {
+	resolve(rendered ? res.html[i] : undefined);
+    });
+} // end of function contentRenderFlush
+
+// Post the file itself as the body, the name is in the query.
+async function fetchPostFile (req, file) {
+    try {
+	const url = new URL(document.URL);
+	url.search = "?name=" + encodeURIComponent(file.name);
+	url.hash = "";
+	const response = await fetch(
+	    url,
+	    {
+		method: "POST",
+		headers: {
+		    "Content-Type": file.type || "application/octet-stream",
+		    "wc-request" : req,
+		},
+		body: file,
+	    }
+	);
+
+	let res_json = await response.json();
+	console.log("fetch res: " + JSON.stringify(res_json));
+	return res_json 
+    } catch (e) {
+	console.error("fetchPostFile: " + e);
+    }
+} // end of function fetchPostFile
 "####
 }
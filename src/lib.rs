@@ -9,6 +9,10 @@ mod js_css;
 mod thread_pool;
 mod wc_handler;
 
+/// Storage of pages, register a storage for a stor_root before wc_note()
+/// to keep pages other than on the filesystem.
+pub use wc_handler::page::page_utility::storage;
+
 // #[macro_use]
 // extern crate markup5ever;
 
@@ -40,6 +44,12 @@ pub fn wc_note(addr: &str, stor_root: &str, capa: usize) -> Result<()> {
     // If you change contents of wc.js or wc.css, you may recall wc_note() to apply the changes.
     js_css::setup();
 
+    // Allowlist of html to sanitize contents.
+    wc_handler::page::page_utility::html_sanitize::allowlist_load(stor_root);
+    wc_handler::page::page_utility::rev_store::store_load(stor_root);
+    // Dirs of exports and imports.
+    wc_handler::page::page_utility::io_roots::roots_load(stor_root);
+
     let listener = match TcpListener::bind(addr) {
         Ok(v) => v,
         Err(e) => {
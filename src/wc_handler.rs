@@ -17,6 +17,12 @@ pub fn response(stream: &mut TcpStream, stor_root: &str) -> Vec<u8> {
 pub fn handle_stream(stream: &mut TcpStream, stor_root: &str) -> Result<Vec<u8>, String> {
     let http_request = match http_request::HttpRequest::from(stream) {
         Ok(v) => v,
+        Err(http_request::RequestError::TooLarge) => {
+            return Ok(http_err("413 Payload Too Large"));
+        }
+        Err(http_request::RequestError::TimedOut) => {
+            return Ok(http_err("408 Request Timeout"));
+        }
         _ => {
             return Err("Failed to get http_request".to_string());
         }
@@ -63,21 +69,113 @@ fn http_hello() -> Vec<u8> {
     http_ok(&contents)
 }
 
+/// moved_to of a page can be changed or cleared later,
+/// so the redirect is temporary and not to be cached by browsers.
+fn http_moved(location: &str) -> Vec<u8> {
+    let headers = [("Location", location), ("Cache-Control", "no-cache")];
+    http_form_headers("302 Found", &headers, &[])
+}
+
 fn http_form(status: &str, contents: &Vec<u8>) -> Vec<u8> {
-    let header = format!(
-        // "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
-        "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n",
-        status,
-        contents.len()
-    );
+    http_form_headers(status, &[], contents)
+}
 
-    [header.into_bytes(), contents.clone()].concat()
+/// headers: additional headers except Content-Length
+fn http_form_headers(status: &str, headers: &[(&str, &str)], contents: &[u8]) -> Vec<u8> {
+    let mut header = format!("HTTP/1.1 {}\r\n", status);
+    for (name, value) in headers {
+        header = header + name + ": " + value + "\r\n";
+    }
+    header = header + &format!("Content-Length: {}\r\n\r\n", contents.len());
+
+    [header.into_bytes(), contents.to_vec()].concat()
 }
 
 fn handle_get(http_request: &http_request::HttpRequest, stor_root: &str) -> Result<Vec<u8>, ()> {
+    if is_private(http_request.path()) {
+        info!("private: {}", http_request.path());
+        return Ok(http_err("403 Forbidden"));
+    }
+
     let mut page = page::Page::new(&stor_root, http_request.path());
+    let attachment = page::page_utility::attachment::is_attachment_path(http_request.path());
+
+    // Redirect to where the page was moved to.
+    // "?stub" gets the moved page itself for maintenance.
+    if page.is_html() && !attachment && !http_request.query_flag("stub") {
+        match page.moved_location() {
+            Ok(Some(location)) => {
+                info!("moved to: {}", location);
+                return Ok(http_moved(&location));
+            }
+            Ok(None) => (),
+            Err(e) => {
+                error!("{}", e);
+                return Ok(http_err("508 Loop Detected"));
+            }
+        }
+    }
+
     // page.read().map_or(Err(()), |v| Ok(http_ok(v)))
-    page.source().map_or(Err(()), |v| Ok(http_ok(v)))
+    let is_rev = page.is_end_with_rev();
+    let is_page = page.is_html() && !attachment;
+    let page_path = page.page_path().to_string();
+    let source = page.source().ok_or(())?;
+    let mut content_type = content_type(http_request.path()).to_string();
+    let mut backlinks_inserted = None;
+    // Pages and their revs are sent as they are, in UTF-8 or a legacy encoding.
+    if content_type.starts_with("text/html") || is_rev {
+        let encoding = page::page_utility::page_encoding::detect(source);
+        content_type = format!("text/html; charset={}", encoding.name());
+        // Links coming in as they are now, not in revs.
+        if is_page {
+            backlinks_inserted = page::page_utility::page_backlinks::static_insert(
+                stor_root, &page_path, source, encoding,
+            );
+        }
+    }
+    let source = backlinks_inserted.as_ref().unwrap_or(source);
+    let mut headers = vec![
+        ("Content-Type", content_type.as_str()),
+        ("X-Content-Type-Options", "nosniff"),
+    ];
+    // An html or svg attached, before those were saved as ".txt", is not to run
+    // scripts on the origin of pages.
+    if attachment && page::page_utility::attachment::is_active(http_request.path()) {
+        headers[0].1 = "application/octet-stream";
+        headers.push(("Content-Disposition", "attachment"));
+    }
+    Ok(http_form_headers("200 OK", &headers, source))
+}
+
+/// Files in stor_root not to be served: configs, the git repository of
+/// rev_store "git" and dot files like temporary ones of write_atomic.
+fn is_private(path: &str) -> bool {
+    const CONFIGS: [&str; 3] = ["/wc_backup.json", "/wc_sanitize.json", "/wc_io.json"];
+    CONFIGS.contains(&path) || path.split('/').any(|name| name.starts_with('.'))
+}
+
+/// MIME type by the extension of path for pages, wc.js, wc.css and attachments.
+fn content_type(path: &str) -> &'static str {
+    let ext = path.rsplit_once('.').map_or("", |(_, ext)| ext);
+    match ext.to_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" | "md" | "csv" | "tsv" => "text/plain; charset=utf-8",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        _ => "application/octet-stream",
+    }
 }
 
 fn handle_post(
@@ -92,6 +190,9 @@ fn handle_post(
         None => return Err(format!("Failed to get wc_request: {}", http_request.path())),
     };
 
+    // Pages saved in the request are one commit in git.
+    let _git_operation = page::page_utility::rev_git::operation(stor_root, wc_request);
+
     if wc_request == "json_save" {
         return json_save(http_request, stor_root);
     }
@@ -112,6 +213,82 @@ fn handle_post(
         return handle_page_mainte(http_request, stor_root);
     }
 
+    if wc_request == "backup_clean" {
+        return handle_backup_clean(http_request, stor_root);
+    }
+
+    if wc_request == "revisions" {
+        return handle_revisions(http_request, stor_root);
+    }
+
+    if wc_request == "mainte_status" || wc_request == "mainte_cancel" {
+        return handle_mainte_job(http_request, wc_request);
+    }
+
+    if wc_request == "page_fsck" {
+        return handle_page_fsck(http_request, stor_root);
+    }
+
+    if wc_request == "page_orphans" {
+        return handle_page_orphans(http_request, stor_root);
+    }
+
+    if wc_request == "page_orphan_attach" {
+        return handle_page_orphan_attach(http_request, stor_root);
+    }
+
+    if wc_request == "search" {
+        return handle_search(http_request, stor_root);
+    }
+
+    if wc_request == "search_index_rebuild" {
+        return handle_search_index_rebuild(http_request, stor_root);
+    }
+
+    if wc_request == "backlinks" {
+        return handle_backlinks(http_request, stor_root);
+    }
+
+    if wc_request == "backlinks_index_rebuild" {
+        return handle_backlinks_index_rebuild(http_request, stor_root);
+    }
+
+    if wc_request == "page_export" {
+        return handle_page_export(http_request, stor_root);
+    }
+
+    if wc_request == "markdown_export" {
+        return handle_markdown_export(http_request, stor_root);
+    }
+
+    if wc_request == "markdown_import" {
+        return handle_markdown_import(http_request, stor_root);
+    }
+
+    if wc_request == "content_render" {
+        return handle_content_render(http_request);
+    }
+
+    if wc_request == "subsection_move" {
+        return handle_subsection_move(http_request, stor_root);
+    }
+
+    if wc_request == "subsection_promote" || wc_request == "page_demote" {
+        return handle_page_promote(http_request, stor_root, wc_request);
+    }
+
+    if wc_request.starts_with("subsection_") {
+        return handle_subsection_edit(http_request, stor_root, wc_request);
+    }
+
+    if wc_request == "attachment_upload" {
+        return handle_attachment_upload(http_request, stor_root);
+    }
+
+    if wc_request == "table_query" {
+        return handle_table_query(http_request);
+    }
+
     // temp
     Ok(http_hello())
 }
@@ -146,6 +323,20 @@ fn json_post(http_request: &http_request::HttpRequest) -> Result<json::JsonValue
     ))
 }
 
+/// Path of json_post["top_url"] on page_url, the top page of the storage if not given.
+fn top_path_from_post(json_post: &json::JsonValue, page_url: &url::Url) -> Result<String, String> {
+    let top_path = json_post["top_url"]
+        .as_str()
+        .map(|top_url| top_url.trim())
+        .filter(|top_url| !top_url.is_empty())
+        .unwrap_or(page::page_utility::PAGE_TOP_PATH);
+
+    let top_url = page_url
+        .join(top_path)
+        .or(Err(format!("Failed to join top_url: {}", top_path)))?;
+    Ok(top_url.path().to_string())
+}
+
 fn json_save(http_request: &http_request::HttpRequest, stor_root: &str) -> Result<Vec<u8>, String> {
     let mut page = page_post(http_request, stor_root)?;
 
@@ -154,18 +345,23 @@ fn json_save(http_request: &http_request::HttpRequest, stor_root: &str) -> Resul
         return Err(format!("Failed to read file: {}", page.file_path()));
     }
 
-    let json_post = match json_post(http_request) {
+    let mut json_post = match json_post(http_request) {
         Ok(v) => v,
         Err(e) => {
             return Ok(http_ok(&format!("{{\"res\":\"{}\"}}", e).into()));
         }
     };
 
+    // Scripts and event handlers in html contents are removed before saving.
+    let sanitized = page::page_utility::html_sanitize::page_json_sanitize(&mut json_post);
+
     let res: Vec<u8> = match page.json_replace_save(json_post) {
-        Ok(rev_uped) => format!(
-            r#"{{"res":"post_handle page_json_save", "rev_uped": {}}}"#,
-            rev_uped
-        )
+        Ok(rev_uped) => json::object! {
+            "res": "post_handle page_json_save",
+            "rev_uped": rev_uped,
+            "sanitized": sanitized,
+        }
+        .dump()
         .into(),
         Err(e) => {
             error!("fn json_save: {}", e);
@@ -296,12 +492,14 @@ fn handle_page_move(
     Ok(http_ok(&res.as_bytes().to_vec()))
 }
 
+/// Start mainte of the page tree from mainte_url as a background job.
+/// json_post: {"mainte_url": "/wc_top.html", "recursive": true}, recursive is optional.
+/// res: {"res": "mainte_started", "job": {"job_id": 1, ...}}
+/// See mainte_status for the job.
 fn handle_page_mainte(
     http_request: &http_request::HttpRequest,
     stor_root: &str,
 ) -> Result<Vec<u8>, String> {
-    //
-
     let json_post = json_post(http_request)?;
     let mainte_url = json_post["mainte_url"]
         .as_str()
@@ -317,14 +515,630 @@ fn handle_page_mainte(
         http_request.path()
     )))?;
 
-    let mut mainte_page = page::Page::new(stor_root, mainte_url.path());
+    let recursive = json_post["recursive"].as_bool().unwrap_or(true);
+    let res = match page::page_utility::page_mainte::mainte_job::mainte_start(
+        stor_root,
+        mainte_url.path(),
+        recursive,
+    ) {
+        Ok(job) => json::object! {"res": "mainte_started", "job": job.to_json()},
+        Err(e) => {
+            error!("{}", e);
+            json::object! {"Err": e}
+        }
+    };
 
-    let recursive = true;
-    let upres = None;
-    mainte_page.mainte(recursive, upres);
+    Ok(http_ok(&res.dump().into_bytes()))
+}
 
-    let res = format!(r#"{{"res":"maintained"}}"#);
-    info!("{}", res);
+/// Move backups out of the retention policy to the garbage, and purge the garbage.
+/// json_post: {"clean_url": "/wc_top.html", "recursive": true, "dry_run": true,
+///  "purge": false, "garbage_days": 30}, those except clean_url are optional.
+/// dry_run: list files to be moved or purged without changes.
+/// purge: purge after garbage_days, garbage_days in wc_backup.json if not given.
+/// res: {"res": "backup_cleaned", "dry_run": true, "moved": [...], "purged": [...],
+///  "policy": {"keep_last": 10, ...}}, policy is of clean_url.
+fn handle_backup_clean(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+) -> Result<Vec<u8>, String> {
+    use page::page_utility::page_mainte::page_backup_clean;
 
-    Ok(http_ok(&res.as_bytes().to_vec()))
+    let json_post = json_post(http_request)?;
+    let clean_url = json_post["clean_url"]
+        .as_str()
+        .ok_or(format!("Faild to get clean_url: {}", http_request.path()))?
+        .trim();
+    let recursive = json_post["recursive"].as_bool().unwrap_or(true);
+    let dry_run = json_post["dry_run"].as_bool().unwrap_or(false);
+    let purge = json_post["purge"].as_bool().unwrap_or(false);
+
+    let page_url = http_request
+        .url()
+        .ok_or(format!("Failed to get url: {}", http_request.path()))?;
+    let clean_url = page_url
+        .join(clean_url)
+        .or(Err(format!("Failed to join clean_url: {}", clean_url)))?;
+
+    let mut clean_page = page::Page::new(stor_root, clean_url.path());
+    let mut log = page_backup_clean::BackupCleanLog::new(dry_run);
+    page_backup_clean::page_backup_clean(&mut clean_page, recursive, &mut log);
+    if purge {
+        let force = true;
+        page_backup_clean::garbage_purge(
+            stor_root,
+            json_post["garbage_days"].as_usize(),
+            force,
+            &mut log,
+        )?;
+    }
+
+    let mut res = log.to_json();
+    res["res"] = "backup_cleaned".into();
+    res["policy"] = page::page_utility::page_mainte::backup_policy::Policies::load(stor_root)
+        .policy(clean_url.path())
+        .to_json();
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// The history of revs of this page, in either form of rev_store.
+/// res: {"res": "revisions", "rev": 12, "revs": [{"rev": 11, "size": 5032,
+///  "stored": 1410, "modified": 1760000000}]}
+/// GET of "page.html.11" gets the rev.
+fn handle_revisions(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+) -> Result<Vec<u8>, String> {
+    let mut page = page_post(http_request, stor_root)?;
+    let res = match page::page_utility::rev_store::revs_json(&mut page) {
+        Ok(mut res) => {
+            res["res"] = "revisions".into();
+            res
+        }
+        Err(e) => {
+            error!("{}", e);
+            json::object! {"Err": e}
+        }
+    };
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Status or cancel of mainte jobs.
+/// mainte_status json_post: {"job_id": 1}, all jobs if job_id is not given.
+/// res: {"res": "mainte_status", "jobs": [{"job_id": 1, "status": "running", ...}]}
+/// mainte_cancel json_post: {"job_id": 1}
+/// res: {"res": "mainte_cancelled", "jobs": [{"job_id": 1, "cancel": true, ...}]}
+fn handle_mainte_job(
+    http_request: &http_request::HttpRequest,
+    wc_request: &str,
+) -> Result<Vec<u8>, String> {
+    use page::page_utility::page_mainte::mainte_job;
+
+    let json_post = json_post(http_request)?;
+    let jobs = match json_post["job_id"].as_usize() {
+        Some(job_id) => match mainte_job::job(job_id) {
+            Some(job) => vec![job],
+            None => {
+                let res = json::object! {"Err": format!("No mainte job: {}", job_id)};
+                return Ok(http_ok(&res.dump().into_bytes()));
+            }
+        },
+        None if wc_request == "mainte_cancel" => {
+            return Err(format!("Faild to get job_id: {}", http_request.path()));
+        }
+        None => mainte_job::jobs(),
+    };
+
+    let res = if wc_request == "mainte_cancel" {
+        for job in jobs.iter() {
+            job.cancel();
+            info!("mainte job {} cancel requested", job.id());
+        }
+        "mainte_cancelled"
+    } else {
+        "mainte_status"
+    };
+    let jobs: Vec<json::JsonValue> = jobs.iter().map(|job| job.to_json()).collect();
+    let res = json::object! {"res": res, "jobs": jobs};
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Check consistency of the page tree from fsck_url.
+/// json_post: {"fsck_url": "/wc_top.html", "fix": false, "format": "json"}
+/// fix: repair what is safely repairable.
+/// format: "json" or "text"
+fn handle_page_fsck(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+) -> Result<Vec<u8>, String> {
+    let json_post = json_post(http_request)?;
+    let fsck_url = json_post["fsck_url"]
+        .as_str()
+        .ok_or(format!("Faild to get fsck_url: {}", http_request.path()))?
+        .trim();
+    let fix = json_post["fix"].as_bool().unwrap_or(false);
+
+    let page_url = http_request
+        .url()
+        .ok_or(format!("Failed to get url: {}", http_request.path()))?;
+
+    let fsck_url = page_url.join(fsck_url).or(Err(format!(
+        "Failed to join fsck_url: {}",
+        http_request.path()
+    )))?;
+
+    let mut fsck_page = page::Page::new(stor_root, fsck_url.path());
+    let fsck = page::page_utility::page_mainte::page_fsck::page_fsck(&mut fsck_page, fix);
+
+    if json_post["format"] == "text" {
+        return Ok(http_ok(&fsck.to_text().into_bytes()));
+    }
+
+    Ok(http_ok(&fsck.to_json().dump().into_bytes()))
+}
+
+/// List pages not reachable from the top page.
+/// json_post: {"top_url": "/wc_top.html"}, top_url is optional.
+fn handle_page_orphans(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+) -> Result<Vec<u8>, String> {
+    let json_post = json_post(http_request)?;
+    let page_url = http_request
+        .url()
+        .ok_or(format!("Failed to get url: {}", http_request.path()))?;
+    let top_path = top_path_from_post(&json_post, &page_url)?;
+
+    let orphans = page::page_utility::page_orphan::page_orphans(stor_root, &top_path);
+    let res = json::object! {
+        "res": "orphans",
+        "orphans": page::page_utility::page_orphan::orphans_json(&orphans),
+    };
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Attach an orphan page under this page.
+/// json_post: {"orphan_url": "child/child.html", "title": "child"}, title is optional.
+fn handle_page_orphan_attach(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+) -> Result<Vec<u8>, String> {
+    let json_post = json_post(http_request)?;
+    let orphan_url = json_post["orphan_url"]
+        .as_str()
+        .ok_or(format!("Faild to get orphan_url: {}", http_request.path()))?
+        .trim();
+    let title = json_post["title"].as_str();
+
+    let mut parent_page = page_post(http_request, stor_root)?;
+    let page_url = http_request
+        .url()
+        .ok_or(format!("Failed to get url: {}", http_request.path()))?;
+    let orphan_url = page_url
+        .join(orphan_url)
+        .or(Err(format!("Failed to join orphan_url: {}", orphan_url)))?;
+
+    let res = match page::page_utility::page_orphan::page_orphan_attach(
+        &mut parent_page,
+        &orphan_url,
+        title,
+    ) {
+        Ok(_) => json::object! {"res": "attached"},
+        Err(e) => {
+            error!("{}", e);
+            json::object! {"Err": e}
+        }
+    };
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Search pages.
+/// json_post: {"query": "words to search", "limit": 50}, limit is optional.
+fn handle_search(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+) -> Result<Vec<u8>, String> {
+    let json_post = json_post(http_request)?;
+    let query = json_post["query"]
+        .as_str()
+        .ok_or(format!("Faild to get query: {}", http_request.path()))?;
+    let limit = json_post["limit"]
+        .as_usize()
+        .unwrap_or(page::page_utility::page_search::SEARCH_LIMIT);
+
+    let hits = page::page_utility::page_search::search(stor_root, query, limit);
+    let res = json::object! {
+        "res": "search",
+        "hits": page::page_utility::page_search::hits_json(&hits),
+    };
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Rebuild the search index from the top page.
+/// json_post: {"top_url": "/wc_top.html"}, top_url is optional.
+fn handle_search_index_rebuild(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+) -> Result<Vec<u8>, String> {
+    let json_post = json_post(http_request)?;
+    let page_url = http_request
+        .url()
+        .ok_or(format!("Failed to get url: {}", http_request.path()))?;
+    let top_path = top_path_from_post(&json_post, &page_url)?;
+
+    let res = match page::page_utility::page_search::index_rebuild(stor_root, &top_path) {
+        Ok(num) => json::object! {"res": "rebuilt", "pages": num},
+        Err(e) => {
+            error!("{}", e);
+            json::object! {"Err": e}
+        }
+    };
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Return links coming in to this page.
+/// json_post: {"href": "#subsection1"}, href is optional to get links to the subsection.
+fn handle_backlinks(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+) -> Result<Vec<u8>, String> {
+    let json_post = json_post(http_request)?;
+    let fragment = json_post["href"]
+        .as_str()
+        .map(|href| href.trim().trim_start_matches('#'))
+        .filter(|fragment| !fragment.is_empty());
+
+    let page = page_post(http_request, stor_root)?;
+    let backlinks =
+        page::page_utility::page_backlinks::backlinks(stor_root, page.page_path(), fragment);
+    let res = json::object! {
+        "res": "backlinks",
+        "backlinks": page::page_utility::page_backlinks::backlinks_json(&backlinks),
+    };
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Rebuild the link index from the top page.
+/// json_post: {"top_url": "/wc_top.html"}, top_url is optional.
+fn handle_backlinks_index_rebuild(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+) -> Result<Vec<u8>, String> {
+    let json_post = json_post(http_request)?;
+    let page_url = http_request
+        .url()
+        .ok_or(format!("Failed to get url: {}", http_request.path()))?;
+    let top_path = top_path_from_post(&json_post, &page_url)?;
+
+    let res = match page::page_utility::page_backlinks::index_rebuild(stor_root, &top_path) {
+        Ok(num) => json::object! {"res": "rebuilt", "pages": num},
+        Err(e) => {
+            error!("{}", e);
+            json::object! {"Err": e}
+        }
+    };
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Export the page and pages under it as a static site.
+/// json_post: {"out_dir": "/site", "full": false}
+/// out_dir is in export_root of wc_io.json, see page_utility::io_roots.
+/// full is optional, only pages changed from the last export are exported if false.
+fn handle_page_export(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+) -> Result<Vec<u8>, String> {
+    let json_post = json_post(http_request)?;
+    let out_dir = json_post["out_dir"]
+        .as_str()
+        .map(|out_dir| out_dir.trim().trim_end_matches('/'))
+        .ok_or(format!("Failed to get out_dir: {}", json_post))?;
+    let full = json_post["full"].as_bool().unwrap_or(false);
+
+    let page = page_post(http_request, stor_root)?;
+    let res = match page::page_utility::page_export::page_export(
+        stor_root,
+        page.page_path(),
+        out_dir,
+        full,
+    ) {
+        Ok(export) => export.to_json(),
+        Err(e) => {
+            error!("{}", e);
+            json::object! {"Err": e}
+        }
+    };
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Convert the page to markdown.
+/// json_post: {"out_dir": "/docs", "recursive": false}
+/// Without out_dir, markdown of the page is returned in the response.
+/// With out_dir, markdown files are written into out_dir in export_root,
+/// recursive: pages under the page are written as well.
+fn handle_markdown_export(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+) -> Result<Vec<u8>, String> {
+    let json_post = json_post(http_request)?;
+    let out_dir = json_post["out_dir"]
+        .as_str()
+        .map(|out_dir| out_dir.trim().trim_end_matches('/'))
+        .filter(|out_dir| !out_dir.is_empty());
+    let recursive = json_post["recursive"].as_bool().unwrap_or(false);
+
+    let mut page = page_post(http_request, stor_root)?;
+    let res = match out_dir {
+        None => page::page_utility::markdown_export::page_markdown(&mut page)
+            .map(|markdown| json::object! {"res": "markdown", "markdown": markdown}),
+        Some(out_dir) => {
+            page::page_utility::markdown_export::page_markdown_export(&mut page, out_dir, recursive)
+                .map(|md_paths| json::object! {"res": "exported", "exported": md_paths})
+        }
+    };
+    let res = res.unwrap_or_else(|e| {
+        error!("{}", e);
+        json::object! {"Err": e}
+    });
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Create a page under the page from markdown.
+/// json_post: {"href": "child/child.html", "markdown": "# title ...", "title": "child"}
+/// title is optional, the first heading of level 1 is used if not given.
+/// To import a directory of .md files as a page tree:
+/// json_post: {"href": "notes/notes.html", "dir": "/notes"}
+/// dir is in import_root of wc_io.json, see page_utility::io_roots.
+fn handle_markdown_import(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+) -> Result<Vec<u8>, String> {
+    let json_post = json_post(http_request)?;
+    let href = json_post["href"]
+        .as_str()
+        .ok_or(format!("Failed to get href: {}", json_post))?;
+
+    let mut parent_page = page_post(http_request, stor_root)?;
+
+    let res = if let Some(dir) = json_post["dir"].as_str() {
+        match page::page_utility::io_roots::import_dir(dir) {
+            Ok(dir) => page::page_utility::markdown_import::dir_markdown_import(
+                &mut parent_page,
+                &dir,
+                href,
+            )
+            .to_json(),
+            Err(e) => {
+                error!("{}", e);
+                json::object! {"Err": e}
+            }
+        }
+    } else {
+        let markdown = json_post["markdown"]
+            .as_str()
+            .ok_or(format!("Failed to get markdown: {}", http_request.path()))?;
+        let title = json_post["title"].as_str();
+        match page::page_utility::markdown_import::page_markdown_import(
+            &mut parent_page,
+            href,
+            markdown,
+            title,
+        ) {
+            Ok(page_path) => json::object! {"res": "imported", "imported": [page_path]},
+            Err(e) => {
+                error!("{}", e);
+                json::object! {"Err": e}
+            }
+        }
+    };
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Render a content in html as the static page does.
+/// It is for contents that javascript can not draw by itself like "markdown".
+/// json_post: {"type": "markdown", "value": "# title"}
+/// res: {"res": "rendered", "html": "<div class=\"markdown\">..."}
+/// Contents drawn at once are rendered in a request,
+/// json_post: {"contents": [{"type": "markdown", ...}, {"type": "table", ...}]}
+/// res: {"res": "rendered", "html": ["<div class=\"markdown\">...", null]}, null if failed.
+fn handle_content_render(http_request: &http_request::HttpRequest) -> Result<Vec<u8>, String> {
+    let json_post = json_post(http_request)?;
+    let content_render = page::page_utility::page_dom_from_json::content_render;
+
+    if json_post["contents"].is_array() {
+        let html: Vec<json::JsonValue> = json_post["contents"]
+            .members()
+            .map(|content| match content_render(content) {
+                Ok(html) => html.into(),
+                Err(e) => {
+                    error!("{}", e);
+                    json::JsonValue::Null
+                }
+            })
+            .collect();
+        let res = json::object! {"res": "rendered", "html": html};
+        return Ok(http_ok(&res.dump().into_bytes()));
+    }
+
+    let res = match content_render(&json_post) {
+        Ok(html) => json::object! {"res": "rendered", "html": html},
+        Err(e) => {
+            error!("{}", e);
+            json::object! {"Err": e}
+        }
+    };
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Sort and filter rows of a "table" content and return it rendered.
+/// The value of the content is returned as well to save it if needed.
+/// json_post: {"content": {"type": "table", "value": "a,b\n1,2"},
+///   "sort": 0, "desc": false, "filter": "text", "column": 1}
+/// sort, filter are optional, filter is on all columns if column is not given.
+fn handle_table_query(http_request: &http_request::HttpRequest) -> Result<Vec<u8>, String> {
+    let json_post = json_post(http_request)?;
+
+    let content = &json_post["content"];
+    if content["type"] != "table" {
+        let res = json::object! {"Err": "Not table content"};
+        return Ok(http_ok(&res.dump().into_bytes()));
+    }
+
+    let content = match page::page_json::page_data::Content::from_json(content) {
+        Ok(v) => v,
+        Err(e) => {
+            let res = json::object! {"Err": format!("Malformed content{}", e)};
+            return Ok(http_ok(&res.dump().into_bytes()));
+        }
+    };
+    let mut table = page::page_utility::content_table::Table::from_content(&content);
+    if let Some(query) = json_post["filter"].as_str().filter(|v| !v.is_empty()) {
+        table.filter(json_post["column"].as_usize(), query);
+    }
+    if let Some(column) = json_post["sort"].as_usize() {
+        table.sort(column, json_post["desc"].as_bool().unwrap_or(false));
+    }
+
+    let res = json::object! {
+        "res": "table",
+        "html": table.to_html(),
+        "value": table.value(),
+        "rows": table.rows.len(),
+    };
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Attach a file to the page, it is stored in the directory of attachments.
+/// The body is multipart/form-data with a file,
+/// or the file itself with the name in the query: "/page.html?name=photo.png"
+/// Return the href to the file relative from the page.
+/// res: {"res": "attachment_upload", "name": "photo.png", "href": "page_files/photo.png", "size": 100}
+fn handle_attachment_upload(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+) -> Result<Vec<u8>, String> {
+    let mut page = page_post(http_request, stor_root)?;
+    let body = http_request
+        .body()
+        .ok_or(format!("Failed to get body: {}", http_request.path()))?;
+
+    let file = match http_request.content_type() {
+        Some(content_type) if content_type.starts_with("multipart/form-data") => {
+            page::page_utility::attachment::multipart_file(content_type, body)
+        }
+        _ => http_request
+            .query_value("name")
+            .map(|name| (name, body.to_vec()))
+            .ok_or("Failed to get name of the file".to_string()),
+    };
+
+    let res = match file.and_then(|(name, data)| {
+        page::page_utility::attachment::attachment_save(&mut page, &name, &data)
+    }) {
+        Ok(attachment) => attachment.to_json(),
+        Err(e) => {
+            error!("{}", e);
+            json::object! {"Err": e}
+        }
+    };
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Create, update, delete or reorder a subsection by id with the rev check.
+/// wc_request: "subsection_create", "subsection_update", "subsection_delete", "subsection_reorder"
+/// json_post: see subsection_edit.rs
+fn handle_subsection_edit(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+    wc_request: &str,
+) -> Result<Vec<u8>, String> {
+    let mut page = page_post(http_request, stor_root)?;
+    let json_post = json_post(http_request)?;
+
+    let subsection_edit = match wc_request {
+        "subsection_create" => page::page_utility::subsection_edit::subsection_create,
+        "subsection_update" => page::page_utility::subsection_edit::subsection_update,
+        "subsection_delete" => page::page_utility::subsection_edit::subsection_delete,
+        "subsection_reorder" => page::page_utility::subsection_edit::subsection_reorder,
+        _ => return Err(format!("Unknown wc_request: {}", wc_request)),
+    };
+
+    let res = match subsection_edit(&mut page, &json_post) {
+        Ok(res) => res,
+        Err(e) => {
+            error!("{}", e);
+            json::object! {"Err": e}
+        }
+    };
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Move a subsection with its children from the page to another page.
+/// json_post: {"rev": 3, "id": 5, "dest_url": "/other/other.html", "dest_rev": 8, "parent": 0, "index": 0}
+fn handle_subsection_move(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+) -> Result<Vec<u8>, String> {
+    let mut page = page_post(http_request, stor_root)?;
+    let json_post = json_post(http_request)?;
+    let page_url = http_request
+        .url()
+        .ok_or(format!("Failed to get url: {}", http_request.path()))?;
+
+    let res = match page::page_utility::subsection_move::subsection_move(
+        &mut page, &page_url, &json_post,
+    ) {
+        Ok(res) => res,
+        Err(e) => {
+            error!("{}", e);
+            json::object! {"Err": e}
+        }
+    };
+
+    Ok(http_ok(&res.dump().into_bytes()))
+}
+
+/// Promote a subsection to a child page, or demote a child page into the page.
+/// subsection_promote json_post: {"rev": 3, "id": 5, "href": "sub/sub.html", "title": "Sub"}
+/// page_demote json_post: {"rev": 4, "id": 5, "child_rev": 2, "delete": false}
+fn handle_page_promote(
+    http_request: &http_request::HttpRequest,
+    stor_root: &str,
+    wc_request: &str,
+) -> Result<Vec<u8>, String> {
+    let mut page = page_post(http_request, stor_root)?;
+    let json_post = json_post(http_request)?;
+    let page_url = http_request
+        .url()
+        .ok_or(format!("Failed to get url: {}", http_request.path()))?;
+
+    let res = if wc_request == "subsection_promote" {
+        page::page_utility::page_promote::subsection_promote(&mut page, &page_url, &json_post)
+    } else {
+        page::page_utility::page_promote::page_demote(&mut page, &page_url, &json_post)
+    };
+    let res = match res {
+        Ok(res) => res,
+        Err(e) => {
+            error!("{}", e);
+            json::object! {"Err": e}
+        }
+    };
+
+    Ok(http_ok(&res.dump().into_bytes()))
 }
@@ -1,19 +1,30 @@
-use std::io::Read;
+use std::io::{Read, Write};
 use std::net::TcpStream;
 // use tracing::info; //  event, instrument, span, Level
 use tracing::error; //  event, info, instrument, span, Level
 
+/// Why a request was not read.
+pub enum RequestError {
+    Invalid,
+    // The body is over BODY_MAX, it is not read.
+    TooLarge,
+    // Nothing came in READ_TIMEOUT, a client stopped sending the request.
+    TimedOut,
+}
+
 pub struct HttpRequest {
     method: String,
     path: String,
+    query: Option<String>,
     wc_request: Option<String>,
     host: Option<String>,
+    content_type: Option<String>,
     body: Option<Vec<u8>>,
 }
 
 impl HttpRequest {
-    pub fn from(stream: &mut TcpStream) -> Result<HttpRequest, ()> {
-        let stream_data = stream_read(stream);
+    pub fn from(stream: &mut TcpStream) -> Result<HttpRequest, RequestError> {
+        let stream_data = stream_read(stream)?;
 
         let mut headers = [httparse::EMPTY_HEADER; 64];
         let mut request = httparse::Request::new(&mut headers);
@@ -25,27 +36,33 @@ impl HttpRequest {
             },
             Err(_) => {
                 error!("request.parse Failed to parse request.");
-                return Err(());
+                return Err(RequestError::Invalid);
             }
         };
 
         // request.path
-        let path = match request.path {
-            Some(path) => path.to_string(),
-            None => return Err(()),
+        // Split "/page.html?stub" into the path and the query.
+        let (path, query) = match request.path {
+            Some(path) => match path.split_once('?') {
+                Some((path, query)) => (path.to_string(), Some(query.to_string())),
+                None => (path.to_string(), None),
+            },
+            None => return Err(RequestError::Invalid),
         };
 
         // request.method
         let method = match request.method {
             Some(method) => method.to_string(),
-            None => return Err(()),
+            None => return Err(RequestError::Invalid),
         };
 
         let mut http_request = HttpRequest {
             method,
             path,
+            query,
             wc_request: None,
             host: None,
+            content_type: None,
             body: None,
         };
 
@@ -71,6 +88,14 @@ impl HttpRequest {
             }
         }
 
+        // Content-Type // ex.: multipart/form-data; boundary=xxx
+        if let Some(v) = head_value(&request, "Content-Type") {
+            let v = v.to_vec();
+            if let Ok(v) = String::from_utf8(v) {
+                http_request.content_type.replace(v);
+            }
+        }
+
         // body
         if body_offset.is_some() {
             let body = stream_data[body_offset.unwrap()..].to_vec();
@@ -88,11 +113,38 @@ impl HttpRequest {
         &self.path
     }
 
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    /// Return true if the query has name as a flag or as a key.
+    /// ex. "/page.html?stub" or "/page.html?stub=1" for name "stub"
+    pub fn query_flag(&self, name: &str) -> bool {
+        self.query().is_some_and(|query| {
+            query
+                .split('&')
+                .any(|item| item.split('=').next() == Some(name))
+        })
+    }
+
+    /// Value of name in the query, percent-decoded.
+    /// ex. "/page.html?name=a%20b.png" for name "name" is "a b.png"
+    pub fn query_value(&self, name: &str) -> Option<String> {
+        let query = self.query()?;
+        url::form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.to_string())
+    }
+
     pub fn wc_request(&self) -> Option<&str> {
         self.wc_request.as_ref().map(|v| v.as_str())
     }
 
-    fn _body(&self) -> Option<&Vec<u8>> {
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    pub fn body(&self) -> Option<&Vec<u8>> {
         self.body.as_ref()
     }
 
@@ -118,21 +170,64 @@ impl HttpRequest {
     }
 }
 
-fn stream_read(stream: &mut TcpStream) -> Vec<u8> {
+fn stream_read(stream: &mut TcpStream) -> Result<Vec<u8>, RequestError> {
     // const MESSAGE_SIZE: usize = 5;
     const MESSAGE_SIZE: usize = 1024;
     let mut rx_bytes = [0u8; MESSAGE_SIZE];
     let mut stream_data: Vec<u8> = vec![];
+    // Length of the whole request when Content-Length is found.
+    let mut request_len: Option<usize> = None;
+
+    // Not to keep a worker on a client stopped sending.
+    if let Err(e) = stream.set_read_timeout(Some(READ_TIMEOUT)) {
+        error!("stream_read: set_read_timeout: {:?}", e);
+        return Err(RequestError::Invalid);
+    }
 
     loop {
         match stream.read(&mut rx_bytes) {
+            Ok(0) => break,
             Ok(bytes_read) => {
                 stream_data.extend_from_slice(&rx_bytes[..bytes_read]);
-                if bytes_read < MESSAGE_SIZE {
-                    break;
+
+                if request_len.is_none() {
+                    request_len = request_len_get(&stream_data);
+                    // Not to read nor handle a part of it.
+                    if let Some(len) = request_len.filter(|len| BODY_MAX < *len) {
+                        error!("stream_read: body too large: {}", len);
+                        return Err(RequestError::TooLarge);
+                    }
+                    // Clients like curl wait the body for "100 Continue" on large body.
+                    if request_len.is_some() && expect_continue(&stream_data) {
+                        let _ = stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n");
+                    }
+                }
+
+                match request_len {
+                    // A body like an upload comes in many reads,
+                    // read until the end of it.
+                    Some(len) => {
+                        if len <= stream_data.len() {
+                            break;
+                        }
+                    }
+                    None => {
+                        if bytes_read < MESSAGE_SIZE {
+                            break;
+                        }
+                    }
                 }
             }
 
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                error!("stream_read: timed out: {} bytes read", stream_data.len());
+                return Err(RequestError::TimedOut);
+            }
             Err(e) => {
                 error!("stream_read: {:?}", e);
                 break;
@@ -140,11 +235,48 @@ fn stream_read(stream: &mut TcpStream) -> Vec<u8> {
         }
     }
 
-    stream_data
+    Ok(stream_data)
+}
+
+/// Limit of the body size of a request.
+const BODY_MAX: usize = 64 * 1024 * 1024;
+
+/// Limit of the wait for each read of a request.
+const READ_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Headers length + Content-Length if headers are complete and have Content-Length.
+fn request_len_get(stream_data: &[u8]) -> Option<usize> {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut request = httparse::Request::new(&mut headers);
+    let httparse::Status::Complete(headers_len) = request.parse(stream_data).ok()? else {
+        return None;
+    };
+
+    let content_len = head_value(&request, "Content-Length")?;
+    let content_len = std::str::from_utf8(content_len)
+        .ok()?
+        .trim()
+        .parse::<usize>()
+        .ok()?;
+    Some(headers_len + content_len)
+}
+
+fn expect_continue(stream_data: &[u8]) -> bool {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut request = httparse::Request::new(&mut headers);
+    if request.parse(stream_data).is_err() {
+        return false;
+    }
+    head_value(&request, "Expect").is_some_and(|v| v.eq_ignore_ascii_case(b"100-continue"))
 }
 
 fn head_value<'a>(request: &'a httparse::Request, name: &str) -> Option<&'a [u8]> {
-    match request.headers.iter().find(|&&h| h.name == name) {
+    // Header names are not case-sensitive.
+    match request
+        .headers
+        .iter()
+        .find(|&&h| h.name.eq_ignore_ascii_case(name))
+    {
         Some(header) => Some(header.value),
         None => None,
     }
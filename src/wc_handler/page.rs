@@ -2,7 +2,6 @@ use html5ever::driver::parse_document; // , serialize
 use html5ever::tendril::TendrilSink; // parse_document(...).one() needs this
 use markup5ever_rcdom::RcDom;
 use std::cell::RefCell;
-use std::fs;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use tracing::{error, info}; //  error, event, info_span, instrument, span, Level debug , warn,// ;
@@ -20,6 +19,8 @@ pub struct Page {
     source: Option<Option<Vec<u8>>>,
     dom: Option<Option<RcDom>>,
     json: Option<Option<page_json::PageJson>>,
+    // Encoding of source detected on dom_parse, pages are saved in UTF-8.
+    encoding: Option<&'static encoding_rs::Encoding>,
 }
 
 impl Page {
@@ -37,6 +38,7 @@ impl Page {
             source: None,
             dom: None,
             json: None,
+            encoding: None,
         }
     }
 
@@ -53,6 +55,7 @@ impl Page {
 
         let mut page = Page::new(stor_root, page_path);
         page.source.replace(Some(source));
+        page.encoding.replace(encoding_rs::UTF_8);
 
         page.dom.replace(Some(page_dom));
 
@@ -80,10 +83,22 @@ impl Page {
         reg.is_match(&self.page_path)
     }
 
+    /// Return true if the page is a html page, not a backup with rev.
+    pub fn is_html(&self) -> bool {
+        self.page_path.contains(".htm") && !self.is_end_with_rev()
+    }
+
     // pub fn read(&mut self) -> Result<&Vec<u8>, ()> {
     fn read(&mut self) -> Result<&Vec<u8>, ()> {
         let file_path = &self.file_path();
-        match fs::read(&file_path) {
+        // A rev may be compressed.
+        let source = if self.is_end_with_rev() {
+            page_utility::rev_store::rev_page_read(self)
+                .map_err(|_| std::io::Error::from(std::io::ErrorKind::NotFound))
+        } else {
+            page_utility::storage::read(Path::new(file_path))
+        };
+        match source {
             Ok(s) => {
                 self.source.replace(Some(s));
                 return Ok(self.source.as_ref().unwrap().as_ref().unwrap());
@@ -128,7 +143,11 @@ impl Page {
             }
         };
 
-        let source = String::from_utf8(source).or(Err(()))?;
+        let (source, encoding) = page_utility::page_encoding::decode(&source);
+        if encoding != encoding_rs::UTF_8 {
+            info!("{} is in {}", self.page_path, encoding.name());
+        }
+        self.encoding.replace(encoding);
         let dom =
             parse_document(markup5ever_rcdom::RcDom::default(), Default::default()).one(source);
 
@@ -146,11 +165,34 @@ impl Page {
         Some(self.dom.as_ref().unwrap().as_ref().unwrap())
     }
 
+    /// Encoding of the source, None if the source was not parsed as html.
+    pub fn encoding(&mut self) -> Option<&'static encoding_rs::Encoding> {
+        self.dom()?;
+        self.encoding
+    }
+
     fn json_parse(&mut self) -> Result<(), ()> {
         let dom = self.dom().ok_or(())?;
         match page_utility::json_from_dom(&dom.document) {
-            Some(v) => {
-                let page_json = page_json::PageJson::from(v);
+            Some(mut v) => {
+                // Pages of older versions are migrated on read
+                // and saved in the current form when those are saved next.
+                let migrated = match page_json::migration::migrate(&mut v) {
+                    Ok(versions) => !versions.is_empty(),
+                    Err(e) => {
+                        error!("Failed to migrate {}: {}", self.page_path, e);
+                        false
+                    }
+                };
+
+                // Malformed pages are read as they are, fsck reports those.
+                if let Err(e) = page_json::page_data::PageData::from_json(&v) {
+                    error!("Malformed page json of {}: {}", self.page_path, e);
+                }
+                let mut page_json = page_json::PageJson::from(v);
+                if migrated {
+                    page_json.may_changed_set();
+                }
                 self.json.replace(Some(page_json));
                 return Ok(());
             }
@@ -188,8 +230,10 @@ impl Page {
     /// rev no (json_data2["data"]["page"]["rev"]) should match with the current no.
     /// json_data2["data"]["page"]["rev"] will be replaced with page_json.rev_plus_one()
     /// Return Ok(rev_plus_one), new rev number
-    pub fn json_replace_save(&mut self, json_data2: json::JsonValue) -> Result<usize, String> {
+    pub fn json_replace_save(&mut self, mut json_data2: json::JsonValue) -> Result<usize, String> {
         page_utility::json_rev_match(self, &json_data2)?;
+        // json_data2 from a page opened before migration.
+        page_json::migration::migrate(&mut json_data2)?;
 
         let page_json = self.json_mut().ok_or("Failed to get page_json.")?;
         page_json.value_replace(json_data2);
@@ -234,41 +278,36 @@ impl Page {
             .or(Err("Failed to repace rev one up.".to_string()))
     }
 
-    fn path_rev(&mut self) -> Result<String, ()> {
-        let page_json = self.json().ok_or(())?;
-        let rev = page_json.rev().ok_or(())?;
-
-        let path_rev = self.path_rev_form(rev);
-        match path_rev.to_str() {
-            Some(v) => Ok(v.to_string()),
-            None => Err(()),
-        }
-    }
-
     /// This function takes rev in arguments, not consering with self.json.rev().
     /// This is only for a path format with rev numaber.
     /// ./stor_root/page_path.html + "." + rev_no
+    /// The rev may be stored in another form, see page_utility::rev_store.
     pub fn path_rev_form(&self, rev: usize) -> PathBuf {
         // self.file_path() + "." + rev.to_string().as_str()
         let path = self.file_path() + "." + rev.to_string().as_str();
         PathBuf::from(path)
     }
 
-    /// Save self.source value to self.path_rev().
-    /// Return self.path_revp() on sucsess as Ok
+    /// Save self.source value as the rev of self.json through rev_store.
+    /// In git, the page saved is committed by page_utility::rev_git instead.
+    /// Return the path saved on sucsess as Ok
     /// Return Err in fail.
     pub fn file_save_rev(&mut self) -> Result<String, String> {
-        let path_rev = match self.path_rev() {
-            Ok(v) => v,
-            Err(()) => return Err(format!("Failed to get path_ref: {}", &self.file_path())),
+        let rev = match self.json().and_then(|page_json| page_json.rev()) {
+            Some(v) => v,
+            None => return Err(format!("Failed to get rev: {}", &self.file_path())),
         };
 
+        if page_utility::rev_store::is_git() {
+            return page_utility::rev_git::page_saved(self, rev);
+        }
+
         let source = match self.source() {
-            Some(v) => v,
+            Some(v) => v.clone(),
             None => return Err(format!("Failed to get source: {}", &self.file_path())),
         };
 
-        page_utility::fs_write(&path_rev, source)
+        page_utility::rev_store::rev_write(self, rev, &source)
     }
 
     /// Save the file and its backup file wit rev suffix.
@@ -289,11 +328,25 @@ impl Page {
             // saved = false;
         }
 
-        if saved {
-            Ok(())
-        } else {
-            Err(())
+        if !saved {
+            return Err(());
+        }
+
+        page_utility::page_search::index_update(self);
+        page_utility::page_backlinks::index_update(self);
+
+        Ok(())
+    }
+
+    /// Save the backup and update indexes of the file saved by file_save,
+    /// for pages saved together, see page_utility::pages_save_rev_up.
+    pub fn file_saved_rev(&mut self) {
+        match self.file_save_rev() {
+            Ok(v) => info!("Saved: {}", v),
+            Err(e) => error!("{}", e),
         }
+        page_utility::page_search::index_update(self);
+        page_utility::page_backlinks::index_update(self);
     }
 
     pub fn json_subsections_data_exists(&mut self) -> bool {
@@ -305,10 +358,16 @@ impl Page {
         &mut self,
         recursive: bool,
         log: Option<Rc<RefCell<page_utility::page_mainte::page_form_update::Log>>>,
-    ) {
+    ) -> page_utility::page_mainte::MainteLog {
         // page_utility::
         // page_utility::page_mainte::page_mainte(self, recursive, log);
-        page_utility::page_mainte(self, recursive, log);
+        page_utility::page_mainte(self, recursive, log)
+    }
+
+    /// Return where this page was finally moved to following moved_to chain.
+    /// Return Ok(None) if the page was not moved.
+    pub fn moved_location(&mut self) -> Result<Option<String>, String> {
+        page_utility::moved_to_chain(self)
     }
 
     /// Move this page to dest_url as a child of parent_url.
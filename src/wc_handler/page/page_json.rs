@@ -1,7 +1,17 @@
 use std::str::FromStr;
 use tracing::error;
+
+pub mod migration;
+pub mod page_data;
+use page_data::PageData;
 // {error, event, info, instrument, span, Level, Node}
 
+// PageJson keeps page json as it was read, a malformed one as well for fsck.
+// The data of a page is read and edited as PageData by page_data() and
+// page_data_replace(). value() and value_mut() are for the whole json as it is:
+// dom from json and back, importers building one, page_move converting hrefs
+// of a tree and fsck fixing what PageData does not read.
+
 /// If PageJson was borrowed as mutable, consider as the contents of PageJson.data is changed.
 pub struct PageJson {
     data: Option<json::JsonValue>,
@@ -27,7 +37,10 @@ impl PageJson {
         self.data.as_ref()
     }
 
-    pub fn value_mut(&mut self) -> Option<&mut json::JsonValue> {
+    /// Raw json to edit, for what PageData does not model: importers building
+    /// the json from dom and fsck fixing a malformed one. Edit page data by
+    /// page_data() and page_data_replace() otherwise.
+    pub(crate) fn value_mut(&mut self) -> Option<&mut json::JsonValue> {
         match self.data.as_mut() {
             Some(v) => {
                 self.may_changed = true;
@@ -50,10 +63,25 @@ impl PageJson {
         self.data.replace(json_value);
     }
 
+    /// Typed data of the page json, Err tells where it is malformed.
+    pub fn page_data(&self) -> Result<PageData, String> {
+        PageData::from_json(self.value().ok_or("No page json".to_string())?)
+    }
+
+    /// Replace the page json with page_data.
+    pub fn page_data_replace(&mut self, page_data: &PageData) {
+        self.value_replace(page_data.to_json());
+    }
+
     pub fn may_changed(&self) -> bool {
         self.may_changed
     }
 
+    /// Consider as changed, eg: migrated on read.
+    pub fn may_changed_set(&mut self) {
+        self.may_changed = true;
+    }
+
     pub fn may_changed_clear(&mut self) {
         self.may_changed = false;
     }
@@ -123,8 +151,12 @@ impl PageJson {
         }
 
         subsections[id_str] = json::object! {
-            "parent_id" : *parent_id,
+            "parent" : *parent_id,
             "id":  id,
+            "title" : "",
+            "href" : "",
+            "content" : [],
+            "child" : [],
         };
 
         // Set new subsection's id to parent subsection
@@ -139,6 +171,146 @@ impl PageJson {
         })
     }
 
+    pub fn subsection_by_id(&mut self, id: &usize) -> Option<Subsection<'_>> {
+        let subsections = self.subsections()?;
+        if !subsections.get(&id.to_string())?.is_object() {
+            return None;
+        }
+
+        self.may_changed = true;
+        Some(Subsection {
+            page_json: self.data.as_mut().unwrap(),
+            id: *id,
+        })
+    }
+
+    /// Ids of the subsection and its descendants.
+    pub fn subsection_descendants(&self, id: &usize) -> Vec<usize> {
+        let Some(subsections) = self.subsections() else {
+            return vec![];
+        };
+
+        let mut ids = vec![];
+        let mut id_s = vec![*id];
+        while let Some(id) = id_s.pop() {
+            // Avoid endless loop on broken data.
+            if ids.contains(&id) {
+                continue;
+            }
+            ids.push(id);
+            if let Some(subsection) = subsections.get(&id.to_string()) {
+                id_s.extend(
+                    subsection["child"]
+                        .members()
+                        .filter_map(|v| to_usize(v).ok()),
+                );
+            }
+        }
+        ids
+    }
+
+    /// Parent id of the subsection from "child" of subsections.
+    pub fn subsection_parent_id(&self, id: &usize) -> Option<usize> {
+        let subsections = self.subsections()?;
+        subsections.iter().find_map(|(parent_id, subsection)| {
+            subsection["child"]
+                .members()
+                .any(|v| to_usize(v).is_ok_and(|v| v == *id))
+                .then(|| usize::from_str(parent_id).ok())
+                .flatten()
+        })
+    }
+
+    /// Delete the subsection with its descendants, return ids deleted.
+    /// The subsection of id 0 is the root, it can not be deleted.
+    pub fn subsection_delete(&mut self, id: &usize) -> Result<Vec<usize>, String> {
+        if *id == 0 {
+            return Err("The root subsection can not be deleted".to_string());
+        }
+        if self
+            .subsections()
+            .and_then(|v| v.get(&id.to_string()))
+            .is_none()
+        {
+            return Err(format!("No subsection of id {}", id));
+        }
+
+        let ids = self.subsection_descendants(id);
+        let parent_id = self.subsection_parent_id(id);
+
+        let subsections = self
+            .subsections_mut()
+            .ok_or("Failed to get subsections".to_string())?;
+        for id in ids.iter() {
+            subsections.remove(&id.to_string());
+        }
+        if let Some(parent_id) = parent_id {
+            if let Some(parent) = subsections.get_mut(&parent_id.to_string()) {
+                child_remove(parent, id);
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// Put the subsection as a child of parent_id at index of its children.
+    /// index: None or larger than the number of children to put it at the last.
+    pub fn subsection_place(
+        &mut self,
+        id: &usize,
+        parent_id: &usize,
+        index: Option<usize>,
+    ) -> Result<(), String> {
+        if *id == 0 {
+            return Err("The root subsection can not be moved".to_string());
+        }
+        if self
+            .subsections()
+            .and_then(|v| v.get(&id.to_string()))
+            .is_none()
+        {
+            return Err(format!("No subsection of id {}", id));
+        }
+        if self
+            .subsections()
+            .and_then(|v| v.get(&parent_id.to_string()))
+            .is_none()
+        {
+            return Err(format!("No subsection of id {}", parent_id));
+        }
+        if self.subsection_descendants(id).contains(parent_id) {
+            return Err(format!(
+                "Subsection {} can not be under itself {}",
+                id, parent_id
+            ));
+        }
+
+        let parent_id_org = self.subsection_parent_id(id);
+
+        let subsections = self
+            .subsections_mut()
+            .ok_or("Failed to get subsections".to_string())?;
+        if let Some(parent_id_org) = parent_id_org {
+            if let Some(parent) = subsections.get_mut(&parent_id_org.to_string()) {
+                child_remove(parent, id);
+            }
+        }
+
+        let parent = subsections
+            .get_mut(&parent_id.to_string())
+            .ok_or(format!("No subsection of id {}", parent_id))?;
+        let mut child: Vec<json::JsonValue> = parent["child"].members().cloned().collect();
+        let index = index.unwrap_or(child.len()).min(child.len());
+        child.insert(index, (*id).into());
+        parent["child"] = json::JsonValue::Array(child);
+
+        if let Some(subsection) = subsections.get_mut(&id.to_string()) {
+            subsection["parent"] = (*parent_id).into();
+        }
+
+        Ok(())
+    }
+
     pub fn subsection_by_name(&mut self, href_arg: &str) -> Option<Subsection> {
         let subsections = self.subsections()?;
 
@@ -230,6 +402,25 @@ impl PageJson {
 
         moveto.as_str().and_then(|v| Some(v.to_string()))
     }
+
+    /// Return true if the page is marked as private
+    /// by value["data"]["page"]["private"] = true.
+    /// Private pages are not exported as a static site.
+    pub fn is_private(&self) -> bool {
+        self.value()
+            .and_then(|value| value["data"]["page"]["private"].as_bool())
+            .unwrap_or(false)
+    }
+}
+
+/// Remove id from "child" of the subsection.
+fn child_remove(subsection: &mut json::JsonValue, id: &usize) {
+    let child: Vec<json::JsonValue> = subsection["child"]
+        .members()
+        .filter(|v| !to_usize(v).is_ok_and(|v| v == *id))
+        .cloned()
+        .collect();
+    subsection["child"] = json::JsonValue::Array(child);
 }
 
 pub fn to_usize(v: &json::JsonValue) -> Result<usize, String> {
@@ -301,7 +492,8 @@ pub fn page_json_plain() -> json::JsonValue {
             // "version" : "0.0.1",
             // "version" : "0.0.2",
             // "version" : "0.0.3",
-            "version" : "0.0.4",
+            // "version" : "0.0.4",
+            "version" : migration::VERSION,
         },
 
         "data" : {
@@ -0,0 +1,225 @@
+// Migrations of page json keyed on system.version.
+//
+// Each migration upgrades page json from a version to the next one,
+// those are applied in a chain up to VERSION when a page is read.
+// page_mainte rewrites pages to VERSION saving a rev on each migration.
+//
+// Add a migration at the end of MIGRATIONS and change VERSION
+// when the form of page json changes.
+
+/// The version of page json written by this system.
+pub const VERSION: &str = "0.0.5";
+
+pub struct Migration {
+    pub from: &'static str,
+    pub to: &'static str,
+    migrate: fn(&mut json::JsonValue) -> Result<(), String>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: "0.0.1",
+        to: "0.0.2",
+        migrate: migrate_none,
+    },
+    Migration {
+        from: "0.0.2",
+        to: "0.0.3",
+        migrate: migrate_none,
+    },
+    Migration {
+        from: "0.0.3",
+        to: "0.0.4",
+        migrate: migrate_none,
+    },
+    Migration {
+        from: "0.0.4",
+        to: "0.0.5",
+        migrate: migrate_0_0_5,
+    },
+];
+
+/// system.version of page json.
+pub fn version(value: &json::JsonValue) -> Option<&str> {
+    value["system"]["version"].as_str()
+}
+
+/// Migrations to apply to get value to VERSION.
+/// Err if no migration from the version of value.
+pub fn migrations(value: &json::JsonValue) -> Result<Vec<&'static Migration>, String> {
+    let mut version = version(value).ok_or("No system.version".to_string())?;
+    let mut migrations = vec![];
+    while version != VERSION {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|migration| migration.from == version)
+            .ok_or(format!("No migration from version {}", version))?;
+        migrations.push(migration);
+        version = migration.to;
+    }
+    Ok(migrations)
+}
+
+impl Migration {
+    /// Migrate value from self.from to self.to.
+    pub fn apply(&self, value: &mut json::JsonValue) -> Result<(), String> {
+        if version(value) != Some(self.from) {
+            return Err(format!("version {:?} is not {}", version(value), self.from));
+        }
+        (self.migrate)(value).map_err(|e| format!("migration to {}: {}", self.to, e))?;
+        value["system"]["version"] = self.to.into();
+        Ok(())
+    }
+}
+
+/// Migrate value to VERSION.
+/// Return versions migrated to, empty if value is VERSION already.
+/// value is not changed if Err.
+pub fn migrate(value: &mut json::JsonValue) -> Result<Vec<&'static str>, String> {
+    let migrations = migrations(value)?;
+    if migrations.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut migrated = value.clone();
+    for migration in migrations.iter() {
+        migration.apply(&mut migrated)?;
+    }
+    *value = migrated;
+
+    Ok(migrations.iter().map(|migration| migration.to).collect())
+}
+
+/// The form did not change but the version, 0.0.1 to 0.0.4.
+fn migrate_none(_value: &mut json::JsonValue) -> Result<(), String> {
+    Ok(())
+}
+
+/// 0.0.4 to 0.0.5
+/// "parent_id" of subsections to "parent",
+/// navi of {"title": "href"} to ["title", "href"].
+fn migrate_0_0_5(value: &mut json::JsonValue) -> Result<(), String> {
+    for (_, subsection) in value["data"]["subsection"]["data"].entries_mut() {
+        let parent_id = subsection.remove("parent_id");
+        if subsection["parent"].is_null() && !parent_id.is_null() {
+            subsection["parent"] = parent_id;
+        }
+    }
+
+    for navi in value["data"]["navi"].members_mut() {
+        let json::JsonValue::Object(object) = navi else {
+            continue;
+        };
+        let (title, href) = object
+            .iter()
+            .next()
+            .map(|(title, href)| (title.to_string(), href.clone()))
+            .ok_or("empty navi".to_string())?;
+        *navi = json::array![title, href];
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page_json_0_0_4() -> json::JsonValue {
+        json::object! {
+            "system": {"version": "0.0.4"},
+            "data": {
+                "page": {"title": "t", "rev": 3, "moved_to": ""},
+                "navi": [{"top": "../top.html"}, ["t", ""]],
+                "subsection": {
+                    "id": {"id_next": 2, "id_notinuse": []},
+                    "data": {
+                        "0": {"parent_id": 0, "id": 0, "title": "", "href": "", "content": [], "child": [1]},
+                        "1": {"parent_id": 0, "id": 1, "title": "s", "href": "#s", "content": [], "child": []}
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn migrate_0_0_5_parent_and_navi() {
+        let mut value = page_json_0_0_4();
+        migrate_0_0_5(&mut value).unwrap();
+
+        let subsection = &value["data"]["subsection"]["data"]["1"];
+        assert_eq!(subsection["parent"], 0);
+        assert!(subsection["parent_id"].is_null());
+        assert_eq!(value["data"]["navi"][0], json::array!["top", "../top.html"]);
+        assert_eq!(value["data"]["navi"][1], json::array!["t", ""]);
+    }
+
+    #[test]
+    fn migrate_0_0_5_keeps_parent() {
+        let mut value = page_json_0_0_4();
+        value["data"]["subsection"]["data"]["1"]["parent"] = 5.into();
+        migrate_0_0_5(&mut value).unwrap();
+
+        let subsection = &value["data"]["subsection"]["data"]["1"];
+        assert_eq!(subsection["parent"], 5);
+        assert!(subsection["parent_id"].is_null());
+    }
+
+    #[test]
+    fn migrate_0_0_5_empty_navi() {
+        let mut value = page_json_0_0_4();
+        value["data"]["navi"][0] = json::object! {};
+        assert!(migrate_0_0_5(&mut value).is_err());
+    }
+
+    #[test]
+    fn migrate_from_0_0_1() {
+        let mut value = page_json_0_0_4();
+        value["system"]["version"] = "0.0.1".into();
+
+        let versions = migrate(&mut value).unwrap();
+        assert_eq!(versions, vec!["0.0.2", "0.0.3", "0.0.4", "0.0.5"]);
+        assert_eq!(version(&value), Some(VERSION));
+        assert_eq!(value["data"]["navi"][0], json::array!["top", "../top.html"]);
+    }
+
+    #[test]
+    fn migrate_current() {
+        let mut value = page_json_0_0_4();
+        migrate(&mut value).unwrap();
+        let migrated = value.clone();
+
+        assert!(migrate(&mut value).unwrap().is_empty());
+        assert_eq!(value, migrated);
+    }
+
+    #[test]
+    fn migrate_unknown_version() {
+        let mut value = page_json_0_0_4();
+        value["system"]["version"] = "0.0.0".into();
+        let org = value.clone();
+
+        assert!(migrate(&mut value).is_err());
+        assert_eq!(value, org);
+    }
+
+    #[test]
+    fn migrate_failed_leaves_value() {
+        let mut value = page_json_0_0_4();
+        value["data"]["navi"][0] = json::object! {};
+        let org = value.clone();
+
+        assert!(migrate(&mut value).is_err());
+        assert_eq!(value, org);
+    }
+
+    #[test]
+    fn migrations_chain_to_version() {
+        let mut version = MIGRATIONS[0].from;
+        for migration in MIGRATIONS.iter() {
+            assert_eq!(migration.from, version);
+            version = migration.to;
+        }
+        assert_eq!(version, VERSION);
+    }
+}
@@ -0,0 +1,499 @@
+use super::to_usize;
+
+// Typed data of page json.
+//
+// {
+//   "system": {"version": "0.0.5"},
+//   "data": {
+//     "page": {"title": "t", "rev": 3, "moved_to": "", "private": false},
+//     "navi": [["top", "../top.html"], ["t", ""]],
+//     "subsection": {
+//       "id": {"id_next": 3, "id_notinuse": []},
+//       "data": {
+//         "0": {"parent": 0, "id": 0, "title": "", "href": "", "content": [], "child": [1]},
+//         "1": {"parent": 0, "id": 1, "title": "s", "href": "#s",
+//               "content": [{"type": "text", "value": "v"}], "child": []}
+//       }
+//     }
+//   }
+// }
+//
+// Ids are written in numbers even if those were strings, "parent_id" is read as "parent".
+// Fields not known here are kept in "extra" of each and written back as they were.
+// Subsections of a key not a number or of an id not the same as the key
+// are kept as they are as well for fsck to find those.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageData {
+    pub system: System,
+    pub page: PageInfo,
+    pub navi: Vec<Navi>,
+    pub subsection: Subsections,
+    // Keys of the top other than "system" and "data"
+    pub extra: json::object::Object,
+    // Keys of "data" other than "page", "navi" and "subsection"
+    pub data_extra: json::object::Object,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct System {
+    pub version: String,
+    pub extra: json::object::Object,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageInfo {
+    pub title: String,
+    pub rev: usize,
+    // "" if not moved
+    pub moved_to: String,
+    pub private: Option<bool>,
+    pub extra: json::object::Object,
+}
+
+/// ["title", "href"], {"title": "href"} of old pages is read as well.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Navi {
+    pub title: String,
+    pub href: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subsections {
+    pub id_next: usize,
+    pub id_notinuse: Vec<usize>,
+    // In the order of the page json.
+    pub data: Vec<Subsection>,
+    // Subsections of a key not a number
+    pub data_extra: json::object::Object,
+    // Keys of "id" other than "id_next" and "id_notinuse"
+    pub id_extra: json::object::Object,
+    pub extra: json::object::Object,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subsection {
+    pub id: usize,
+    pub parent: usize,
+    pub title: String,
+    pub href: String,
+    pub content: Vec<Content>,
+    pub child: Vec<usize>,
+    pub extra: json::object::Object,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Content {
+    pub content_type: ContentType,
+    pub value: String,
+    // "lang" of script, "header" and "align" of table and so on.
+    pub extra: json::object::Object,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContentType {
+    Html,
+    Text,
+    Script,
+    Markdown,
+    Table,
+    Other(String),
+}
+
+impl ContentType {
+    pub fn from_name(content_type: &str) -> ContentType {
+        match content_type {
+            "html" => ContentType::Html,
+            "text" => ContentType::Text,
+            "script" => ContentType::Script,
+            "markdown" => ContentType::Markdown,
+            "table" => ContentType::Table,
+            _ => ContentType::Other(content_type.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ContentType::Html => "html",
+            ContentType::Text => "text",
+            ContentType::Script => "script",
+            ContentType::Markdown => "markdown",
+            ContentType::Table => "table",
+            ContentType::Other(v) => v.as_str(),
+        }
+    }
+}
+
+impl PageData {
+    /// Read page json, Err tells where it is malformed,
+    /// eg: "data.subsection.data.3.child: not an array"
+    pub fn from_json(value: &json::JsonValue) -> Result<PageData, String> {
+        let top = object(value, "")?;
+        let data = object(&value["data"], "data")?;
+
+        let navi = match &value["data"]["navi"] {
+            json::JsonValue::Null => vec![],
+            json::JsonValue::Array(navis) => navis
+                .iter()
+                .enumerate()
+                .map(|(i, navi)| Navi::from_json(navi, &format!("data.navi.{}", i)))
+                .collect::<Result<_, _>>()?,
+            _ => return Err("data.navi: not an array".to_string()),
+        };
+
+        Ok(PageData {
+            system: System::from_json(&value["system"])?,
+            page: PageInfo::from_json(&value["data"]["page"])?,
+            navi,
+            subsection: Subsections::from_json(&value["data"]["subsection"])?,
+            extra: extra(top, &["system", "data"]),
+            data_extra: extra(data, &["page", "navi", "subsection"]),
+        })
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut data = json::object! {
+            "page": self.page.to_json(),
+            "navi": self.navi.iter().map(|navi| navi.to_json()).collect::<Vec<_>>(),
+            "subsection": self.subsection.to_json(),
+        };
+        extra_append(&mut data, &self.data_extra);
+
+        let mut value = json::object! {
+            "system": self.system.to_json(),
+            "data": data,
+        };
+        extra_append(&mut value, &self.extra);
+        value
+    }
+
+    /// Subsections whose "parent" is not the subsection having it in "child".
+    /// Return (id, the parent having it in "child")
+    /// Those do not stop reading the page but should be fixed.
+    pub fn parent_mismatches(&self) -> Vec<(usize, usize)> {
+        let mut mismatches = vec![];
+        for subsection in self.subsection.data.iter() {
+            for child in subsection.child.iter() {
+                match self.subsection.get(child) {
+                    Some(child) if child.parent != subsection.id => {
+                        mismatches.push((child.id, subsection.id))
+                    }
+                    _ => (),
+                }
+            }
+        }
+        mismatches
+    }
+}
+
+impl System {
+    fn from_json(value: &json::JsonValue) -> Result<System, String> {
+        if value.is_null() {
+            return Ok(System {
+                version: "".to_string(),
+                extra: json::object::Object::new(),
+            });
+        }
+        let system = object(value, "system")?;
+        Ok(System {
+            version: string(&value["version"], "system.version")?,
+            extra: extra(system, &["version"]),
+        })
+    }
+
+    fn to_json(&self) -> json::JsonValue {
+        let mut value = json::object! {"version": self.version.as_str()};
+        extra_append(&mut value, &self.extra);
+        value
+    }
+}
+
+impl PageInfo {
+    fn from_json(value: &json::JsonValue) -> Result<PageInfo, String> {
+        let page = object(value, "data.page")?;
+        let rev = to_usize(&value["rev"]).map_err(|_| "data.page.rev: not a number".to_string())?;
+        let private = match &value["private"] {
+            json::JsonValue::Null => None,
+            json::JsonValue::Boolean(v) => Some(*v),
+            _ => return Err("data.page.private: not a boolean".to_string()),
+        };
+        Ok(PageInfo {
+            title: string(&value["title"], "data.page.title")?,
+            rev,
+            moved_to: string(&value["moved_to"], "data.page.moved_to")?,
+            private,
+            extra: extra(page, &["title", "rev", "moved_to", "private"]),
+        })
+    }
+
+    fn to_json(&self) -> json::JsonValue {
+        let mut value = json::object! {
+            "title": self.title.as_str(),
+            "rev": self.rev,
+            "moved_to": self.moved_to.as_str(),
+        };
+        if let Some(private) = self.private {
+            value["private"] = private.into();
+        }
+        extra_append(&mut value, &self.extra);
+        value
+    }
+}
+
+impl Navi {
+    fn from_json(value: &json::JsonValue, path: &str) -> Result<Navi, String> {
+        if let json::JsonValue::Object(object) = value {
+            let (title, href) = object.iter().next().ok_or(format!("{}: empty", path))?;
+            return Ok(Navi {
+                title: title.to_string(),
+                href: string(href, &format!("{}.{}", path, title))?,
+            });
+        }
+        if !value.is_array() {
+            return Err(format!("{}: not an array", path));
+        }
+        Ok(Navi {
+            title: string(&value[0], &format!("{}.0", path))?,
+            href: string(&value[1], &format!("{}.1", path))?,
+        })
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        json::array![self.title.as_str(), self.href.as_str()]
+    }
+}
+
+impl Subsections {
+    fn from_json(value: &json::JsonValue) -> Result<Subsections, String> {
+        let subsection = object(value, "data.subsection")?;
+        let id = object(&value["id"], "data.subsection.id")?;
+        let id_next = to_usize(&value["id"]["id_next"])
+            .map_err(|_| "data.subsection.id.id_next: not a number".to_string())?;
+        let id_notinuse = ids(
+            &value["id"]["id_notinuse"],
+            "data.subsection.id.id_notinuse",
+        )?;
+
+        let data_object = object(&value["data"], "data.subsection.data")?;
+        let mut data = vec![];
+        let mut data_extra = json::object::Object::new();
+        for (key, subsection) in data_object.iter() {
+            match key.parse::<usize>() {
+                Ok(id) => data.push(Subsection::from_json(id, subsection)?),
+                Err(_) => data_extra.insert(key, subsection.clone()),
+            }
+        }
+
+        Ok(Subsections {
+            id_next,
+            id_notinuse,
+            data,
+            data_extra,
+            id_extra: extra(id, &["id_next", "id_notinuse"]),
+            extra: extra(subsection, &["id", "data"]),
+        })
+    }
+
+    fn to_json(&self) -> json::JsonValue {
+        let mut id = json::object! {
+            "id_next": self.id_next,
+            "id_notinuse": self.id_notinuse.clone(),
+        };
+        extra_append(&mut id, &self.id_extra);
+
+        let mut data = json::object! {};
+        for subsection in self.data.iter() {
+            data[subsection.id.to_string().as_str()] = subsection.to_json();
+        }
+        extra_append(&mut data, &self.data_extra);
+
+        let mut value = json::object! {"id": id, "data": data};
+        extra_append(&mut value, &self.extra);
+        value
+    }
+
+    pub fn get(&self, id: &usize) -> Option<&Subsection> {
+        self.data.iter().find(|subsection| subsection.id == *id)
+    }
+
+    pub fn get_mut(&mut self, id: &usize) -> Option<&mut Subsection> {
+        self.data.iter_mut().find(|subsection| subsection.id == *id)
+    }
+
+    /// Return id_next as a new id and add one to id_next.
+    /// Ids in use are skipped, id_next of a broken page may be one of those.
+    pub fn id_new(&mut self) -> Option<usize> {
+        let mut id = self.id_next;
+        while self.get(&id).is_some() {
+            id = id.checked_add(1)?;
+        }
+        self.id_next = id.checked_add(1)?;
+        Some(id)
+    }
+
+    /// Ids of the subsection and its descendants, those not in data are left out.
+    pub fn descendants(&self, id: &usize) -> Vec<usize> {
+        let mut ids = vec![];
+        let mut id_s = vec![*id];
+        while let Some(id) = id_s.pop() {
+            // Avoid endless loop on broken data.
+            if ids.contains(&id) {
+                continue;
+            }
+            if let Some(subsection) = self.get(&id) {
+                ids.push(id);
+                id_s.extend(subsection.child.iter().rev());
+            }
+        }
+        ids
+    }
+}
+
+impl Subsection {
+    /// id: the key in "data"
+    fn from_json(id: usize, value: &json::JsonValue) -> Result<Subsection, String> {
+        let path = format!("data.subsection.data.{}", id);
+        let subsection = object(value, &path)?;
+        // "id" not the same as the key is left in extra as it is, fsck fixes it.
+        let id_known = if to_usize(&value["id"]) == Ok(id) {
+            "id"
+        } else {
+            ""
+        };
+
+        // "parent_id" of old pages
+        let parent = if value["parent"].is_null() {
+            &value["parent_id"]
+        } else {
+            &value["parent"]
+        };
+        let parent = if parent.is_null() {
+            0
+        } else {
+            to_usize(parent).map_err(|_| format!("{}.parent: not a number", path))?
+        };
+
+        let content = match &value["content"] {
+            json::JsonValue::Null => vec![],
+            json::JsonValue::Array(contents) => contents
+                .iter()
+                .enumerate()
+                .map(|(i, content)| {
+                    Content::from_json(content).map_err(|e| format!("{}.content.{}{}", path, i, e))
+                })
+                .collect::<Result<_, _>>()?,
+            _ => return Err(format!("{}.content: not an array", path)),
+        };
+
+        Ok(Subsection {
+            id,
+            parent,
+            title: string(&value["title"], &format!("{}.title", path))?,
+            href: string(&value["href"], &format!("{}.href", path))?,
+            content,
+            child: ids(&value["child"], &format!("{}.child", path))?,
+            extra: extra(
+                subsection,
+                &[
+                    id_known,
+                    "parent",
+                    "parent_id",
+                    "title",
+                    "href",
+                    "content",
+                    "child",
+                ],
+            ),
+        })
+    }
+
+    fn to_json(&self) -> json::JsonValue {
+        let mut value = json::object! {
+            "parent": self.parent,
+            "id": self.id,
+            "title": self.title.as_str(),
+            "href": self.href.as_str(),
+            "content": self.content.iter().map(|content| content.to_json()).collect::<Vec<_>>(),
+            "child": self.child.clone(),
+        };
+        extra_append(&mut value, &self.extra);
+        value
+    }
+}
+
+impl Content {
+    /// Err is the place in the content and what is wrong, eg: ".value: not a string"
+    pub fn from_json(value: &json::JsonValue) -> Result<Content, String> {
+        let content = object(value, "")?;
+        let content_type = value["type"]
+            .as_str()
+            .ok_or(".type: not a string".to_string())?;
+        Ok(Content {
+            content_type: ContentType::from_name(content_type),
+            value: string(&value["value"], ".value")?,
+            extra: extra(content, &["type", "value"]),
+        })
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut value = json::object! {
+            "type": self.content_type.as_str(),
+            "value": self.value.as_str(),
+        };
+        extra_append(&mut value, &self.extra);
+        value
+    }
+
+    /// A value in extra, eg: "lang" of script
+    pub fn attr(&self, key: &str) -> Option<&str> {
+        self.extra.get(key).and_then(|v| v.as_str())
+    }
+}
+
+fn object<'a>(value: &'a json::JsonValue, path: &str) -> Result<&'a json::object::Object, String> {
+    match value {
+        json::JsonValue::Object(object) => Ok(object),
+        json::JsonValue::Null => Err(format!("{}: missing", path)),
+        _ => Err(format!("{}: not an object", path)),
+    }
+}
+
+/// "" if missing
+fn string(value: &json::JsonValue, path: &str) -> Result<String, String> {
+    match value {
+        json::JsonValue::Null => Ok("".to_string()),
+        _ => value
+            .as_str()
+            .map(|v| v.to_string())
+            .ok_or(format!("{}: not a string", path)),
+    }
+}
+
+/// [1, "2"] to [1, 2], [] if missing
+fn ids(value: &json::JsonValue, path: &str) -> Result<Vec<usize>, String> {
+    match value {
+        json::JsonValue::Null => Ok(vec![]),
+        json::JsonValue::Array(ids) => ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| to_usize(id).map_err(|_| format!("{}.{}: not a number", path, i)))
+            .collect(),
+        _ => Err(format!("{}: not an array", path)),
+    }
+}
+
+fn extra(object: &json::object::Object, known: &[&str]) -> json::object::Object {
+    let mut extra = json::object::Object::new();
+    for (key, value) in object.iter() {
+        if !known.contains(&key) {
+            extra.insert(key, value.clone());
+        }
+    }
+    extra
+}
+
+fn extra_append(value: &mut json::JsonValue, extra: &json::object::Object) {
+    for (key, v) in extra.iter() {
+        value[key] = v.clone();
+    }
+}
@@ -1,17 +1,45 @@
+use super::page_json::page_data::{Navi, PageData};
 use super::Page;
 use html5ever::serialize::SerializeOpts;
 use markup5ever_rcdom::{Handle, Node, NodeData, RcDom, SerializableHandle};
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::rc::Rc;
 use std::str::FromStr;
+use std::sync::Mutex;
 use tracing::{error, info}; // {event, info, instrument, span, Level, Node}
+pub mod attachment;
+pub mod content_table;
 mod dom_utility;
+pub mod highlight;
+pub mod html_sanitize;
+pub mod index_file;
+pub mod io_roots;
 mod json_from_dom_html;
+pub mod markdown_export;
+pub mod markdown_import;
+pub mod page_backlinks;
 pub mod page_dom_from_json;
+pub mod page_encoding;
+pub mod page_export;
 mod page_move;
 pub use super::page_json;
 pub use page_move::page_move;
 pub mod page_mainte;
+pub mod page_orphan;
+pub mod page_promote;
+pub mod page_search;
+pub mod rev_git;
+pub mod rev_store;
+pub mod storage;
+pub mod subsection_edit;
+pub mod subsection_move;
+
+/// The top page of the storage.
+pub const PAGE_TOP_PATH: &str = "/wc_top.html";
+
+/// Limit of pages to follow on a chain of moved_to.
+pub const MOVED_TO_CHAIN_MAX: usize = 10;
 
 pub fn file_path(stor_root: &str, page_path: &str) -> String {
     // String + "." + &str
@@ -26,9 +54,9 @@ pub fn page_url(page: &mut Page) -> Result<url::Url, String> {
 }
 
 pub fn fs_write(file_path: &str, contents: &Vec<u8>) -> Result<String, String> {
-    std::fs::write(&file_path, contents)
+    storage::write_atomic(std::path::Path::new(file_path), contents)
         .and(Ok(file_path.to_string()))
-        .or_else(|e| Err(e.to_string()))
+        .map_err(|e| e.to_string())
 }
 
 /// Create dir of the path
@@ -44,7 +72,7 @@ pub fn dir_build(path: &std::path::Path, recursive: bool) -> Result<String, Stri
     // Consider how avoid too match deep directorys making.
 
     // Already exists.
-    if let Ok(true) = parent.try_exists() {
+    if storage::exists(parent) {
         return Ok(format!("Already exists: {:?}", path.parent()));
     }
 
@@ -52,10 +80,7 @@ pub fn dir_build(path: &std::path::Path, recursive: bool) -> Result<String, Stri
     let parent_path = parent
         .to_str()
         .ok_or(format!("Failed to get parent in str: {:?}", parent))?;
-    match std::fs::DirBuilder::new()
-        .recursive(recursive)
-        .create(parent_path)
-    {
+    match storage::mkdir(parent, recursive) {
         Ok(_) => {
             info!("dir created: {}", parent_path);
             Ok(format!("dir created: {}", parent_path))
@@ -186,10 +211,29 @@ fn page_html_plain() -> &'static str {
 "#
 }
 
+// page_html_plain without javascript and the json span for a static site.
+fn page_html_export() -> &'static str {
+    r#"<!DOCTYPE html><html><head><title></title><meta charset="UTF-8"></meta>
+    <link rel="stylesheet" href="/wc.css"></link>
+    <style type="text/css"></style>
+</head><body></body></html>
+"#
+}
+
 pub fn page_dom_from_json(page_path: &str, page_json: &json::JsonValue) -> Result<RcDom, String> {
     page_dom_from_json::page_dom_from_json(page_path, page_json)
 }
 
+/// Extensions of markdown used for "markdown" content and markdown import.
+pub fn markdown_options() -> pulldown_cmark::Options {
+    let mut options = pulldown_cmark::Options::empty();
+    options.insert(pulldown_cmark::Options::ENABLE_TABLES);
+    options.insert(pulldown_cmark::Options::ENABLE_STRIKETHROUGH);
+    options.insert(pulldown_cmark::Options::ENABLE_FOOTNOTES);
+    options.insert(pulldown_cmark::Options::ENABLE_TASKLISTS);
+    options
+}
+
 /// Serialize dom
 pub fn source_from_dom(node: Rc<Node>) -> std::result::Result<Vec<u8>, std::io::Error> {
     let sh = SerializableHandle::from(node);
@@ -241,6 +285,7 @@ pub fn set_source_dom_from_json(page: &mut Page) -> Result<(), String> {
     let page_node = Rc::clone(&page_dom.document);
     let source = source_from_dom(page_node).or_else(|e| Err(format!("{}", e)))?;
     page.source.replace(Some(source));
+    page.encoding.replace(encoding_rs::UTF_8);
 
     Ok(())
 }
@@ -321,22 +366,23 @@ pub fn page_child_new(
 
     // json plain
     // let mut child_json = super::page_json::page_json_plain();
-    let mut child_json = page_json::page_json_plain();
+    let mut child_data = PageData::from_json(&page_json::page_json_plain()).or(Err(()))?;
 
     // title
-    child_json["data"]["page"]["title"] = child_title.into();
+    child_data.page.title = child_title.to_string();
 
     // navi
     // navi of parent_page
     let mut child_navi = child_navi(parent_page, &parent_url, &child_url).or(Err(()))?;
 
     // add navi of child_href
-    let navi_child: Vec<json::JsonValue> = vec![child_title.into(), "".into()];
-    if child_navi.push(json::JsonValue::Array(navi_child)).is_err() {
-        return Err(());
-    }
+    child_navi.push(Navi {
+        title: child_title.to_string(),
+        href: "".to_string(),
+    });
 
-    child_json["data"]["navi"] = child_navi;
+    child_data.navi = child_navi;
+    let child_json = child_data.to_json();
 
     // let child_page = page_from_json(parent_page.stor_root(), child_path, &child_json);
     // let child_page = Page::from_json(parent_page.stor_root(), child_path, &child_json);
@@ -377,32 +423,20 @@ fn child_navi(
     parent_page: &mut Page,
     parent_url: &url::Url,
     child_url: &url::Url,
-) -> Result<json::JsonValue, ()> {
+) -> Result<Vec<Navi>, ()> {
     let parent_json = parent_page.json().ok_or(())?;
-    let parent_json = parent_json.value().ok_or(())?;
+    let parent_data = parent_json.page_data().or(Err(()))?;
 
-    let parent_navi = match &parent_json["data"]["navi"] {
-        json::JsonValue::Array(ref v) => v,
-        _ => return Err(()),
-    };
-
-    let mut child_navi = json::JsonValue::Array(vec![]);
-
-    for navi in parent_navi {
-        let title = navi[0].clone();
+    let mut child_navi = vec![];
 
+    for navi in parent_data.navi {
         // Convert href switching its base on paretn_url to child_url
-        let href = navi[1]
-            .as_str()
-            .and_then(|href| href_url(&parent_url, href, &child_url))
-            .or(Some("".to_string())) // only Some
-            .unwrap();
-
-        let mut navi2 = json::JsonValue::Array(vec![]);
-        navi2.push::<json::JsonValue>(title.into()).or(Err(()))?;
-        navi2.push::<json::JsonValue>(href.into()).or(Err(()))?;
+        let href = href_url(parent_url, &navi.href, child_url).unwrap_or_default();
 
-        child_navi.push(navi2).or(Err(()))?;
+        child_navi.push(Navi {
+            title: navi.title,
+            href,
+        });
     }
 
     Ok(child_navi)
@@ -498,6 +532,80 @@ fn href_on(base_url: &url::Url, org_href: &str) -> Option<(String, bool)> {
     Some((href, is_not_child))
 }
 
+/// Count up rev of the page and save it.
+pub fn page_save_rev_up(page: &mut Page) -> Result<(), String> {
+    page.rev_replace_one_up()?;
+    page.file_save_and_rev()
+        .or(Err(format!("Failed to save: {}", page.file_path())))
+}
+
+/// Save pages with rev up, all of those or none of those.
+/// Sources of all are built before writing any, a file written is restored
+/// if writing another fails. Backups and indexes are saved after all files are,
+/// not to leave those of a change undone.
+pub fn pages_save_rev_up(pages: &mut [&mut Page]) -> Result<(), String> {
+    for page in pages.iter_mut() {
+        page.rev_replace_one_up()?;
+        if page.source().is_none() {
+            return Err(format!("Failed to get source: {}", page.file_path()));
+        }
+    }
+
+    // (file_path, source before, None if not existed)
+    let mut written: Vec<(String, Option<Vec<u8>>)> = vec![];
+    for page in pages.iter_mut() {
+        let file_path = page.file_path();
+        let source_org = storage::read(std::path::Path::new(&file_path)).ok();
+        if let Err(e) = page.file_save() {
+            error!("{}", e);
+            for (file_path, source_org) in written.iter().rev() {
+                let res = match source_org {
+                    Some(source_org) => fs_write(file_path, source_org).map(|_| ()),
+                    None => {
+                        storage::remove(std::path::Path::new(file_path)).map_err(|e| e.to_string())
+                    }
+                };
+                if let Err(e) = res {
+                    error!("Failed to restore {}: {}", file_path, e);
+                }
+            }
+            return Err(format!("Failed to save: {}", file_path));
+        }
+        written.push((file_path, source_org));
+    }
+
+    for page in pages.iter_mut() {
+        page.file_saved_rev();
+    }
+    Ok(())
+}
+
+/// Revs compared and pages saved by pages_save_rev_checked are one at a time.
+static REV_SAVE_LOCK: Mutex<()> = Mutex::new(());
+
+/// pages_save_rev_up if revs of the files are still those pages were read with,
+/// for pages edited after a rev check of the request, not to overwrite
+/// a change saved in between. The check and the save are under REV_SAVE_LOCK.
+pub fn pages_save_rev_checked(pages: &mut [&mut Page]) -> Result<(), String> {
+    let _lock = REV_SAVE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    for page in pages.iter_mut() {
+        let page_path = page.page_path().to_string();
+        let rev = page
+            .rev()
+            .map_err(|_| format!("Failed to get rev of {}", page_path))?;
+        let rev_file = Page::new(page.stor_root(), &page_path)
+            .rev()
+            .map_err(|_| format!("Failed to get rev of {}", page_path))?;
+        if rev != rev_file {
+            return Err(format!(
+                "rev not match {} : {} on {}, the page was changed",
+                rev_file, rev, page_path
+            ));
+        }
+    }
+    pages_save_rev_up(pages)
+}
+
 /// Return a list of children url.
 /// You can create instances of children page from this.
 pub fn page_children_url(parent_page: &mut Page) -> Vec<url::Url> {
@@ -553,10 +661,94 @@ pub fn page_children_url(parent_page: &mut Page) -> Vec<url::Url> {
     // }
 }
 
+/// Return paths of pages reachable from top_path
+/// walking children by page_children_url and following moved_to.
+/// Pages not exist are not included.
+pub fn page_tree_paths(stor_root: &str, top_path: &str) -> Vec<String> {
+    let mut page_paths = vec![];
+    let mut handled = HashSet::new();
+    let mut page_path_s = vec![top_path.to_string()];
+
+    while let Some(page_path) = page_path_s.pop() {
+        if !handled.insert(page_path.clone()) {
+            continue;
+        }
+
+        let mut page = Page::new(stor_root, &page_path);
+        if page.json().is_none() {
+            continue;
+        }
+        page_paths.push(page_path);
+
+        if let Ok(Some(location)) = page.moved_location() {
+            let moved_path = location.split('#').next().unwrap_or("");
+            page_path_s.push(moved_path.to_string());
+        }
+
+        let mut child_path_s: Vec<String> = page_children_url(&mut page)
+            .iter()
+            .map(|url| url.path().to_string())
+            .collect();
+        // Keep the order of subsections popping from the last.
+        child_path_s.reverse();
+        page_path_s.append(&mut child_path_s);
+    }
+
+    page_paths
+}
+
+/// Follow moved_to of the page and of the pages it was moved to,
+/// and return the path of the last destination with its fragment if any.
+/// eg. "/dest/dest.html" or "/dest/dest.html#subsection1"
+/// Return Ok(None) if the page was not moved.
+/// Return Err if moved_to makes a loop or the chain is longer than MOVED_TO_CHAIN_MAX.
+/// The last destination is not confirmed if it exists.
+pub fn moved_to_chain(page: &mut Page) -> Result<Option<String>, String> {
+    let Some(moved_to) = page.json().and_then(|page_json| page_json.moved_to()) else {
+        return Ok(None);
+    };
+
+    let stor_root = page.stor_root().to_string();
+    let mut page_url = page_url(page)?;
+    let mut moved_to = moved_to;
+    let mut visited = HashSet::new();
+    visited.insert(page.page_path().to_string());
+
+    loop {
+        let dest_url = page_url
+            .join(&moved_to)
+            .or(Err(format!("Failed to join moved_to: {}", moved_to)))?;
+        let dest_path = dest_url.path().to_string();
+
+        if !visited.insert(dest_path.clone()) {
+            return Err(format!("moved_to loops at: {}", dest_path));
+        }
+
+        if MOVED_TO_CHAIN_MAX < visited.len() {
+            return Err(format!("moved_to chain too long at: {}", dest_path));
+        }
+
+        let mut dest_page = Page::new(&stor_root, &dest_path);
+        let dest_moved_to = dest_page.json().and_then(|page_json| page_json.moved_to());
+
+        // The end of the chain.
+        let Some(dest_moved_to) = dest_moved_to else {
+            let mut location = dest_path;
+            if let Some(fragment) = dest_url.fragment() {
+                location = location + "#" + fragment;
+            }
+            return Ok(Some(location));
+        };
+
+        page_url = dest_url;
+        moved_to = dest_moved_to;
+    }
+}
+
 pub fn page_mainte(
     page: &mut Page,
     recursive: bool,
     log: Option<Rc<RefCell<page_mainte::page_form_update::Log>>>,
-) {
+) -> page_mainte::MainteLog {
     page_mainte::page_mainte(page, recursive, log)
 }
@@ -0,0 +1,443 @@
+use super::page_json::page_data::PageData;
+use super::Page;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use tracing::{error, info};
+
+// Files attached to a page like images are stored beside the page.
+//
+// /dir/page.html
+// /dir/page_files/photo.png
+//
+// Contents link to those by a relative href "page_files/photo.png".
+//
+// Files a browser runs scripts in, html, svg and others, are saved as
+// "name.html.txt" not to run on the origin of pages. Those saved before are
+// served for download, see wc_handler::handle_get.
+
+/// Suffix of the directory of attachments to the page stem.
+const FILES_SUFFIX: &str = "_files";
+
+/// Limit of the length of a file name.
+const NAME_MAX: usize = 100;
+
+/// Extensions of files a browser runs scripts in.
+const EXTS_ACTIVE: &[&str] = &[
+    "html", "htm", "xhtml", "shtml", "svg", "svgz", "xml", "xsl", "js", "mjs",
+];
+
+/// Appended to the name of an active file.
+const ACTIVE_SUFFIX: &str = ".txt";
+
+pub struct Attachment {
+    pub name: String,
+    pub href: String,
+    pub size: usize,
+}
+
+impl Attachment {
+    pub fn to_json(&self) -> json::JsonValue {
+        json::object! {
+            "res": "attachment_upload",
+            "name": self.name.as_str(),
+            "href": self.href.as_str(),
+            "size": self.size,
+        }
+    }
+}
+
+/// "/dir/page.html" to "/dir/page_files"
+pub fn files_dir_path(page_path: &str) -> Option<String> {
+    let stem = page_path
+        .strip_suffix(".html")
+        .or(page_path.strip_suffix(".htm"))?;
+    Some(stem.to_string() + FILES_SUFFIX)
+}
+
+/// If the name is of a file a browser runs scripts in.
+pub fn is_active(name: &str) -> bool {
+    name.rsplit_once('.')
+        .is_some_and(|(_, ext)| EXTS_ACTIVE.contains(&ext.to_lowercase().as_str()))
+}
+
+/// If the path is of a file in the directory of attachments of a page.
+/// "/dir/page_files/photo.png"
+pub fn is_attachment_path(path: &str) -> bool {
+    path.rsplit_once('/')
+        .is_some_and(|(dir, _)| dir.ends_with(FILES_SUFFIX))
+}
+
+/// If dir is the directory of attachments of a page, "page_files" beside "page.html".
+pub fn is_files_dir(dir: &Path) -> bool {
+    let Some(stem) = dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix(FILES_SUFFIX))
+    else {
+        return false;
+    };
+    [".html", ".htm"]
+        .iter()
+        .any(|ext| super::storage::is_file(&dir.with_file_name(stem.to_string() + ext)))
+}
+
+/// "page_files" of "/dir/page.html", it is the href prefix from the page.
+fn files_dir_name(page_path: &str) -> Option<String> {
+    let files_dir_path = files_dir_path(page_path)?;
+    files_dir_path.rsplit('/').next().map(|v| v.to_string())
+}
+
+/// Save data as name in the directory of attachments of the page.
+/// name is made safe, and numbered if another file of the name exists.
+/// The same data of the same name is not saved again.
+pub fn attachment_save(page: &mut Page, name: &str, data: &[u8]) -> Result<Attachment, String> {
+    let page_path = page.page_path().to_string();
+    let page_json = page
+        .json()
+        .ok_or(format!("Failed to get page_json: {}", page_path))?;
+    if page_json.moved_to().is_some() {
+        return Err(format!("The page was moved: {}", page_path));
+    }
+    if data.is_empty() {
+        return Err("No data to attach".to_string());
+    }
+
+    let files_dir_path = files_dir_path(&page_path).ok_or(format!("Not html: {}", page_path))?;
+    let files_dir_name = files_dir_name(&page_path).ok_or(format!("Not html: {}", page_path))?;
+    let dir = super::file_path(page.stor_root(), &files_dir_path);
+    super::storage::mkdir(Path::new(&dir), true)
+        .map_err(|e| format!("Failed to create dir: {}, {}", dir, e))?;
+
+    let name = name_safe(name);
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem.to_string(), format!(".{}", ext)),
+        _ => (name.clone(), "".to_string()),
+    };
+
+    let mut name_new = name.clone();
+    let mut i = 0;
+    loop {
+        let file_path = format!("{}/{}", dir, name_new);
+        match super::storage::read(Path::new(&file_path)) {
+            // Same file uploaded again.
+            Ok(data_exist) if data_exist == data => break,
+            Ok(_) => {
+                i += 1;
+                name_new = format!("{}-{}{}", stem, i, ext);
+            }
+            Err(_) => {
+                super::fs_write(&file_path, &data.to_vec())?;
+                info!("attached: {}", file_path);
+                break;
+            }
+        }
+    }
+
+    Ok(Attachment {
+        href: format!("{}/{}", files_dir_name, name_new),
+        name: name_new,
+        size: data.len(),
+    })
+}
+
+/// Letters, digits, ".", "-", "_" are kept, others become "_".
+/// Leading dots are removed not to be a hidden file or "..".
+/// ".txt" is appended to an active file, "x.html" to "x.html.txt".
+fn name_safe(name: &str) -> String {
+    // Only the file name even if a path is given, eg: "C:\dir\photo.png"
+    let name = name.rsplit(['/', '\\']).next().unwrap_or("");
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = name.trim_start_matches('.');
+    // Keep the end of the name for its extension.
+    let skip = name.chars().count().saturating_sub(NAME_MAX);
+    let name: String = name.chars().skip(skip).collect();
+
+    if name.is_empty() {
+        "attachment".to_string()
+    } else if is_active(&name) {
+        name + ACTIVE_SUFFIX
+    } else {
+        name
+    }
+}
+
+/// Get the file of a multipart/form-data body.
+/// Return the file name and the data of the first part with filename.
+pub fn multipart_file(content_type: &str, body: &[u8]) -> Result<(String, Vec<u8>), String> {
+    let boundary = content_type
+        .split(';')
+        .find_map(|v| v.trim().strip_prefix("boundary="))
+        .map(|v| v.trim_matches('"'))
+        .ok_or("Failed to get boundary of multipart".to_string())?;
+    let delimiter = format!("--{}", boundary).into_bytes();
+
+    let mut pos = bytes_find(body, &delimiter, 0).ok_or("No part in multipart".to_string())?;
+    loop {
+        let part_start = pos + delimiter.len();
+        // "--" after the delimiter is the end.
+        if body[part_start..].starts_with(b"--") {
+            break;
+        }
+        let part_end = bytes_find(body, &delimiter, part_start).unwrap_or(body.len());
+        let part = &body[part_start..part_end];
+
+        if let Some(headers_end) = bytes_find(part, b"\r\n\r\n", 0) {
+            let headers = String::from_utf8_lossy(&part[..headers_end]);
+            let data = &part[headers_end + 4..];
+            // CRLF before the next delimiter
+            let data = data.strip_suffix(b"\r\n").unwrap_or(data);
+
+            let filename = headers
+                .split([';', '\r', '\n'])
+                .find_map(|v| v.trim().strip_prefix("filename="))
+                .map(|v| v.trim_matches('"').to_string());
+            if let Some(filename) = filename {
+                return Ok((filename, data.to_vec()));
+            }
+        }
+
+        if part_end == body.len() {
+            break;
+        }
+        pos = part_end;
+    }
+
+    Err("No file in multipart".to_string())
+}
+
+fn bytes_find(data: &[u8], ptn: &[u8], start: usize) -> Option<usize> {
+    if data.len() < start {
+        return None;
+    }
+    data[start..]
+        .windows(ptn.len())
+        .position(|v| v == ptn)
+        .map(|i| start + i)
+}
+
+/// Names of files in the directory of attachments of the page.
+pub fn attachments(stor_root: &str, page_path: &str) -> Vec<String> {
+    let Some(files_dir_path) = files_dir_path(page_path) else {
+        return vec![];
+    };
+    let dir = super::file_path(stor_root, &files_dir_path);
+    let Ok(paths) = super::storage::list(Path::new(&dir)) else {
+        return vec![];
+    };
+
+    let mut names: Vec<String> = paths
+        .iter()
+        .filter(|path| super::storage::is_file(path))
+        .filter_map(|path| path.file_name()?.to_str().map(|v| v.to_string()))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Attachments of the page that no content nor subsection href of the page refers to.
+pub fn attachments_unreferenced(page: &mut Page) -> Vec<String> {
+    let page_path = page.page_path().to_string();
+    let names = attachments(page.stor_root(), &page_path);
+    if names.is_empty() {
+        return vec![];
+    }
+    let (Some(files_dir_path), Some(files_dir_name)) =
+        (files_dir_path(&page_path), files_dir_name(&page_path))
+    else {
+        return vec![];
+    };
+    let Some(subsections) = page.json().and_then(|page_json| page_json.subsections()) else {
+        return vec![];
+    };
+
+    let mut text = String::new();
+    for (_, subsection) in subsections.iter() {
+        text += subsection["href"].as_str().unwrap_or("");
+        for content in subsection["content"].members() {
+            text += content["value"].as_str().unwrap_or("");
+        }
+    }
+
+    names
+        .into_iter()
+        .filter(|name| {
+            // Relative from the page or absolute from the root.
+            let relative = format!("{}/{}", files_dir_name, name);
+            let absolute = format!("{}/{}", files_dir_path, name);
+            !text.contains(&relative) && !text.contains(&absolute)
+        })
+        .map(|name| format!("{}/{}", files_dir_name, name))
+        .collect()
+}
+
+/// Copy attachments of the page at org_url to the page at dest_url when the page moves,
+/// and convert relative hrefs to those in dest_json.
+/// The original files are left for links to the old location.
+pub fn attachments_move(
+    stor_root: &str,
+    org_url: &url::Url,
+    dest_url: &url::Url,
+    dest_json: &mut json::JsonValue,
+) -> Result<(), String> {
+    let names = attachments(stor_root, org_url.path());
+    if names.is_empty() {
+        return Ok(());
+    }
+
+    let org_dir_path = files_dir_path(org_url.path()).ok_or("Not html".to_string())?;
+    let dest_dir_path = files_dir_path(dest_url.path()).ok_or("Not html".to_string())?;
+    let org_dir = super::file_path(stor_root, &org_dir_path);
+    let dest_dir = super::file_path(stor_root, &dest_dir_path);
+    super::storage::mkdir(Path::new(&dest_dir), true)
+        .map_err(|e| format!("Failed to create dir: {}, {}", dest_dir, e))?;
+
+    let mut copied = HashSet::new();
+    for name in names.iter() {
+        let from = format!("{}/{}", org_dir, name);
+        let to = format!("{}/{}", dest_dir, name);
+        if super::storage::exists(Path::new(&to)) {
+            error!("attachment already exists: {}", to);
+            continue;
+        }
+        let copy = super::storage::read(Path::new(&from))
+            .and_then(|data| super::storage::write_atomic(Path::new(&to), &data));
+        match copy {
+            Ok(_) => {
+                copied.insert(name.as_str());
+            }
+            Err(e) => error!("Failed to copy {} to {}, {}", from, to, e),
+        }
+    }
+    info!(
+        "attachments {} to {}: {}",
+        org_dir_path,
+        dest_dir_path,
+        copied.len()
+    );
+
+    let org_name = files_dir_name(org_url.path()).unwrap_or_default();
+    let dest_name = files_dir_name(dest_url.path()).unwrap_or_default();
+    if org_name == dest_name {
+        return Ok(());
+    }
+
+    // "org_files/x.png" in href="", src="" and markdown (...) to "dest_files/x.png"
+    let reg = regex::Regex::new(&format!(r#"(["'(]){}/"#, regex::escape(&org_name)))
+        .map_err(|e| e.to_string())?;
+    for (_, subsection) in dest_json["data"]["subsection"]["data"].entries_mut() {
+        for content in subsection["content"].members_mut() {
+            let Some(value) = content["value"].as_str() else {
+                continue;
+            };
+            let value = reg
+                .replace_all(value, format!("${{1}}{}/", dest_name).as_str())
+                .to_string();
+            content["value"] = value.into();
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy attachments of the page at org_url that subsections of ids in dest_data refer to
+/// into those of dest_page, when the subsections move to dest_page,
+/// and convert the hrefs to the copies. hrefs in href="", src="", markdown (...)
+/// and the href of the subsection are referred, relative from either page or absolute.
+/// The original files are left for other links to those.
+pub fn attachments_carry(
+    org_url: &url::Url,
+    dest_page: &mut Page,
+    dest_data: &mut PageData,
+    ids: &[usize],
+) -> Result<(), String> {
+    let stor_root = dest_page.stor_root().to_string();
+    if attachments(&stor_root, org_url.path()).is_empty() {
+        return Ok(());
+    }
+    let org_dir_path = files_dir_path(org_url.path()).ok_or("Not html".to_string())?;
+    let org_name = files_dir_name(org_url.path()).ok_or("Not html".to_string())?;
+    let dest_url = org_url
+        .join(dest_page.page_path())
+        .map_err(|e| format!("Failed to join {}, {}", dest_page.page_path(), e))?;
+
+    // The name of the attachment of the origin the href is to.
+    // hrefs converted are relative from the destination, others from the origin.
+    let name_of = |href: &str| -> Option<String> {
+        if !href.contains(&org_name) {
+            return None;
+        }
+        [&dest_url, org_url].iter().find_map(|base| {
+            let href_url = base.join(href).ok()?;
+            if href_url.origin() != org_url.origin() {
+                return None;
+            }
+            let name = href_url
+                .path()
+                .strip_prefix(&org_dir_path)?
+                .strip_prefix('/')?;
+            (!name.is_empty() && !name.contains('/')).then(|| name.to_string())
+        })
+    };
+
+    // name to the href of the copy, None if failed.
+    let mut copies: HashMap<String, Option<String>> = HashMap::new();
+    let mut carry = |href: &str| -> Option<String> {
+        let name = name_of(href)?;
+        copies
+            .entry(name.clone())
+            .or_insert_with(|| {
+                let from = super::file_path(&stor_root, &format!("{}/{}", org_dir_path, name));
+                let copy = super::storage::read(Path::new(&from))
+                    .map_err(|e| format!("Failed to read {}, {}", from, e))
+                    .and_then(|data| attachment_save(dest_page, &name, &data));
+                match copy {
+                    Ok(attachment) => Some(attachment.href),
+                    Err(e) => {
+                        error!("{}", e);
+                        None
+                    }
+                }
+            })
+            .clone()
+    };
+
+    let reg = regex::Regex::new(r#"(["'(])([^"'()<>\s]+)"#).map_err(|e| e.to_string())?;
+    for subsection in dest_data
+        .subsection
+        .data
+        .iter_mut()
+        .filter(|subsection| ids.contains(&subsection.id))
+    {
+        if let Some(href) = carry(&subsection.href) {
+            subsection.href = href;
+        }
+
+        for content in subsection.content.iter_mut() {
+            content.value = reg
+                .replace_all(&content.value, |caps: &regex::Captures| {
+                    match carry(&caps[2]) {
+                        Some(href) => format!("{}{}", &caps[1], href),
+                        None => caps[0].to_string(),
+                    }
+                })
+                .to_string();
+        }
+    }
+
+    info!(
+        "attachments {} to {}: {}",
+        org_dir_path,
+        dest_page.page_path(),
+        copies.values().filter(|href| href.is_some()).count()
+    );
+    Ok(())
+}
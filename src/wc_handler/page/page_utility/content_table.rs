@@ -0,0 +1,309 @@
+use super::page_json::page_data::Content;
+
+// "table" content
+//
+// {
+//   "type" : "table",
+//   "value" : "name,price\napple,100\norange,80",
+//   "format" : "csv", // "csv" or "tsv", tsv if the first line has a tab when not given
+//   "header" : true, // the first row is the header, true if not given
+//   "align" : ["left", "right"] // "left", "right", "center" for each column, optional
+// }
+//
+// Cells are text, "<" and ">" are shown as they are.
+
+pub struct Table {
+    pub header: Option<Vec<String>>,
+    pub rows: Vec<Vec<String>>,
+    pub align: Vec<Align>,
+    pub format: Format,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,
+    Tsv,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Align {
+    None,
+    Left,
+    Right,
+    Center,
+}
+
+impl Align {
+    /// "left", "right", "center" or "l", "r", "c"
+    fn from(value: &str) -> Align {
+        match value.trim().to_lowercase().as_str() {
+            "left" | "l" => Align::Left,
+            "right" | "r" => Align::Right,
+            "center" | "c" => Align::Center,
+            _ => Align::None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&'static str> {
+        match self {
+            Align::None => None,
+            Align::Left => Some("left"),
+            Align::Right => Some("right"),
+            Align::Center => Some("center"),
+        }
+    }
+}
+
+impl Table {
+    pub fn from_content(content: &Content) -> Table {
+        let value = content.value.as_str();
+
+        let format = match content.attr("format") {
+            Some("tsv") => Format::Tsv,
+            Some("csv") => Format::Csv,
+            _ if value.lines().next().is_some_and(|line| line.contains('\t')) => Format::Tsv,
+            _ => Format::Csv,
+        };
+
+        let mut rows = match format {
+            Format::Csv => csv_parse(value),
+            Format::Tsv => tsv_parse(value),
+        };
+
+        let header_given = content.extra.get("header").and_then(|v| v.as_bool());
+        let header = if header_given.unwrap_or(true) && !rows.is_empty() {
+            Some(rows.remove(0))
+        } else {
+            None
+        };
+
+        let align = content
+            .extra
+            .get("align")
+            .into_iter()
+            .flat_map(|align| align.members())
+            .map(|align| Align::from(align.as_str().unwrap_or("")))
+            .collect();
+
+        Table {
+            header,
+            rows,
+            align,
+            format,
+        }
+    }
+
+    /// Number of columns of the widest row.
+    pub fn columns(&self) -> usize {
+        self.header
+            .iter()
+            .chain(self.rows.iter())
+            .map(|row| row.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn align(&self, column: usize) -> Align {
+        self.align.get(column).copied().unwrap_or(Align::None)
+    }
+
+    /// Sort rows by the column, numbers are compared as numbers.
+    pub fn sort(&mut self, column: usize, desc: bool) {
+        self.rows.sort_by(|a, b| {
+            let a = a.get(column).map(|v| v.trim()).unwrap_or("");
+            let b = b.get(column).map(|v| v.trim()).unwrap_or("");
+            let ordering = match (a.parse::<f64>(), b.parse::<f64>()) {
+                (Ok(a), Ok(b)) => a.total_cmp(&b),
+                _ => a.cmp(b),
+            };
+            if desc {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+
+    /// Leave rows that contain query in the column, or in any columns if column is None.
+    /// It is not case-sensitive.
+    pub fn filter(&mut self, column: Option<usize>, query: &str) {
+        let query = query.to_lowercase();
+        self.rows.retain(|row| match column {
+            Some(column) => row
+                .get(column)
+                .is_some_and(|cell| cell.to_lowercase().contains(&query)),
+            None => row.iter().any(|cell| cell.to_lowercase().contains(&query)),
+        });
+    }
+
+    /// Value of the content in its format.
+    pub fn value(&self) -> String {
+        let mut lines = vec![];
+        for row in self.header.iter().chain(self.rows.iter()) {
+            let cells: Vec<String> = match self.format {
+                Format::Csv => row.iter().map(|cell| csv_cell(cell)).collect(),
+                Format::Tsv => row
+                    .iter()
+                    .map(|cell| cell.replace(['\t', '\n'], " "))
+                    .collect(),
+            };
+            let delimiter = if self.format == Format::Csv {
+                ","
+            } else {
+                "\t"
+            };
+            lines.push(cells.join(delimiter));
+        }
+        lines.join("\n")
+    }
+
+    /// <table class="tableBorder"><thead>..</thead><tbody>..</tbody></table>
+    pub fn to_html(&self) -> String {
+        let columns = self.columns();
+        let mut html = String::from("<table class=\"tableBorder\">");
+
+        if let Some(header) = self.header.as_ref() {
+            html += "<thead>";
+            html += &self.row_html(header, columns, "th");
+            html += "</thead>";
+        }
+
+        html += "<tbody>";
+        for row in self.rows.iter() {
+            html += &self.row_html(row, columns, "td");
+        }
+        html += "</tbody></table>";
+
+        html
+    }
+
+    fn row_html(&self, row: &[String], columns: usize, tag: &str) -> String {
+        let mut html = String::from("<tr>");
+        for column in 0..columns {
+            let cell = row.get(column).map_or("", |cell| cell.as_str());
+            match self.align(column).as_str() {
+                Some(align) => html += &format!("<{} style=\"text-align: {}\">", tag, align),
+                None => html += &format!("<{}>", tag),
+            }
+            html += &super::highlight::html_escape(cell).replace('\n', "<br>");
+            html += &format!("</{}>", tag);
+        }
+        html + "</tr>"
+    }
+
+    /// Table of GitHub Flavored Markdown.
+    /// The header is blank if the table has no header.
+    pub fn to_markdown(&self) -> String {
+        let columns = self.columns();
+        if columns == 0 {
+            return "".to_string();
+        }
+
+        let blank = vec![];
+        let header = self.header.as_ref().unwrap_or(&blank);
+        let mut markdown = markdown_row(header, columns);
+
+        let delimiters: Vec<&str> = (0..columns)
+            .map(|column| match self.align(column) {
+                Align::None => "---",
+                Align::Left => ":---",
+                Align::Right => "---:",
+                Align::Center => ":---:",
+            })
+            .collect();
+        markdown += &format!("| {} |\n", delimiters.join(" | "));
+
+        for row in self.rows.iter() {
+            markdown += &markdown_row(row, columns);
+        }
+        markdown
+    }
+
+    /// Plain text of cells for search.
+    pub fn to_text(&self) -> String {
+        self.header
+            .iter()
+            .chain(self.rows.iter())
+            .map(|row| row.join(" "))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+fn markdown_row(row: &[String], columns: usize) -> String {
+    let cells: Vec<String> = (0..columns)
+        .map(|column| {
+            let cell = row.get(column).map_or("", |cell| cell.as_str());
+            cell.replace('\\', "\\\\")
+                .replace('|', "\\|")
+                .replace('\n', "<br>")
+        })
+        .collect();
+    format!("| {} |\n", cells.join(" | "))
+}
+
+/// Quote the cell if it has , " or \n.
+fn csv_cell(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/// Parse CSV of RFC 4180, quoted cells may have , "" and line breaks.
+/// Blank lines are ignored.
+fn csv_parse(value: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row: Vec<String> = vec![];
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    cell.push('"');
+                    chars.next();
+                }
+                '"' => quoted = false,
+                _ => cell.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if cell.trim().is_empty() => {
+                cell.clear();
+                quoted = true;
+            }
+            ',' => row.push(std::mem::take(&mut cell)),
+            '\r' => (),
+            '\n' => {
+                row.push(std::mem::take(&mut cell));
+                csv_row_push(&mut rows, std::mem::take(&mut row));
+            }
+            _ => cell.push(c),
+        }
+    }
+    row.push(cell);
+    csv_row_push(&mut rows, row);
+
+    rows
+}
+
+fn csv_row_push(rows: &mut Vec<Vec<String>>, row: Vec<String>) {
+    if row.len() == 1 && row[0].trim().is_empty() {
+        return;
+    }
+    rows.push(row);
+}
+
+fn tsv_parse(value: &str) -> Vec<Vec<String>> {
+    value
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.split('\t').map(|cell| cell.to_string()).collect())
+        .collect()
+}
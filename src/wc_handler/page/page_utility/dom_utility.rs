@@ -1,7 +1,7 @@
-use html5ever::parse_document; // , serialize
 use html5ever::tendril::TendrilSink; // Default::default()).one needs this.
+use html5ever::{parse_document, parse_fragment}; // , serialize
 use markup5ever::interface::Attribute;
-use markup5ever::{namespace_url, ns};
+use markup5ever::{local_name, namespace_url, ns};
 use markup5ever::{tendril::Tendril, LocalName, QualName}; // local_name,
 use markup5ever_rcdom::{Handle, Node, NodeData, RcDom}; // , SerializableHandle
 use std::cell::RefCell;
@@ -32,6 +32,19 @@ pub fn to_dom_parts(html_text: &str) -> Vec<Rc<Node>> {
     body.children.take()
 }
 
+/// Parse html_text as contents of <body>.
+/// Unlike to_dom_parts, elements of head like <script>, <meta> or <base> at the start
+/// are kept in order, not moved to <head>, to be checked by html_sanitize.
+pub fn to_dom_fragment(html_text: &str) -> Vec<Rc<Node>> {
+    let context = QualName::new(None, ns!(html), local_name!("body"));
+    let parsed =
+        parse_fragment(RcDom::default(), Default::default(), context, vec![]).one(html_text);
+
+    // The fragment is put in <html> of the document.
+    let html = parsed.document.children.borrow().first().cloned();
+    html.map(|html| html.children.take()).unwrap_or_default()
+}
+
 pub fn attrs(attrs_vec: &Vec<(&str, &str)>) -> RefCell<Vec<Attribute>> {
     let mut attr_list: Vec<Attribute> = vec![];
     for (name, value) in attrs_vec {
@@ -0,0 +1,420 @@
+// Syntax highlighting of "script" contents with "lang".
+//
+// {"type": "script", "lang": "rust", "value": "fn main() {}"}
+//
+// Code is split into tokens and each token is put in <span class="hl-xxx">.
+//   hl-comment, hl-string, hl-keyword, hl-literal, hl-number, hl-type, hl-macro, hl-variable, hl-key
+// Colors of the classes are in wc.css.
+
+struct Lang {
+    // separated by spaces
+    keywords: &'static str,
+    literals: &'static str,
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    // Keywords are not case-sensitive (SQL).
+    ignore_case: bool,
+    // Backslash does not escape in single quoted strings (shell, SQL).
+    single_quote_raw: bool,
+    // """ and ''' strings (Python).
+    triple_quote: bool,
+    // Capitalized identifiers are types, name! are macros (Rust).
+    rust_like: bool,
+    // $name, ${name} are variables (shell).
+    shell_like: bool,
+    // Strings followed by : are keys (JSON).
+    json_like: bool,
+}
+
+const RUST: Lang = Lang {
+    keywords: "as async await break const continue crate dyn else enum extern fn for if impl in \
+        let loop match mod move mut pub ref return self Self static struct super trait \
+        type unsafe use where while",
+    literals: "true false None Some Ok Err",
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    ignore_case: false,
+    single_quote_raw: false,
+    triple_quote: false,
+    rust_like: true,
+    shell_like: false,
+    json_like: false,
+};
+
+const SHELL: Lang = Lang {
+    keywords: "if then else elif fi case esac for while until do done in function select return \
+        exit export local readonly source alias unset shift break continue",
+    literals: "true false",
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\'', '`'],
+    ignore_case: false,
+    single_quote_raw: true,
+    triple_quote: false,
+    rust_like: false,
+    shell_like: true,
+    json_like: false,
+};
+
+const JSON: Lang = Lang {
+    keywords: "",
+    literals: "true false null",
+    line_comments: &[],
+    block_comment: None,
+    quotes: &['"'],
+    ignore_case: false,
+    single_quote_raw: false,
+    triple_quote: false,
+    rust_like: false,
+    shell_like: false,
+    json_like: true,
+};
+
+const SQL: Lang = Lang {
+    keywords: "select from where insert into values update set delete create table index view \
+        drop alter add column primary key foreign references join inner left right outer \
+        full on as and or not in is like between exists group by order having limit \
+        offset distinct union all case when then else end begin commit rollback \
+        transaction with asc desc default unique check constraint if integer int text \
+        varchar char boolean real float date timestamp",
+    literals: "null true false",
+    line_comments: &["--"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['\''],
+    ignore_case: true,
+    single_quote_raw: true,
+    triple_quote: false,
+    rust_like: false,
+    shell_like: false,
+    json_like: false,
+};
+
+const PYTHON: Lang = Lang {
+    keywords: "and as assert async await break class continue def del elif else except finally \
+        for from global if import in is lambda nonlocal not or pass raise return try \
+        while with yield self",
+    literals: "True False None",
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    ignore_case: false,
+    single_quote_raw: false,
+    triple_quote: true,
+    rust_like: false,
+    shell_like: false,
+    json_like: false,
+};
+
+fn lang_get(lang: &str) -> Option<&'static Lang> {
+    match lang.trim().to_lowercase().as_str() {
+        "rust" | "rs" => Some(&RUST),
+        "shell" | "sh" | "bash" | "zsh" | "console" => Some(&SHELL),
+        "json" => Some(&JSON),
+        "sql" => Some(&SQL),
+        "python" | "py" => Some(&PYTHON),
+        _ => None,
+    }
+}
+
+/// Highlight code of lang in html.
+/// Code of languages not supported is only escaped.
+pub fn highlight(lang: &str, code: &str) -> String {
+    match lang_get(lang) {
+        Some(lang) => Highlighter::new(lang, code).run(),
+        None => html_escape(code),
+    }
+}
+
+pub fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+struct Highlighter<'a> {
+    lang: &'a Lang,
+    chars: Vec<char>,
+    pos: usize,
+    html: String,
+}
+
+impl<'a> Highlighter<'a> {
+    fn new(lang: &'a Lang, code: &str) -> Highlighter<'a> {
+        Highlighter {
+            lang,
+            chars: code.chars().collect(),
+            pos: 0,
+            html: String::new(),
+        }
+    }
+
+    fn run(mut self) -> String {
+        while self.pos < self.chars.len() {
+            if self.comment() || self.string() || self.number() || self.word() {
+                continue;
+            }
+            if self.lang.shell_like && self.variable() {
+                continue;
+            }
+            let c = self.chars[self.pos];
+            self.plain(&c.to_string());
+            self.pos += 1;
+        }
+        self.html
+    }
+
+    fn starts_with(&self, ptn: &str) -> bool {
+        let ptn: Vec<char> = ptn.chars().collect();
+        self.chars[self.pos..].starts_with(&ptn)
+    }
+
+    fn prev_is_word(&self) -> bool {
+        0 < self.pos && is_word_char(self.chars[self.pos - 1])
+    }
+
+    fn take_to(&mut self, end: usize) -> String {
+        let end = end.min(self.chars.len());
+        let token: String = self.chars[self.pos..end].iter().collect();
+        self.pos = end;
+        token
+    }
+
+    fn span(&mut self, class: &str, token: &str) {
+        self.html += &format!("<span class=\"hl-{}\">{}</span>", class, html_escape(token));
+    }
+
+    fn plain(&mut self, token: &str) {
+        self.html += &html_escape(token);
+    }
+
+    fn comment(&mut self) -> bool {
+        if let Some((start, end)) = self.lang.block_comment {
+            if self.starts_with(start) {
+                let from = self.pos + start.chars().count();
+                let mut to = self.chars.len();
+                let mut i = from;
+                while i < self.chars.len() {
+                    let rest: String = self.chars[i..].iter().take(end.len()).collect();
+                    if rest == end {
+                        to = i + end.chars().count();
+                        break;
+                    }
+                    i += 1;
+                }
+                let token = self.take_to(to);
+                self.span("comment", &token);
+                return true;
+            }
+        }
+
+        for line_comment in self.lang.line_comments {
+            if !self.starts_with(line_comment) {
+                continue;
+            }
+            // "#" in the middle of a word is not a comment in shell, eg: $#, a#b
+            if self.lang.shell_like && 0 < self.pos && !self.chars[self.pos - 1].is_whitespace() {
+                return false;
+            }
+            let to = self.chars[self.pos..]
+                .iter()
+                .position(|c| *c == '\n')
+                .map_or(self.chars.len(), |i| self.pos + i);
+            let token = self.take_to(to);
+            self.span("comment", &token);
+            return true;
+        }
+        false
+    }
+
+    fn string(&mut self) -> bool {
+        let c = self.chars[self.pos];
+
+        // Rust: r"raw", r#"raw"#, b"bytes"
+        if self.lang.rust_like && (c == 'r' || c == 'b') && !self.prev_is_word() {
+            if let Some(to) = self.rust_raw_string_end() {
+                let token = self.take_to(to);
+                self.span("string", &token);
+                return true;
+            }
+        }
+
+        if !self.lang.quotes.contains(&c) {
+            return false;
+        }
+
+        if self.lang.triple_quote {
+            let triple: String = [c, c, c].iter().collect();
+            if self.starts_with(&triple) {
+                let from = self.pos + 3;
+                let to = (from..self.chars.len())
+                    .find(|i| self.chars[*i..].starts_with(&[c, c, c]))
+                    .map_or(self.chars.len(), |i| i + 3);
+                let token = self.take_to(to);
+                self.span("string", &token);
+                return true;
+            }
+        }
+
+        let raw = c == '\'' && self.lang.single_quote_raw;
+        let mut i = self.pos + 1;
+        while i < self.chars.len() {
+            if self.chars[i] == '\\' && !raw {
+                i += 2;
+                continue;
+            }
+            if self.chars[i] == c {
+                i += 1;
+                break;
+            }
+            i += 1;
+        }
+        let token = self.take_to(i);
+
+        let is_key = self.lang.json_like
+            && self.chars[self.pos..]
+                .iter()
+                .find(|c| !c.is_whitespace())
+                .is_some_and(|c| *c == ':');
+        self.span(if is_key { "key" } else { "string" }, &token);
+        true
+    }
+
+    /// Position after r"...", r#"..."#, b"..." or br"..." from self.pos.
+    fn rust_raw_string_end(&self) -> Option<usize> {
+        let mut i = self.pos;
+        if self.chars.get(i) == Some(&'b') {
+            i += 1;
+        }
+        let raw = self.chars.get(i) == Some(&'r');
+        if raw {
+            i += 1;
+        }
+        let hashes = self.chars[i..].iter().take_while(|c| **c == '#').count();
+        i += hashes;
+        if self.chars.get(i) != Some(&'"') || (i == self.pos) || (!raw && 0 < hashes) {
+            return None;
+        }
+        i += 1;
+
+        while i < self.chars.len() {
+            if self.chars[i] == '\\' && !raw {
+                i += 2;
+                continue;
+            }
+            if self.chars[i] == '"'
+                && self.chars[i + 1..]
+                    .iter()
+                    .take_while(|c| **c == '#')
+                    .count()
+                    >= hashes
+            {
+                return Some(i + 1 + hashes);
+            }
+            i += 1;
+        }
+        Some(self.chars.len())
+    }
+
+    fn number(&mut self) -> bool {
+        let c = self.chars[self.pos];
+        let minus = c == '-' && self.lang.json_like;
+        let digit = if minus {
+            self.chars.get(self.pos + 1)
+        } else {
+            Some(&c)
+        };
+        if !digit.is_some_and(|c| c.is_ascii_digit()) || self.prev_is_word() {
+            return false;
+        }
+
+        let mut i = self.pos + 1;
+        while i < self.chars.len() {
+            let c = self.chars[i];
+            // 1.5, 1e-3, 0xff, 10_000, 1u8
+            let exponent_sign = (c == '-' || c == '+') && matches!(self.chars[i - 1], 'e' | 'E');
+            if !(is_word_char(c) || c == '.' || exponent_sign) {
+                break;
+            }
+            // 1..10 of Rust range
+            if c == '.' && self.chars.get(i + 1) == Some(&'.') {
+                break;
+            }
+            i += 1;
+        }
+        let token = self.take_to(i);
+        self.span("number", &token);
+        true
+    }
+
+    fn word(&mut self) -> bool {
+        let c = self.chars[self.pos];
+        if !(c.is_alphabetic() || c == '_') {
+            return false;
+        }
+
+        let to = self.chars[self.pos..]
+            .iter()
+            .position(|c| !is_word_char(*c))
+            .map_or(self.chars.len(), |i| self.pos + i);
+        let token = self.take_to(to);
+        let key = if self.lang.ignore_case {
+            token.to_lowercase()
+        } else {
+            token.clone()
+        };
+
+        if self.lang.keywords.split_whitespace().any(|v| v == key) {
+            self.span("keyword", &token);
+        } else if self.lang.literals.split_whitespace().any(|v| v == key) {
+            self.span("literal", &token);
+        } else if self.lang.rust_like && self.chars.get(self.pos) == Some(&'!') {
+            // println!
+            let token = token + "!";
+            self.pos += 1;
+            self.span("macro", &token);
+        } else if self.lang.rust_like && token.starts_with(|c: char| c.is_uppercase()) {
+            self.span("type", &token);
+        } else {
+            self.plain(&token);
+        }
+        true
+    }
+
+    /// $name, ${name}, $1, $?
+    fn variable(&mut self) -> bool {
+        if self.chars[self.pos] != '$' {
+            return false;
+        }
+        let Some(next) = self.chars.get(self.pos + 1) else {
+            return false;
+        };
+
+        let to = if *next == '{' {
+            self.chars[self.pos..]
+                .iter()
+                .position(|c| *c == '}')
+                .map_or(self.chars.len(), |i| self.pos + i + 1)
+        } else if is_word_char(*next) {
+            self.chars[self.pos + 1..]
+                .iter()
+                .position(|c| !is_word_char(*c))
+                .map_or(self.chars.len(), |i| self.pos + 1 + i)
+        } else if "?#@*!$-".contains(*next) {
+            self.pos + 2
+        } else {
+            return false;
+        };
+
+        let token = self.take_to(to);
+        self.span("variable", &token);
+        true
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
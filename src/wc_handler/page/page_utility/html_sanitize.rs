@@ -0,0 +1,335 @@
+use markup5ever_rcdom::{Node, NodeData};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::OnceLock;
+use tracing::{error, info};
+
+// Sanitize html of contents with an allowlist of elements and attributes.
+//
+// Elements not allowed are removed but their children are kept,
+// elements to drop like <script> are removed with their children.
+// Attributes not allowed, event handlers (on*) and urls of schemes
+// not allowed like "javascript:" are removed.
+//
+// The allowlist is read from stor_root/wc_sanitize.json on start,
+// keys not given are the defaults.
+// {
+//   "elements" : ["a", "b", "div", ...],
+//   "elements_drop" : ["script", "style", ...],
+//   "attributes" : { "*" : ["class", "id"], "a" : ["href"], ... },
+//   "url_schemes" : ["http", "https", "mailto"]
+// }
+
+const CONFIG_FILE: &str = "/wc_sanitize.json";
+
+const ELEMENTS: &str = "a abbr address article aside b bdi bdo big blockquote br caption center \
+    cite code col colgroup dd del details dfn div dl dt em figcaption figure font footer \
+    h1 h2 h3 h4 h5 h6 header hr i img input ins kbd li main mark nav ol p pre q rp rt ruby \
+    s samp section small span strike strong sub summary sup table tbody td tfoot th thead \
+    time tr tt u ul var wbr";
+
+const ELEMENTS_DROP: &str = "script style iframe frame frameset object embed applet noscript \
+    template base link meta title form textarea select button";
+
+const ATTRIBUTES: &[(&str, &str)] = &[
+    (
+        "*",
+        "id class style title lang dir align valign width height name",
+    ),
+    ("a", "href target rel"),
+    ("img", "src alt border"),
+    ("input", "type checked disabled"),
+    ("ol", "start type reversed"),
+    ("li", "value"),
+    ("td", "colspan rowspan"),
+    ("th", "colspan rowspan scope"),
+    ("col", "span"),
+    ("colgroup", "span"),
+    ("blockquote", "cite"),
+    ("q", "cite"),
+    ("del", "cite datetime"),
+    ("ins", "cite datetime"),
+    ("time", "datetime"),
+    ("font", "color size face"),
+    ("table", "border cellpadding cellspacing"),
+];
+
+const URL_SCHEMES: &str = "http https mailto tel ftp";
+
+/// Attributes with a url.
+const URL_ATTRIBUTES: &str = "href src cite action formaction background poster";
+
+pub struct Allowlist {
+    elements: HashSet<String>,
+    elements_drop: HashSet<String>,
+    // element name or "*" for all elements: attribute names
+    attributes: HashMap<String, HashSet<String>>,
+    url_schemes: HashSet<String>,
+}
+
+static ALLOWLIST: OnceLock<Allowlist> = OnceLock::new();
+
+impl Allowlist {
+    fn from_json(config: &json::JsonValue) -> Allowlist {
+        let names = |key: &str, default: &str| -> HashSet<String> {
+            if config[key].is_array() {
+                config[key]
+                    .members()
+                    .filter_map(|v| v.as_str())
+                    .map(|v| v.trim().to_lowercase())
+                    .collect()
+            } else {
+                default.split_whitespace().map(|v| v.to_string()).collect()
+            }
+        };
+
+        let attributes = if config["attributes"].is_object() {
+            config["attributes"]
+                .entries()
+                .map(|(element, names)| {
+                    let names = names
+                        .members()
+                        .filter_map(|v| v.as_str())
+                        .map(|v| v.trim().to_lowercase())
+                        .collect();
+                    (element.to_lowercase(), names)
+                })
+                .collect()
+        } else {
+            ATTRIBUTES
+                .iter()
+                .map(|(element, names)| {
+                    let names = names.split_whitespace().map(|v| v.to_string()).collect();
+                    (element.to_string(), names)
+                })
+                .collect()
+        };
+
+        Allowlist {
+            elements: names("elements", ELEMENTS),
+            elements_drop: names("elements_drop", ELEMENTS_DROP),
+            attributes,
+            url_schemes: names("url_schemes", URL_SCHEMES),
+        }
+    }
+
+    fn attribute_allowed(&self, element: &str, attribute: &str) -> bool {
+        ["*", element].iter().any(|key| {
+            self.attributes
+                .get(*key)
+                .is_some_and(|names| names.contains(attribute))
+        })
+    }
+
+    /// Relative urls and urls of url_schemes are allowed.
+    fn url_allowed(&self, url: &str) -> bool {
+        // Browsers ignore whitespace and control charactors in the scheme,
+        // eg: "java\tscript:"
+        let url: String = url
+            .chars()
+            .filter(|c| !c.is_whitespace() && !c.is_control())
+            .collect();
+        let url = url.to_lowercase();
+        let scheme_end = url.find(['/', '?', '#']).unwrap_or(url.len());
+        match url[..scheme_end].split_once(':') {
+            Some((scheme, _)) => self.url_schemes.contains(scheme),
+            None => true,
+        }
+    }
+}
+
+/// Read the allowlist from stor_root, it is done once on start.
+pub fn allowlist_load(stor_root: &str) {
+    let path = super::file_path(stor_root, CONFIG_FILE);
+    let config = match super::storage::read_to_string(std::path::Path::new(&path)) {
+        Ok(v) => match json::parse(&v) {
+            Ok(v) => {
+                info!("sanitize allowlist: {}", path);
+                v
+            }
+            Err(e) => {
+                error!("Failed to parse {}, the default is used: {}", path, e);
+                json::JsonValue::Null
+            }
+        },
+        // No config, the default is used.
+        Err(_) => json::JsonValue::Null,
+    };
+    let _ = ALLOWLIST.set(Allowlist::from_json(&config));
+}
+
+fn allowlist() -> &'static Allowlist {
+    ALLOWLIST.get_or_init(|| Allowlist::from_json(&json::JsonValue::Null))
+}
+
+/// Sanitize nodes in place and return nodes to be kept.
+/// What removed is pushed into removed, eg: "<script>", "<img onerror>"
+pub fn nodes_sanitize(nodes: Vec<Rc<Node>>, removed: &mut Vec<String>) -> Vec<Rc<Node>> {
+    let allowlist = allowlist();
+    let mut kept = vec![];
+    for node in nodes.into_iter() {
+        node_sanitize(allowlist, node, &mut kept, removed);
+    }
+    kept
+}
+
+fn node_sanitize(
+    allowlist: &Allowlist,
+    node: Rc<Node>,
+    kept: &mut Vec<Rc<Node>>,
+    removed: &mut Vec<String>,
+) {
+    let children = node.children.take();
+    let mut children_kept = vec![];
+    for child in children.into_iter() {
+        node_sanitize(allowlist, child, &mut children_kept, removed);
+    }
+
+    match &node.data {
+        NodeData::Element { name, attrs, .. } => {
+            let element = name.local.to_string();
+
+            if allowlist.elements_drop.contains(&element) {
+                removed.push(format!("<{}>", element));
+                return;
+            }
+
+            if !allowlist.elements.contains(&element) {
+                // Children are kept in place of the element.
+                removed.push(format!("<{}>", element));
+                kept.append(&mut children_kept);
+                return;
+            }
+
+            attrs.borrow_mut().retain(|attr| {
+                let attribute = attr.name.local.to_string();
+                let allowed = !attribute.starts_with("on")
+                    && allowlist.attribute_allowed(&element, &attribute)
+                    && (!URL_ATTRIBUTES.split_whitespace().any(|v| v == attribute)
+                        || allowlist.url_allowed(&attr.value))
+                    && !(attribute == "style" && style_unsafe(&attr.value));
+                if !allowed {
+                    removed.push(format!("<{} {}>", element, attribute));
+                }
+                allowed
+            });
+        }
+        NodeData::ProcessingInstruction { .. } | NodeData::Doctype { .. } => return,
+        _ => (),
+    }
+
+    *node.children.borrow_mut() = children_kept;
+    kept.push(node);
+}
+
+/// expression() of old IE and javascript: in url()
+fn style_unsafe(style: &str) -> bool {
+    let style: String = style
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase();
+    style.contains("expression(") || style.contains("javascript:") || style.contains("behavior:")
+}
+
+/// Sanitize html text.
+/// The value is returned as it is if nothing is removed
+/// not to change the form of html.
+pub fn html_sanitize(value: &str) -> (String, Vec<String>) {
+    let mut removed = vec![];
+    let nodes = nodes_sanitize(super::dom_utility::to_dom_fragment(value), &mut removed);
+    if removed.is_empty() {
+        return (value.to_string(), removed);
+    }
+
+    let node_top = super::dom_utility::div_subsection_content_new();
+    *node_top.children.borrow_mut() = nodes;
+    // Only children of node_top are serialized.
+    let html = super::source_from_dom(node_top)
+        .ok()
+        .and_then(|v| String::from_utf8(v).ok())
+        .unwrap_or_default();
+    (html, removed)
+}
+
+/// Sanitize contents of page_json value posted, see contents_sanitize.
+/// Return what removed on each subsection:
+/// [{"id": "3", "removed": ["<script>", "<img onerror>"]}]
+pub fn page_json_sanitize(page_json: &mut json::JsonValue) -> json::JsonValue {
+    let mut report = json::JsonValue::new_array();
+
+    for (id, subsection) in page_json["data"]["subsection"]["data"].entries_mut() {
+        let removed_s = contents_sanitize(&mut subsection["content"]);
+        if !removed_s.is_empty() {
+            info!("sanitized subsection {}: {}", id, removed_s.join(", "));
+            let _ = report.push(json::object! {"id": id, "removed": removed_s});
+        }
+    }
+
+    report
+}
+
+/// Sanitize contents of a subsection drawn as html, return what removed.
+/// "html", and "text" and "script" that are html with "\<", "\>" for '<', '>'.
+/// "script" with lang is drawn as code, it is not html.
+pub fn contents_sanitize(contents: &mut json::JsonValue) -> Vec<String> {
+    let mut removed_s = vec![];
+    for content in contents.members_mut() {
+        let Some(value) = content["value"].as_str() else {
+            continue;
+        };
+        let has_lang = content["lang"]
+            .as_str()
+            .is_some_and(|lang| !lang.is_empty());
+        let (value, mut removed) = match content["type"].as_str() {
+            Some("html") => html_sanitize(value),
+            // '<' escaped as "\<" is a charactor, it is "&lt;" after sanitized.
+            Some("text") => html_sanitize(&super::page_dom_from_json::text_angle_entity(value)),
+            Some("script") if !has_lang => {
+                html_sanitize(&super::page_dom_from_json::text_angle_entity(value))
+            }
+            _ => continue,
+        };
+        if removed.is_empty() {
+            continue;
+        }
+        content["value"] = value.into();
+        removed_s.append(&mut removed);
+    }
+    removed_s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_event_handler() {
+        let mut page_json = json::object! {"data": {"subsection": {"data": {"1": {"content": [
+            {"type": "text", "value": "a\n<img src=\"x.png\" onerror=\"alert(1)\">"},
+            {"type": "text", "value": "\\<img src=x onerror=alert(1)\\> <b>b</b>"},
+            {"type": "script", "value": "<b onclick=\"alert(1)\">b</b>"},
+            {"type": "script", "lang": "rust", "value": "let v: Vec<String> = vec![];"},
+            {"type": "html", "value": "<meta http-equiv=\"refresh\" content=\"0\"><script>alert(1)</script><p>p</p>"}
+        ]}}}}};
+        let report = page_json_sanitize(&mut page_json);
+        assert_eq!(report[0]["id"], "1");
+        assert_eq!(
+            report[0]["removed"],
+            json::array!["<img onerror>", "<b onclick>", "<meta>", "<script>"]
+        );
+
+        let contents = &page_json["data"]["subsection"]["data"]["1"]["content"];
+        assert_eq!(contents[0]["value"], "a\n<img src=\"x.png\">");
+        // Shown as it is in text, not an element.
+        assert_eq!(
+            contents[1]["value"],
+            "\\<img src=x onerror=alert(1)\\> <b>b</b>"
+        );
+        assert_eq!(contents[2]["value"], "<b>b</b>");
+        // Code, not html.
+        assert_eq!(contents[3]["value"], "let v: Vec<String> = vec![];");
+        // Not moved to <head> out of the sanitizer.
+        assert_eq!(contents[4]["value"], "<p>p</p>");
+    }
+}
@@ -0,0 +1,153 @@
+use super::storage::{self, Metadata};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use tracing::error;
+
+// An index saved in stor_root as json, {"pages": {"/page.html": ...}},
+// eg: the search index and the link index.
+//
+// The index is kept in memory for each stor_root not to read and parse the file
+// on every save, it is read again if the file was changed out of here.
+// An update is written only when it changed the index, atomically by fs_write.
+
+pub struct IndexFile {
+    file: &'static str,
+    // stor_root: (metadata of the file as read or written, index)
+    cache: Mutex<BTreeMap<String, (Option<Metadata>, json::JsonValue)>>,
+}
+
+type Cache = BTreeMap<String, (Option<Metadata>, json::JsonValue)>;
+
+impl IndexFile {
+    /// file: path of the index in stor_root, eg: "/wc_search_index.json"
+    pub const fn new(file: &'static str) -> IndexFile {
+        IndexFile {
+            file,
+            cache: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn path(&self, stor_root: &str) -> String {
+        super::file_path(stor_root, self.file)
+    }
+
+    // The index file is read, updated and written by threads of the pool.
+    fn lock(&self) -> MutexGuard<'_, Cache> {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The index cached, read from the file if it is not cached or the file was changed.
+    fn cached<'a>(&self, cache: &'a mut Cache, stor_root: &str) -> &'a mut json::JsonValue {
+        let path = self.path(stor_root);
+        let metadata = storage::metadata(Path::new(&path)).ok();
+        let fresh = metadata.is_some()
+            && cache
+                .get(stor_root)
+                .is_some_and(|(cached, _)| *cached == metadata);
+        if !fresh {
+            cache.insert(stor_root.to_string(), (metadata, index_read(&path)));
+        }
+        &mut cache.get_mut(stor_root).unwrap().1
+    }
+
+    /// A copy of the index.
+    pub fn read(&self, stor_root: &str) -> json::JsonValue {
+        let mut cache = self.lock();
+        self.cached(&mut cache, stor_root).clone()
+    }
+
+    /// Update the index by update, it is written if update returns true.
+    pub fn update(&self, stor_root: &str, update: impl FnOnce(&mut json::JsonValue) -> bool) {
+        let mut cache = self.lock();
+        let index = self.cached(&mut cache, stor_root);
+        if !update(index) {
+            return;
+        }
+        match self.write(stor_root, index) {
+            Ok(metadata) => {
+                if let Some(cached) = cache.get_mut(stor_root) {
+                    cached.0 = metadata;
+                }
+            }
+            Err(e) => {
+                // The index in memory is not what the file has.
+                cache.remove(stor_root);
+                error!("Failed to write {}: {}", self.file, e);
+            }
+        }
+    }
+
+    /// Replace the whole index, eg: on rebuild.
+    pub fn replace(&self, stor_root: &str, index: json::JsonValue) -> Result<(), String> {
+        let mut cache = self.lock();
+        match self.write(stor_root, &index) {
+            Ok(metadata) => {
+                cache.insert(stor_root.to_string(), (metadata, index));
+                Ok(())
+            }
+            Err(e) => {
+                cache.remove(stor_root);
+                Err(e)
+            }
+        }
+    }
+
+    /// Write the index, return the metadata of the file written.
+    fn write(&self, stor_root: &str, index: &json::JsonValue) -> Result<Option<Metadata>, String> {
+        let path = self.path(stor_root);
+        super::fs_write(&path, &index.dump().into_bytes())?;
+        Ok(storage::metadata(Path::new(&path)).ok())
+    }
+}
+
+fn index_read(path: &str) -> json::JsonValue {
+    let index = storage::read_to_string(Path::new(path))
+        .ok()
+        .and_then(|v| json::parse(&v).ok());
+
+    match index {
+        Some(index) if index["pages"].is_object() => index,
+        _ => json::object! {"pages": {}},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::storage::{Storage, StorageMem};
+    use super::*;
+    use std::sync::Arc;
+    use std::time::SystemTime;
+
+    static INDEX: IndexFile = IndexFile::new("/index.json");
+
+    #[test]
+    fn index_update_changed() {
+        let stor_root = "/mem_index_file";
+        let mem = Arc::new(StorageMem::new(stor_root));
+        storage::register(stor_root, mem.clone());
+        let path = Path::new("/mem_index_file/index.json");
+
+        INDEX.update(stor_root, |index| {
+            index["pages"]["/a.html"] = 1.into();
+            true
+        });
+        assert_eq!(INDEX.read(stor_root)["pages"]["/a.html"], 1);
+
+        // Not written if not changed.
+        mem.set_modified(path, SystemTime::UNIX_EPOCH).unwrap();
+        INDEX.update(stor_root, |_| false);
+        assert_eq!(mem.metadata(path).unwrap().modified, SystemTime::UNIX_EPOCH);
+
+        // Read again if written out of here.
+        mem.write_atomic(path, br#"{"pages": {"/b.html": 2}}"#)
+            .unwrap();
+        assert_eq!(INDEX.read(stor_root)["pages"].dump(), r#"{"/b.html":2}"#);
+
+        INDEX
+            .replace(stor_root, json::object! {"pages": {}})
+            .unwrap();
+        assert_eq!(mem.read(path).unwrap(), br#"{"pages":{}}"#);
+        storage::unregister(stor_root);
+    }
+}
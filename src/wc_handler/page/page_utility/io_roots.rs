@@ -0,0 +1,109 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+use tracing::{error, info};
+
+// Directories out of stor_root that requests write to or read from.
+//
+// page_export and markdown_export write under export_root,
+// markdown_import of a directory reads under import_root.
+// Dirs in requests are relative to the root, "/site" is export_root/site.
+// Those going out of the root by ".." or symlinks are refused.
+//
+// The roots are in stor_root/wc_io.json read on start, relative ones are from
+// the working directory, the defaults are
+// {"export_root": "./wc_export", "import_root": "./wc_import"}
+
+const CONFIG_FILE: &str = "/wc_io.json";
+const EXPORT_ROOT: &str = "./wc_export";
+const IMPORT_ROOT: &str = "./wc_import";
+
+struct IoRoots {
+    export_root: PathBuf,
+    import_root: PathBuf,
+}
+
+static IO_ROOTS: OnceLock<IoRoots> = OnceLock::new();
+
+impl IoRoots {
+    fn from_json(config: &json::JsonValue) -> IoRoots {
+        IoRoots {
+            export_root: PathBuf::from(config["export_root"].as_str().unwrap_or(EXPORT_ROOT)),
+            import_root: PathBuf::from(config["import_root"].as_str().unwrap_or(IMPORT_ROOT)),
+        }
+    }
+}
+
+/// Read the roots from stor_root, it is done once on start.
+pub fn roots_load(stor_root: &str) {
+    let path = super::file_path(stor_root, CONFIG_FILE);
+    let config = match super::storage::read_to_string(Path::new(&path)) {
+        Ok(v) => match json::parse(&v) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed to parse {}, the default is used: {}", path, e);
+                json::JsonValue::Null
+            }
+        },
+        // No config, the default is used.
+        Err(_) => json::JsonValue::Null,
+    };
+    let roots = IoRoots::from_json(&config);
+    info!(
+        "export_root: {:?}, import_root: {:?}",
+        roots.export_root, roots.import_root
+    );
+    let _ = IO_ROOTS.set(roots);
+}
+
+fn roots() -> &'static IoRoots {
+    IO_ROOTS.get_or_init(|| IoRoots::from_json(&json::JsonValue::Null))
+}
+
+/// base + rel, rel should not go out of base.
+/// "/a/b.html" and "a/./b.html" are of base/a/b.html, "../b.html" is Err.
+pub fn path_join(base: &Path, rel: &str) -> Result<PathBuf, String> {
+    let mut path = base.to_path_buf();
+    for component in Path::new(rel.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(name) => path.push(name),
+            Component::CurDir => (),
+            _ => return Err(format!("Out of {:?}: {}", base, rel)),
+        }
+    }
+    Ok(path)
+}
+
+/// If path is in root after symlinks resolved, both should exist.
+fn is_in(root: &Path, path: &Path) -> Result<(), String> {
+    let root = std::fs::canonicalize(root).map_err(|e| format!("{:?}: {}", root, e))?;
+    let path_real = std::fs::canonicalize(path).map_err(|e| format!("{:?}: {}", path, e))?;
+    if !path_real.starts_with(&root) {
+        return Err(format!("Out of {:?}: {:?}", root, path));
+    }
+    Ok(())
+}
+
+/// The dir to export to, out_dir in export_root.
+/// The dir is created if not exists.
+pub fn export_dir(out_dir: &str) -> Result<PathBuf, String> {
+    let root = &roots().export_root;
+    let path = path_join(root, out_dir)?;
+    std::fs::create_dir_all(root)
+        .map_err(|e| format!("Failed to create dir: {:?}, {}", root, e))?;
+    // Not to create dirs through a symlink to out of the root.
+    if let Some(existing) = path.ancestors().find(|ancestor| ancestor.exists()) {
+        is_in(root, existing)?;
+    }
+    std::fs::create_dir_all(&path)
+        .map_err(|e| format!("Failed to create dir: {:?}, {}", path, e))?;
+    is_in(root, &path)?;
+    Ok(path)
+}
+
+/// The dir to import from, dir in import_root.
+pub fn import_dir(dir: &str) -> Result<PathBuf, String> {
+    let root = &roots().import_root;
+    let path = path_join(root, dir)?;
+    is_in(root, &path)?;
+    Ok(path)
+}
@@ -1,20 +1,58 @@
 use super::super::page_json::{PageJson, Subsection};
 use super::dom_utility;
 use markup5ever_rcdom::Handle;
-// use tracing::info; // {error, event, info, instrument, span, Level, Node}
+use tracing::info; // {error, event, info, instrument, span, Level, Node}
+mod json_from_dom_html_common;
+mod json_from_dom_html_heading;
 mod json_from_dom_html_type01;
 mod json_from_dom_html_type02;
 
+/// Importer of html pages those do not contain page data in json,
+/// legacy pages of this system or pages of other tools.
+pub trait HtmlImporter {
+    fn name(&self) -> &'static str;
+
+    /// How likely page_node is of the form this importer reads, 0 for not at all to 100.
+    fn confidence(&self, page_node: &Handle) -> u8;
+
+    /// page_json data from page_node.
+    fn import(&self, page_node: &Handle) -> Option<json::JsonValue>;
+}
+
+/// Add an importer here to read pages of another form.
+const IMPORTERS: &[&dyn HtmlImporter] = &[
+    &json_from_dom_html_type02::Type02,
+    &json_from_dom_html_type01::Type01,
+    &json_from_dom_html_heading::Heading,
+];
+
+/// Importers with confidence more than 0, most confident first.
+fn importers(page_node: &Handle) -> Vec<(&'static dyn HtmlImporter, u8)> {
+    let mut importers: Vec<(&'static dyn HtmlImporter, u8)> = IMPORTERS
+        .iter()
+        .map(|importer| (*importer, importer.confidence(page_node)))
+        .filter(|(_, confidence)| 0 < *confidence)
+        .collect();
+    // stable, the order of IMPORTERS for the same confidence.
+    importers.sort_by_key(|(_, confidence)| std::cmp::Reverse(*confidence));
+    importers
+}
+
 /// Convert page_dom that represent page contents, not page data in json as text,
 /// to page_json data
+/// The most confident importer is used, the next one if it fails.
 pub fn json_from_dom_html(page_node: &Handle) -> Option<json::JsonValue> {
-    if let Some(json) = json_from_dom_html_type02::json_from_dom_html(page_node) {
-        return Some(json);
-    };
-
-    if let Some(json) = json_from_dom_html_type01::json_from_dom_html(page_node) {
-        return Some(json);
+    for (importer, confidence) in importers(page_node) {
+        if let Some(json) = importer.import(page_node) {
+            info!(
+                "imported by {} (confidence {})",
+                importer.name(),
+                confidence
+            );
+            return Some(json);
+        }
     }
 
+    info!("no importer for the html");
     None
 }
@@ -0,0 +1,222 @@
+use super::dom_utility;
+use super::{PageJson, Subsection};
+use html5ever::serialize::{SerializeOpts, TraversalScope};
+use markup5ever_rcdom::{Handle, Node, NodeData, SerializableHandle}; // Handle, RcDom;
+use std::rc::Rc;
+
+// Parts shared by importers of legacy html pages.
+
+// <title>title_name</title>
+pub fn json_html_title(page_node: &Rc<Node>, page_json: &mut json::JsonValue) -> Option<()> {
+    let ptn = dom_utility::node_element("title", &vec![]);
+    let title_node = dom_utility::child_match_first(page_node, &ptn, true)?;
+    if let Some(value) = node_text_first(&title_node) {
+        page_json["data"]["page"]["title"] = value.as_str().into();
+    }
+
+    Some(())
+}
+
+/// Set navi of a elements as [title, href].
+pub fn json_html_navi_set(page_json: &mut json::JsonValue, navi_list: Vec<(String, String)>) {
+    let navis_json = &mut page_json["data"]["navi"];
+    for (href, title) in navi_list {
+        let _ = navis_json.push(json::array![title, href]);
+    }
+}
+
+/// Get href and text content of a node;
+pub fn a_node_href_title(a_node: &Rc<Node>) -> (String, String) {
+    let mut href = None;
+    if let NodeData::Element { attrs, .. } = &a_node.data {
+        for attr in attrs.borrow().iter() {
+            if &attr.name.local == "href" {
+                let value = String::from(&attr.value);
+                href.replace(value);
+                break;
+            }
+        }
+    }
+    let href = href.unwrap_or_default();
+    let title = node_text_first(a_node).unwrap_or_default();
+
+    (href, title)
+}
+
+/// Text of the first text node in children.
+pub fn node_text_first(node: &Rc<Node>) -> Option<String> {
+    for child in node.children.borrow().iter() {
+        if let NodeData::Text { contents } = &child.data {
+            return Some(contents.borrow().to_string());
+        }
+    }
+    None
+}
+
+/// Subsections of li elements in the first ul, ul in li for child subsections.
+pub fn json_html_ul(parent_node: &Rc<Node>, page_json: &mut PageJson, parent_id: &usize) {
+    // the firstest ul
+    // It may be <ul class="listItemBase">, but mo matter what class is.
+    let ptn = dom_utility::node_element("ul", &vec![]);
+
+    let Some(ul_node) = dom_utility::child_match_first(parent_node, &ptn, true) else {
+        return;
+    };
+
+    let ptn = dom_utility::node_element("li", &vec![]);
+    let li_list = dom_utility::child_match_list(&ul_node, &ptn, false, false);
+    for li in li_list {
+        let Some(subsection_id) = json_html_li(&li, parent_id, page_json) else {
+            continue;
+        };
+        json_html_ul(&li, page_json, &subsection_id);
+    }
+}
+
+fn json_html_li(li_node: &Rc<Node>, parent_id: &usize, page_json: &mut PageJson) -> Option<usize> {
+    let ptn = dom_utility::node_element("a", &vec![]);
+    let a_node = dom_utility::child_match_first(li_node, &ptn, false)?;
+
+    let (href, title) = a_node_href_title(&a_node);
+    if href.is_empty() || href == "#" {
+        return None;
+    }
+
+    let mut subsection = page_json.subsection_new(parent_id)?;
+
+    subsection.title_set(&title);
+    subsection.href_set(&href);
+    let subsection_id = subsection.id();
+
+    Some(subsection_id)
+}
+
+/// How contents of <div class="subsection"> are read in a page type.
+pub struct SubsectionForm {
+    /// If div.subsection are searched in descendants of body, or in children.
+    pub recursive: bool,
+    /// Name (key) of the subsection, without "#".
+    pub name: fn(&Rc<Node>) -> Option<String>,
+    /// class of the div containing contents.
+    pub contents_class: &'static str,
+    /// Push a content of a child node of the contents div.
+    pub content_set: fn(&mut json::JsonValue, &Rc<Node>),
+}
+
+pub fn subsections_contents_set(
+    page_node: &Rc<Node>,
+    page_json: &mut PageJson,
+    form: &SubsectionForm,
+) -> Option<()> {
+    let ptn = dom_utility::node_element("body", &vec![]);
+    let body_node = dom_utility::child_match_first(page_node, &ptn, true)?;
+
+    // <div class="subsection">
+    let attrs = &vec![("class", "subsection")];
+    let ptn = dom_utility::node_element("div", attrs);
+    let div_list = dom_utility::child_match_list(&body_node, &ptn, form.recursive, false);
+
+    for subsection_node in div_list {
+        subsection_contents_set(&subsection_node, page_json, form);
+    }
+
+    Some(())
+}
+
+fn subsection_contents_set(
+    subsection_node: &Rc<Node>,
+    page_json: &mut PageJson,
+    form: &SubsectionForm,
+) -> Option<()> {
+    let mut name = (form.name)(subsection_node)?;
+
+    if name.is_empty() {
+        return None;
+    }
+
+    // # + subsection_name
+    name.insert(0, '#');
+
+    // Get subsection defined in json_html_ul referring by name.
+    if page_json.subsection_by_name(&name).is_none() {
+        subsection_by_subsection_node(subsection_node, page_json, &name)?;
+    }
+    let mut subsection = page_json.subsection_by_name(&name)?;
+
+    // contents
+    let attrs = &vec![("class", form.contents_class)];
+    let ptn = dom_utility::node_element("div", attrs);
+    let contents_node = dom_utility::child_match_first(subsection_node, &ptn, false)?;
+
+    let subsection_content = subsection.contents_mut();
+    for content in contents_node.children.borrow().iter() {
+        (form.content_set)(subsection_content, content);
+    }
+
+    Some(())
+}
+
+fn subsection_by_subsection_node<'a>(
+    subsection_node: &Rc<Node>,
+    page_json: &'a mut PageJson,
+    name: &str,
+) -> Option<Subsection<'a>> {
+    // Create a new subseciton.
+    // Since name is not found in li elements, consume that parent_id = 0.
+    let parent_id = 0;
+    let mut subsection = page_json.subsection_new(&parent_id)?;
+    // title <div class="subsectionTitle">
+    let attrs = &vec![("class", "subsectionTitle")];
+    let ptn = dom_utility::node_element("div", attrs);
+    let title = dom_utility::child_match_first(subsection_node, &ptn, true)
+        .and_then(|div_node| node_text_first(&div_node))
+        .unwrap_or_default();
+    subsection.href_set(name);
+    subsection.title_set(&title);
+
+    Some(subsection)
+}
+
+/// Serialize children of the node as html text.
+pub fn node_serialize(node: &Rc<Node>) -> Option<String> {
+    node_serialize_opts(node, SerializeOpts::default())
+}
+
+/// Serialize the node including itself as html text.
+pub fn node_serialize_outer(node: &Rc<Node>) -> Option<String> {
+    let opts = SerializeOpts {
+        traversal_scope: TraversalScope::IncludeNode,
+        ..Default::default()
+    };
+    node_serialize_opts(node, opts)
+}
+
+fn node_serialize_opts(node: &Rc<Node>, opts: SerializeOpts) -> Option<String> {
+    let sh = SerializableHandle::from(node.clone());
+    let mut bytes = vec![];
+    html5ever::serialize(&mut bytes, &sh, opts).ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// < to \<, > to \>
+pub fn text_contents_gt_lt_escape(contents: &str) -> String {
+    contents.replace('<', "\\<").replace('>', "\\>")
+}
+
+/// Value of the attribute of an element.
+pub fn attr_value(node: &Rc<Node>, name: &str) -> Option<String> {
+    if let NodeData::Element { attrs, .. } = &node.data {
+        for attr in attrs.borrow().iter() {
+            if &attr.name.local == name {
+                return Some(String::from(&attr.value));
+            }
+        }
+    }
+    None
+}
+
+/// Handle of the body element, or the node if not found.
+pub fn body_node(page_node: &Handle) -> Handle {
+    let ptn = dom_utility::node_element("body", &vec![]);
+    dom_utility::child_match_first(page_node, &ptn, true).unwrap_or(page_node.clone())
+}
@@ -0,0 +1,222 @@
+use super::json_from_dom_html_common::{
+    attr_value, body_node, json_html_title, node_serialize_outer,
+};
+use super::{HtmlImporter, PageJson};
+use markup5ever_rcdom::{Handle, Node, NodeData}; // Handle, RcDom;
+use std::rc::Rc;
+
+// Html of any kind, saved web pages or pages of other tools.
+// <h1>-<h6> make subsections, a heading of lower level is a child subsection
+// of the previous heading of higher level.
+// Nodes between headings are the contents of the subsection as html.
+// Contents before the first heading go to a subsection titled as the page.
+// Html without headings is not of this form, eg: non-wc html linked as a child,
+// it is not imported to be rewritten by mainte.
+
+pub struct Heading;
+
+impl HtmlImporter for Heading {
+    fn name(&self) -> &'static str {
+        "heading"
+    }
+
+    fn confidence(&self, page_node: &Handle) -> u8 {
+        if has_heading(&body_node(page_node)) {
+            30
+        } else {
+            0
+        }
+    }
+
+    fn import(&self, page_node: &Handle) -> Option<json::JsonValue> {
+        let mut page_json = PageJson::new();
+        let page_json_data = page_json.value_mut()?;
+        // title is not necessary
+        let _ = json_html_title(page_node, page_json_data);
+        let title = page_json_data["data"]["page"]["title"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        let mut import = Import {
+            page_json: &mut page_json,
+            title,
+            stack: vec![],
+            contents: String::new(),
+        };
+        import.node(&body_node(page_node))?;
+        import.contents_flush()?;
+
+        page_json.value_take()
+    }
+}
+
+/// 1 for h1 to 6 for h6, None if not a heading.
+fn heading_level(node: &Rc<Node>) -> Option<usize> {
+    let NodeData::Element { name, .. } = &node.data else {
+        return None;
+    };
+    match name.local.as_ref() {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+fn has_heading(node: &Rc<Node>) -> bool {
+    node.children
+        .borrow()
+        .iter()
+        .any(|child| heading_level(child).is_some() || has_heading(child))
+}
+
+/// Elements not to be contents.
+fn is_skipped(node: &Rc<Node>) -> bool {
+    match &node.data {
+        NodeData::Element { name, .. } => matches!(
+            name.local.as_ref(),
+            "script" | "style" | "noscript" | "template" | "link" | "meta"
+        ),
+        NodeData::Comment { .. } => true,
+        _ => false,
+    }
+}
+
+/// All text in descendants.
+fn text_contents(node: &Rc<Node>) -> String {
+    let mut text = String::new();
+    for child in node.children.borrow().iter() {
+        match &child.data {
+            NodeData::Text { contents } => text.push_str(&contents.borrow()),
+            _ => text.push_str(&text_contents(child)),
+        }
+    }
+    text
+}
+
+/// Text as html text.
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+struct Import<'a> {
+    page_json: &'a mut PageJson,
+    title: String,
+    // (level, id) of headings containing the current position.
+    stack: Vec<(usize, usize)>,
+    // html of contents not set to a subsection yet.
+    contents: String,
+}
+
+impl Import<'_> {
+    fn node(&mut self, node: &Rc<Node>) -> Option<()> {
+        for child in node.children.borrow().iter() {
+            if is_skipped(child) {
+                continue;
+            }
+
+            if let Some(level) = heading_level(child) {
+                self.contents_flush()?;
+                self.heading(child, level)?;
+                continue;
+            }
+
+            // Containers of headings like <div>, <section> are opened.
+            if has_heading(child) {
+                self.node(child)?;
+                continue;
+            }
+
+            match &child.data {
+                NodeData::Text { contents } => {
+                    self.contents.push_str(&html_escape(&contents.borrow()))
+                }
+                NodeData::Element { .. } => {
+                    if let Some(html) = node_serialize_outer(child) {
+                        self.contents.push_str(&html);
+                    }
+                }
+                _ => (),
+            }
+        }
+        Some(())
+    }
+
+    fn heading(&mut self, node: &Rc<Node>, level: usize) -> Option<()> {
+        while self.stack.last().is_some_and(|(l, _)| level <= *l) {
+            self.stack.pop();
+        }
+        let parent_id = self.stack.last().map(|(_, id)| *id).unwrap_or(0);
+
+        let title = text_contents(node)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let href = attr_value(node, "id")
+            .map(|id| format!("#{}", id))
+            .unwrap_or_default();
+        let id = self.subsection_new(&parent_id, &title, &href)?;
+        self.stack.push((level, id));
+        Some(())
+    }
+
+    /// Set contents to the current subsection.
+    fn contents_flush(&mut self) -> Option<()> {
+        let contents = std::mem::take(&mut self.contents);
+        if contents.trim().is_empty() {
+            return Some(());
+        }
+
+        let id = match self.stack.last() {
+            Some((_, id)) => *id,
+            None => {
+                // before the first heading
+                let title = self.title.clone();
+                let id = self.subsection_new(&0, &title, "")?;
+                // following contents before a heading go to the same subsection.
+                self.stack.push((usize::MAX, id));
+                id
+            }
+        };
+
+        let mut subsection = self.page_json.subsection_by_id(&id)?;
+        let _ = subsection
+            .contents_mut()
+            .push(json::object! {"type": "html", "value": contents.trim()});
+        Some(())
+    }
+
+    fn subsection_new(&mut self, parent_id: &usize, title: &str, href: &str) -> Option<usize> {
+        let mut subsection = self.page_json.subsection_new(parent_id)?;
+        subsection.title_set(title);
+        subsection.href_set(href);
+        Some(subsection.id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::dom_utility::to_dom;
+    use super::*;
+
+    #[test]
+    fn confidence_heading() {
+        let dom = to_dom("<html><body><h2>a</h2><p>b</p></body></html>");
+        assert_eq!(Heading.confidence(&dom.document), 30);
+        let page_json = Heading.import(&dom.document).unwrap();
+        assert!(!page_json["data"]["subsection"]["data"].is_empty());
+    }
+
+    #[test]
+    fn confidence_no_heading() {
+        let dom = to_dom("<html><body><p>a</p><div>b</div></body></html>");
+        assert_eq!(Heading.confidence(&dom.document), 0);
+        assert!(super::super::json_from_dom_html(&dom.document).is_none());
+    }
+}
@@ -0,0 +1,346 @@
+use super::page_json;
+use super::page_json::page_data::{ContentType, Subsection, Subsections};
+use super::Page;
+use std::collections::HashSet;
+use std::path::Path;
+use tracing::info;
+
+// Convert PageJson to CommonMark.
+//
+// page title            : # title
+// subsection (depth 1)  : ## title
+// subsection (depth 2)  : ### title, up to ######
+// "text" content        : paragraphs, "\n" to a hard line break "\"
+// "html" content        : inlined as a html block
+// "script" content      : fenced code block with "lang"
+// "markdown" content    : as it is
+// "table" content       : table of GitHub Flavored Markdown
+//
+// Links to child pages "child/child.html" become "child/child.md".
+
+/// Heading level of the deepest subsections.
+const HEADING_MAX: usize = 6;
+
+/// Return markdown of the page.
+pub fn page_markdown(page: &mut Page) -> Result<String, String> {
+    let page_url = super::page_url(page)?;
+    let page_json = page
+        .json()
+        .ok_or(format!("Failed to get page_json of {}", page_url.path()))?;
+    markdown_from_json(&page_url, page_json)
+}
+
+/// Write markdown of the page into out_dir in export_root as page_path with .md.
+/// recursive: pages under the page found by page_children_url are written as well.
+/// Return page paths of markdown written.
+pub fn page_markdown_export(
+    page: &mut Page,
+    out_dir: &str,
+    recursive: bool,
+) -> Result<Vec<String>, String> {
+    let stor_root = page.stor_root().to_string();
+    let out_dir = &super::page_export::out_dir_resolve(&stor_root, out_dir)?;
+
+    let mut md_paths = vec![];
+    let mut handled = HashSet::new();
+    let mut page_path_s = vec![page.page_path().to_string()];
+
+    while let Some(page_path) = page_path_s.pop() {
+        if !handled.insert(page_path.clone()) {
+            continue;
+        }
+
+        let mut page = Page::new(&stor_root, &page_path);
+        let markdown = match page.json() {
+            // Contents were moved.
+            Some(page_json) if page_json.moved_to().is_some() => continue,
+            Some(_) => page_markdown(&mut page)?,
+            None => continue,
+        };
+
+        let md_path = md_path(&page_path);
+        let out_path = out_dir.to_string() + &md_path;
+        super::dir_build(Path::new(&out_path), true)?;
+        super::fs_write(&out_path, &markdown.into_bytes())?;
+        info!("markdown: {}", out_path);
+        md_paths.push(md_path);
+
+        if !recursive {
+            break;
+        }
+
+        let mut child_path_s: Vec<String> = super::page_children_url(&mut page)
+            .iter()
+            .map(|url| url.path().to_string())
+            .collect();
+        child_path_s.reverse();
+        page_path_s.append(&mut child_path_s);
+    }
+
+    Ok(md_paths)
+}
+
+/// "/child/child.html" to "/child/child.md"
+fn md_path(page_path: &str) -> String {
+    let stem = page_path
+        .strip_suffix(".html")
+        .or(page_path.strip_suffix(".htm"))
+        .unwrap_or(page_path);
+    stem.to_string() + ".md"
+}
+
+pub fn markdown_from_json(
+    page_url: &url::Url,
+    page_json: &page_json::PageJson,
+) -> Result<String, String> {
+    let page_data = page_json.page_data()?;
+
+    let mut markdown = format!("# {}\n", text_inline(&page_data.page.title));
+
+    // Check dublication of id to avoid endlessloop.
+    let mut handled = HashSet::new();
+    subsections_markdown(
+        page_url,
+        &page_data.subsection,
+        &0,
+        1,
+        &mut handled,
+        &mut markdown,
+    );
+
+    Ok(markdown)
+}
+
+fn subsections_markdown(
+    page_url: &url::Url,
+    subsections: &Subsections,
+    parent_id: &usize,
+    depth: usize,
+    handled: &mut HashSet<usize>,
+    markdown: &mut String,
+) {
+    if !handled.insert(*parent_id) {
+        return;
+    }
+
+    let Some(parent) = subsections.get(parent_id) else {
+        return;
+    };
+    for child_id in parent.child.iter() {
+        let Some(subsection) = subsections.get(child_id) else {
+            continue;
+        };
+
+        subsection_markdown(page_url, subsection, depth, markdown);
+        subsections_markdown(
+            page_url,
+            subsections,
+            child_id,
+            depth + 1,
+            handled,
+            markdown,
+        );
+    }
+}
+
+fn subsection_markdown(
+    page_url: &url::Url,
+    subsection: &Subsection,
+    depth: usize,
+    markdown: &mut String,
+) {
+    let level = (depth + 1).min(HEADING_MAX);
+    let title = text_inline(&subsection.title);
+    let href = subsection.href.as_str();
+
+    markdown.push('\n');
+    // "#abc" is the name of the subsection,
+    // it is kept as an anchor so that links to it still work.
+    if let Some(name) = href.strip_prefix('#').filter(|name| !name.is_empty()) {
+        *markdown += &format!("<a id=\"{}\"></a>\n\n", name);
+    }
+    *markdown += &"#".repeat(level);
+    markdown.push(' ');
+    if href.is_empty() || href.starts_with('#') {
+        *markdown += &title;
+    } else {
+        let href = href_md(page_url, href).unwrap_or(href.to_string());
+        *markdown += &format!("[{}]({})", title, href);
+    }
+    markdown.push('\n');
+
+    for content in subsection.content.iter() {
+        let value = super::page_move::content_href_convert(&content.value, &mut |href| {
+            href_md(page_url, href)
+        });
+
+        let block = match content.content_type {
+            ContentType::Markdown => {
+                let value = super::page_move::markdown_href_convert(&value, &mut |href| {
+                    href_md(page_url, href)
+                });
+                let value = value.trim_end_matches('\n');
+                if value.is_empty() {
+                    String::new()
+                } else {
+                    value.to_string() + "\n"
+                }
+            }
+            ContentType::Table => super::content_table::Table::from_content(content).to_markdown(),
+            ContentType::Script => script_markdown(&value, content.attr("lang").unwrap_or("")),
+            ContentType::Html => html_markdown(&value),
+            _ => text_markdown(&value),
+        };
+        if block.is_empty() {
+            continue;
+        }
+
+        markdown.push('\n');
+        *markdown += &block;
+    }
+}
+
+/// Convert href to a child page to .md
+/// eg: "child/child.html#abc" to "child/child.md#abc"
+/// Return None if the href is not to a child page.
+fn href_md(page_url: &url::Url, href: &str) -> Option<String> {
+    let (href, is_child) = super::href_on(page_url, href)?;
+    if !is_child {
+        return None;
+    }
+
+    let (path, fragment) = match href.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (href.as_str(), None),
+    };
+    let mut href = path
+        .strip_suffix(".html")
+        .or(path.strip_suffix(".htm"))?
+        .to_string()
+        + ".md";
+    if let Some(fragment) = fragment {
+        href = href + "#" + fragment;
+    }
+    Some(href)
+}
+
+/// Escape charactors that work as markdown in a line.
+fn text_inline(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Paragraphs are separated by "\n\n",
+/// lines in a paragraph are joined with a hard line break "\" at the end.
+/// html elements in the text are left as inline html,
+/// "\<", "\>" are kept as they are since those are escapes in markdown as well.
+fn text_markdown(value: &str) -> String {
+    let mut paragraphs = vec![];
+    for paragraph in value.split("\n\n") {
+        let lines: Vec<String> = paragraph
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(text_line)
+            .collect();
+        if lines.is_empty() {
+            continue;
+        }
+        paragraphs.push(lines.join("\\\n") + "\n");
+    }
+    paragraphs.join("\n")
+}
+
+/// Escape markdown in a line of text except in html tags.
+/// Spaces in the beginning of the line are kept as &nbsp;
+/// not to be a code block.
+fn text_line(line: &str) -> String {
+    let indent: String = line
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .map(|c| {
+            if c == '\t' {
+                "&nbsp;".repeat(4)
+            } else {
+                "&nbsp;".to_string()
+            }
+        })
+        .collect();
+    let line = line.trim_start_matches([' ', '\t']);
+
+    let mut escaped = indent;
+    let mut chars = line.chars().peekable();
+    let mut in_tag = false;
+    let mut line_start = true;
+
+    while let Some(c) = chars.next() {
+        if in_tag {
+            escaped.push(c);
+            if c == '>' {
+                in_tag = false;
+            }
+            continue;
+        }
+
+        match c {
+            // "\<", "\>" or "\\"
+            '\\' => {
+                escaped.push(c);
+                if let Some(next) = chars.next_if(|next| matches!(next, '<' | '>' | '\\')) {
+                    escaped.push(next);
+                } else {
+                    escaped.push('\\');
+                }
+            }
+            '<' => {
+                in_tag = true;
+                escaped.push(c);
+            }
+            '`' | '*' | '_' | '[' | ']' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '#' | '>' | '-' | '+' | '=' | '|' if line_start => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+        line_start = false;
+    }
+
+    escaped
+}
+
+/// html is inlined as a html block.
+/// A blank line ends a html block of markdown, so those are removed.
+fn html_markdown(value: &str) -> String {
+    let lines: Vec<&str> = value
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    if lines.is_empty() {
+        return "".to_string();
+    }
+    format!("<div>\n{}\n</div>\n", lines.join("\n"))
+}
+
+/// "\<", "\>" to "<", ">" in a fenced code block.
+/// The fence is longer than backticks in the value.
+/// lang is put as the info string of the fence, eg: ```rust
+fn script_markdown(value: &str, lang: &str) -> String {
+    let value = value.replace("\\<", "<").replace("\\>", ">");
+    let value = value.trim_end_matches('\n');
+
+    let mut fence = "```".to_string();
+    while value.contains(&fence) {
+        fence.push('`');
+    }
+
+    format!("{}{}\n{}\n{}\n", fence, lang.trim(), value, fence)
+}
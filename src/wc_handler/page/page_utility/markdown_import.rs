@@ -0,0 +1,611 @@
+use super::page_json;
+use super::Page;
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag};
+use std::collections::HashSet;
+use std::path::Path;
+use tracing::{error, info};
+
+// Convert markdown to PageJson.
+//
+// # title (the first heading of level 1) : title of the page
+// ## heading, ### heading ...            : subsections nested by their levels
+// paragraphs                             : "text" content, inlines as html elements
+// code blocks                            : "script" content, with "lang" of ```lang
+// lists, tables, block quotes, html      : "html" content
+//
+// Links to "xxx.md" become "xxx.html".
+// Contents before the first heading are put in a subsection titled as the page.
+
+/// Names of markdown files used as the contents of the page of their directory.
+const DIR_INDEX_FILES: [&str; 2] = ["index.md", "README.md"];
+
+/// Result of importing markdown.
+pub struct Import {
+    pub imported: Vec<String>,
+    pub failed: Vec<(String, String)>,
+}
+
+impl Import {
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut failed = json::array![];
+        for (path, e) in self.failed.iter() {
+            let _ = failed.push(json::object! {"path": path.as_str(), "Err": e.as_str()});
+        }
+        json::object! {
+            "res": "imported",
+            "imported": self.imported.clone(),
+            "failed": failed,
+        }
+    }
+}
+
+/// Create a page at child_href under parent_page from markdown
+/// and a subsection linking to it in parent_page.
+/// title: title of the page, the first heading of level 1 or child_href if None.
+/// Return the path of the page created.
+pub fn page_markdown_import(
+    parent_page: &mut Page,
+    child_href: &str,
+    markdown: &str,
+    title: Option<&str>,
+) -> Result<String, String> {
+    let (md_title, subsection) = subsection_from_markdown(markdown);
+    let title = title
+        .map(|title| title.trim().to_string())
+        .filter(|title| !title.is_empty())
+        .or(md_title)
+        .unwrap_or(title_from_href(child_href));
+
+    let mut child_page =
+        super::page_child_new(parent_page, &title, child_href).or(Err(format!(
+            "Failed to create page_child of {} on href: {}",
+            parent_page.page_path(),
+            child_href
+        )))?;
+
+    let child_json = child_page
+        .json_mut()
+        .and_then(page_json::PageJson::value_mut)
+        .ok_or("Failed to get page_json.".to_string())?;
+    child_json["data"]["subsection"] = subsection;
+    // Html written in the markdown, as json_save does on contents posted.
+    let sanitized = super::html_sanitize::page_json_sanitize(child_json);
+    if !sanitized.is_empty() {
+        info!(
+            "sanitized on import of {}: {}",
+            child_href,
+            sanitized.dump()
+        );
+    }
+
+    child_page.dir_build()?;
+    child_page
+        .file_save_and_rev()
+        .or(Err(format!("Failed to save: {}", child_page.file_path())))?;
+
+    parent_link_add(parent_page, &title, child_page.page_path())?;
+
+    info!("markdown imported: {}", child_page.page_path());
+
+    Ok(child_page.page_path().to_string())
+}
+
+/// Import a directory of markdown files as a page tree under parent_page.
+/// The directory becomes a page at dir_href with index.md or README.md as its contents,
+/// files of .md in it become child pages, sub directories become child trees.
+/// eg: dir_href: "notes/notes.html"
+///   notes/README.md  : notes/notes.html
+///   notes/a.md       : notes/a.html
+///   notes/sub/b.md   : notes/sub/sub.html, notes/sub/b.html
+pub fn dir_markdown_import(parent_page: &mut Page, dir: &Path, dir_href: &str) -> Import {
+    let mut import = Import {
+        imported: vec![],
+        failed: vec![],
+    };
+    dir_import(parent_page, dir, dir_href, &mut import);
+    import
+}
+
+fn dir_import(parent_page: &mut Page, dir: &Path, dir_href: &str, import: &mut Import) {
+    let dir_name = dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("")
+        .to_string();
+
+    let mut entries: Vec<std::path::PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries.flatten().map(|entry| entry.path()).collect(),
+        Err(e) => {
+            error!("Failed to read dir: {:?}, {}", dir, e);
+            import
+                .failed
+                .push((dir.display().to_string(), e.to_string()));
+            return;
+        }
+    };
+    entries.sort();
+
+    let index_markdown = DIR_INDEX_FILES
+        .iter()
+        .find_map(|index| std::fs::read_to_string(dir.join(index)).ok())
+        .unwrap_or_default();
+
+    let dir_path = match page_markdown_import(parent_page, dir_href, &index_markdown, None) {
+        Ok(dir_path) => dir_path,
+        Err(e) => {
+            error!("{}", e);
+            import.failed.push((dir.display().to_string(), e));
+            return;
+        }
+    };
+    import.imported.push(dir_path.clone());
+    let mut dir_page = Page::new(parent_page.stor_root(), &dir_path);
+
+    for path in entries {
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        // hidden files and dirs
+        if file_name.starts_with('.') {
+            continue;
+        }
+        // Not to read out of the dir imported.
+        if path.is_symlink() {
+            continue;
+        }
+
+        if path.is_dir() {
+            let child_href = format!("{}/{}.html", file_name, file_name);
+            dir_import(&mut dir_page, &path, &child_href, import);
+            continue;
+        }
+
+        if DIR_INDEX_FILES.contains(&file_name) {
+            continue;
+        }
+        let Some(stem) = file_name.strip_suffix(".md") else {
+            continue;
+        };
+        // The page of the directory itself.
+        if stem == dir_name {
+            import.failed.push((
+                path.display().to_string(),
+                "Same name as the directory page".to_string(),
+            ));
+            continue;
+        }
+
+        let res = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|markdown| {
+                page_markdown_import(
+                    &mut dir_page,
+                    &(stem.to_string() + ".html"),
+                    &markdown,
+                    None,
+                )
+            });
+        match res {
+            Ok(page_path) => import.imported.push(page_path),
+            Err(e) => {
+                error!("{}", e);
+                import.failed.push((path.display().to_string(), e));
+            }
+        }
+    }
+}
+
+/// "child/child.html" to "child"
+fn title_from_href(href: &str) -> String {
+    let file_name = href.rsplit('/').next().unwrap_or(href);
+    file_name.split('.').next().unwrap_or(file_name).to_string()
+}
+
+/// Add a subsection linking to child_path at the end of parent_page.
+fn parent_link_add(parent_page: &mut Page, title: &str, child_path: &str) -> Result<(), String> {
+    let parent_url = super::page_url(parent_page)?;
+    let (href, _) = super::href_on(&parent_url, child_path)
+        .ok_or(format!("Failed to get href of: {}", child_path))?;
+
+    let parent_json = parent_page
+        .json_mut()
+        .ok_or("Failed to get page_json.".to_string())?;
+    let mut subsection = parent_json
+        .subsection_new(&0)
+        .ok_or("Failed to create subsection".to_string())?;
+    subsection.title_set(title);
+    subsection.href_set(&href);
+
+    super::page_save_rev_up(parent_page)
+}
+
+/// Return the title from the first heading of level 1
+/// and value["data"]["subsection"] of PageJson from markdown.
+pub fn subsection_from_markdown(markdown: &str) -> (Option<String>, json::JsonValue) {
+    let mut page_json = page_json::PageJson::from(page_json::page_json_plain());
+
+    let mut events = Parser::new_ext(markdown, super::markdown_options()).map(event_link_md);
+
+    let mut title = None;
+    // (heading level, subsection id) of the parents
+    let mut parents: Vec<(usize, usize)> = vec![];
+    // subsection id that contents are put in
+    let mut current = None;
+    let mut hrefs = HashSet::new();
+    let mut contents = Contents::default();
+
+    while let Some(event) = events.next() {
+        match event {
+            Event::Start(Tag::Heading(level, _, _)) => {
+                let heading = heading_text(&mut events);
+                let level = level as usize;
+
+                // The first heading of level 1 is the title of the page.
+                if level == 1 && title.is_none() && parents.is_empty() {
+                    title = Some(heading);
+                    continue;
+                }
+
+                contents.flush(&mut page_json, &mut current, &title, &mut hrefs);
+                while parents
+                    .last()
+                    .is_some_and(|(parent_level, _)| level <= *parent_level)
+                {
+                    parents.pop();
+                }
+                let parent_id = parents.last().map_or(0, |(_, id)| *id);
+                if let Some(id) = subsection_add(&mut page_json, &parent_id, &heading, &mut hrefs) {
+                    parents.push((level, id));
+                    current = Some(id);
+                }
+            }
+            Event::Start(Tag::Paragraph) => {
+                let text = inline_text(&mut events, Tag::Paragraph);
+                contents.text_push(&text);
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                // ```rust : "lang" of the script
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => {
+                        info.split_whitespace().next().unwrap_or("").to_string()
+                    }
+                    CodeBlockKind::Indented => "".to_string(),
+                };
+                let code = code_text(&mut events);
+                contents.script_push(&code, &lang);
+            }
+            Event::Start(tag) => {
+                let html = block_html(&mut events, tag);
+                contents.html_push(&html);
+            }
+            Event::Html(html) => contents.html_push(&html),
+            Event::Rule => contents.html_push("<hr>"),
+            Event::Text(text) => contents.text_push(&text_escape(&text)),
+            _ => (),
+        }
+    }
+    contents.flush(&mut page_json, &mut current, &title, &mut hrefs);
+
+    let subsection = page_json
+        .value_take()
+        .map(|mut value| value["data"]["subsection"].take())
+        .unwrap_or(json::JsonValue::Null);
+    (title, subsection)
+}
+
+/// Contents read but not put into a subsection yet.
+#[derive(Default)]
+struct Contents {
+    text: Vec<String>,
+    contents: Vec<json::JsonValue>,
+}
+
+impl Contents {
+    /// Paragraphs in a row are put in a "text" content separated by "\n\n".
+    fn text_push(&mut self, text: &str) {
+        self.text.push(text.to_string());
+    }
+
+    fn text_close(&mut self) {
+        if self.text.is_empty() {
+            return;
+        }
+        let text = self.text.join("\n\n");
+        self.text.clear();
+        self.contents
+            .push(json::object! {"type": "text", "value": text});
+    }
+
+    fn script_push(&mut self, value: &str, lang: &str) {
+        self.text_close();
+        let mut content = json::object! {"type": "script", "value": value};
+        if !lang.is_empty() {
+            content["lang"] = lang.into();
+        }
+        self.contents.push(content);
+    }
+
+    /// html in a row is put in a "html" content.
+    fn html_push(&mut self, html: &str) {
+        self.text_close();
+        if let Some(last) = self.contents.last_mut() {
+            if last["type"] == "html" {
+                let value = last["value"].as_str().unwrap_or("").to_string() + html;
+                last["value"] = value.into();
+                return;
+            }
+        }
+        self.contents
+            .push(json::object! {"type": "html", "value": html});
+    }
+
+    /// Put contents into the last subsection.
+    /// Contents before any headings are put into a new subsection titled as the page.
+    fn flush(
+        &mut self,
+        page_json: &mut page_json::PageJson,
+        current: &mut Option<usize>,
+        title: &Option<String>,
+        hrefs: &mut HashSet<String>,
+    ) {
+        self.text_close();
+        if self.contents.is_empty() {
+            return;
+        }
+
+        let id = match current {
+            Some(id) => *id,
+            None => {
+                let title = title.as_deref().unwrap_or("");
+                let Some(id) = subsection_add(page_json, &0, title, hrefs) else {
+                    return;
+                };
+                current.replace(id);
+                id
+            }
+        };
+
+        let Some(value) = page_json.value_mut() else {
+            return;
+        };
+        let subsection = &mut value["data"]["subsection"]["data"][id.to_string().as_str()];
+        for content in self.contents.drain(..) {
+            let _ = subsection["content"].push(content);
+        }
+    }
+}
+
+/// Create a subsection under parent_id with href made from the title.
+fn subsection_add(
+    page_json: &mut page_json::PageJson,
+    parent_id: &usize,
+    title: &str,
+    hrefs: &mut HashSet<String>,
+) -> Option<usize> {
+    let id_next = page_json.subsection_id_next()?;
+    let href = href_new(title, id_next, hrefs);
+
+    let mut subsection = page_json.subsection_new(parent_id)?;
+    subsection.title_set(title);
+    subsection.href_set(&href);
+    subsection.contents_mut();
+    let id = subsection.id();
+
+    if let Some(value) = page_json.value_mut() {
+        value["data"]["subsection"]["data"][id.to_string().as_str()]["child"] = json::array![];
+    }
+    Some(id)
+}
+
+/// Make "#name" from the title as anchors of markdown
+/// so that links "xxx.md#name" keep working.
+/// eg: "Hello World" : "#hello-world"
+fn href_new(title: &str, id: usize, hrefs: &mut HashSet<String>) -> String {
+    let name: String = title
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect();
+    let name = if name.is_empty() {
+        format!("subsection{}", id)
+    } else {
+        name
+    };
+
+    let mut href = "#".to_string() + &name;
+    let mut i = 1;
+    while !hrefs.insert(href.clone()) {
+        href = format!("#{}-{}", name, i);
+        i += 1;
+    }
+    href
+}
+
+/// Convert links to markdown files "xxx.md" to "xxx.html".
+fn event_link_md(event: Event) -> Event {
+    match event {
+        Event::Start(Tag::Link(link_type, dest, title)) => {
+            Event::Start(Tag::Link(link_type, href_html(&dest).into(), title))
+        }
+        Event::End(Tag::Link(link_type, dest, title)) => {
+            Event::End(Tag::Link(link_type, href_html(&dest).into(), title))
+        }
+        event => event,
+    }
+}
+
+/// "child/child.md#abc" to "child/child.html#abc"
+/// Links with scheme like "https://example.com/a.md" are left as they are.
+fn href_html(href: &str) -> String {
+    if href.contains("://") {
+        return href.to_string();
+    }
+    let (path, fragment) = match href.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (href, None),
+    };
+    let Some(stem) = path.strip_suffix(".md") else {
+        return href.to_string();
+    };
+    let mut href = stem.to_string() + ".html";
+    if let Some(fragment) = fragment {
+        href = href + "#" + fragment;
+    }
+    href
+}
+
+fn heading_text<'a>(events: &mut impl Iterator<Item = Event<'a>>) -> String {
+    let mut heading = String::new();
+    for event in events.by_ref() {
+        match event {
+            Event::End(Tag::Heading(..)) => break,
+            Event::Text(text) | Event::Code(text) => heading += &text,
+            Event::SoftBreak | Event::HardBreak => heading.push(' '),
+            _ => (),
+        }
+    }
+    heading.trim().to_string()
+}
+
+fn code_text<'a>(events: &mut impl Iterator<Item = Event<'a>>) -> String {
+    let mut code = String::new();
+    for event in events.by_ref() {
+        match event {
+            Event::End(Tag::CodeBlock(_)) => break,
+            Event::Text(text) => code += &text,
+            _ => (),
+        }
+    }
+    text_escape(code.trim_end_matches('\n'))
+}
+
+/// Inline elements until the end of the tag as a value of "text" content.
+/// Inline markdown becomes html elements.
+fn inline_text<'a>(events: &mut impl Iterator<Item = Event<'a>>, end: Tag) -> String {
+    let mut text = String::new();
+    // alt of an image
+    let mut alt: Option<String> = None;
+
+    for event in events.by_ref() {
+        if let Some(alt_text) = alt.as_mut() {
+            match event {
+                Event::End(Tag::Image(_, src, title)) => {
+                    text += &format!(
+                        "<img src=\"{}\" alt=\"{}\" title=\"{}\">",
+                        attr_escape(&src),
+                        attr_escape(alt_text),
+                        attr_escape(&title)
+                    );
+                    alt = None;
+                }
+                Event::Text(value) | Event::Code(value) => alt_text.push_str(&value),
+                _ => (),
+            }
+            continue;
+        }
+
+        match event {
+            Event::End(tag) if tag == end => break,
+            Event::Text(value) => text += &text_escape(&value),
+            Event::Code(value) => text += &format!("<code>{}</code>", html_escape(&value)),
+            Event::Html(html) => text += &html,
+            Event::SoftBreak => text.push(' '),
+            Event::HardBreak => text.push('\n'),
+            Event::FootnoteReference(name) => text += &format!("[^{}]", text_escape(&name)),
+            Event::TaskListMarker(checked) => text += if checked { "[x] " } else { "[ ] " },
+            Event::Start(Tag::Emphasis) => text += "<em>",
+            Event::End(Tag::Emphasis) => text += "</em>",
+            Event::Start(Tag::Strong) => text += "<b>",
+            Event::End(Tag::Strong) => text += "</b>",
+            Event::Start(Tag::Strikethrough) => text += "<s>",
+            Event::End(Tag::Strikethrough) => text += "</s>",
+            Event::Start(Tag::Link(_, dest, _)) => {
+                text += &format!("<a href=\"{}\">", attr_escape(&dest));
+            }
+            Event::End(Tag::Link(..)) => text += "</a>",
+            Event::Start(Tag::Image(..)) => alt = Some(String::new()),
+            _ => (),
+        }
+    }
+
+    text
+}
+
+/// Blocks other than paragraphs and code blocks are converted to html.
+fn block_html<'a>(events: &mut impl Iterator<Item = Event<'a>>, start: Tag<'a>) -> String {
+    let mut block = vec![Event::Start(start)];
+    let mut depth = 1;
+    for event in events.by_ref() {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => (),
+        }
+        block.push(event);
+        if depth == 0 {
+            break;
+        }
+    }
+
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, block.into_iter());
+    html
+}
+
+/// "<", ">" to "\<", "\>" for "text" and "script" contents.
+fn text_escape(value: &str) -> String {
+    value.replace('<', "\\<").replace('>', "\\>")
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn attr_escape(value: &str) -> String {
+    html_escape(value).replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::storage::{self, StorageMem};
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn import_html_sanitized() {
+        let stor_root = "/mem_markdown_import";
+        storage::register(stor_root, Arc::new(StorageMem::new(stor_root)));
+        let mut page_json = page_json::page_json_plain();
+        page_json["data"]["page"]["title"] = "top".into();
+        let mut top = Page::from_json(stor_root, "/wc_top.html", &page_json).unwrap();
+        top.file_save_and_rev().unwrap();
+
+        let markdown =
+            "# t\n\n<img src=\"x.png\" onerror=\"alert(1)\">\n\na <b onclick=\"alert(1)\">b</b>\n";
+        let mut top = Page::new(stor_root, "/wc_top.html");
+        let page_path = page_markdown_import(&mut top, "t.html", markdown, None).unwrap();
+
+        let mut page = Page::new(stor_root, &page_path);
+        let value = page.json_value().unwrap().dump();
+        assert!(
+            value.contains("x.png") && value.contains(">b</b>"),
+            "{}",
+            value
+        );
+        assert!(
+            !value.contains("onerror") && !value.contains("onclick"),
+            "{}",
+            value
+        );
+        storage::unregister(stor_root);
+    }
+}
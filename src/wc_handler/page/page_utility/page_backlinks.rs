@@ -0,0 +1,314 @@
+use super::dom_utility;
+use super::index_file::IndexFile;
+use super::page_json;
+use super::Page;
+use tracing::info;
+
+// Link index is saved in stor_root as json.
+// It keeps links going out from each page,
+// links coming in to a page are searched in all of them.
+//
+// {
+//   "pages" : {
+//     "/source.html" : [
+//       {
+//         "subsection" : "#subsection1", // href of the subsection that has the link
+//         "title" : "subsection title",
+//         "href" : "child/child.html#abc", // href as it is written
+//         "target" : "/child/child.html", // path of the page linked
+//         "fragment" : "abc" // fragment of the href, "" if none
+//       }
+//     ]
+//   }
+// }
+//
+// Links are taken from href of subsections (except "#abc" of the subsection itself)
+// and href="xxx" in contents.
+// The index is updated page by page on saving the page, see index_file.rs.
+
+const INDEX_FILE: &str = "/wc_link_index.json";
+
+static INDEX: IndexFile = IndexFile::new(INDEX_FILE);
+
+/// Update links going out from the page in the link index.
+/// If the page was moved, its links are removed.
+pub fn index_update(page: &mut Page) {
+    let page_path = page.page_path().to_string();
+    let stor_root = page.stor_root().to_string();
+
+    let links = match super::page_url(page) {
+        Ok(page_url) => match page.json() {
+            Some(page_json) if page_json.moved_to().is_none() => page_links(&page_url, page_json),
+            _ => None,
+        },
+        Err(_) => None,
+    };
+
+    INDEX.update(&stor_root, |index| match links {
+        Some(links) if index["pages"][page_path.as_str()] != links => {
+            index["pages"][page_path.as_str()] = links;
+            true
+        }
+        Some(_) => false,
+        None => !index["pages"].remove(&page_path).is_null(),
+    });
+}
+
+/// Rebuild the link index from pages reachable from top_path.
+/// Return number of pages indexed.
+pub fn index_rebuild(stor_root: &str, top_path: &str) -> Result<usize, String> {
+    let mut pages = json::object! {};
+
+    for page_path in super::page_tree_paths(stor_root, top_path) {
+        let mut page = Page::new(stor_root, &page_path);
+        let Ok(page_url) = super::page_url(&mut page) else {
+            continue;
+        };
+        let links = match page.json() {
+            Some(page_json) if page_json.moved_to().is_none() => page_links(&page_url, page_json),
+            _ => None,
+        };
+        if let Some(links) = links {
+            pages[page_path.as_str()] = links;
+        }
+    }
+
+    let num = pages.len();
+    let index = json::object! {"pages": pages};
+
+    INDEX.replace(stor_root, index)?;
+
+    info!("link index rebuilt: {} pages", num);
+    Ok(num)
+}
+
+fn page_links(page_url: &url::Url, page_json: &page_json::PageJson) -> Option<json::JsonValue> {
+    let subsections = page_json.subsections()?;
+
+    let mut links = json::array![];
+    for (_, subsection) in subsections.iter() {
+        let subsection_href = subsection["href"].as_str().unwrap_or("");
+        let title = subsection["title"].as_str().unwrap_or("");
+
+        let mut hrefs = vec![];
+        // "#abc" is the name of the subsection itself, not a link.
+        if !subsection_href.is_empty() && !subsection_href.starts_with('#') {
+            hrefs.push(subsection_href.to_string());
+        }
+        for content in subsection["content"].members() {
+            let value = content["value"].as_str().unwrap_or("");
+            hrefs.append(&mut super::page_move::content_hrefs(value));
+            if content["type"] == "markdown" {
+                hrefs.append(&mut super::page_move::markdown_hrefs(value));
+            }
+        }
+
+        for href in hrefs {
+            let Some((target, fragment)) = href_target(page_url, &href) else {
+                continue;
+            };
+            let _ = links.push(json::object! {
+                "subsection": subsection_href,
+                "title": title,
+                "href": href.as_str(),
+                "target": target.as_str(),
+                "fragment": fragment.as_str(),
+            });
+        }
+    }
+
+    Some(links)
+}
+
+/// Resolve href on page_url as href_on does
+/// and return the path and the fragment of the page linked.
+/// Return None if the href is not to a page of this storage.
+fn href_target(page_url: &url::Url, href: &str) -> Option<(String, String)> {
+    let href_url = page_url.join(href).ok()?;
+    if href_url.host() != page_url.host() {
+        return None;
+    }
+    let fragment = href_url.fragment().unwrap_or("").to_string();
+    Some((href_url.path().to_string(), fragment))
+}
+
+/// A link coming in to a page.
+pub struct Backlink {
+    pub page_path: String,
+    pub subsection: String,
+    pub title: String,
+    pub href: String,
+    pub fragment: String,
+}
+
+/// Return links coming in to target_path.
+/// If fragment is Some, only links to the subsection are returned.
+/// eg: fragment: Some("abc") for "#abc"
+pub fn backlinks(stor_root: &str, target_path: &str, fragment: Option<&str>) -> Vec<Backlink> {
+    let index = INDEX.read(stor_root);
+
+    let mut backlinks = vec![];
+    for (page_path, links) in index["pages"].entries() {
+        for link in links.members() {
+            if link["target"] != target_path {
+                continue;
+            }
+            if fragment.is_some_and(|fragment| link["fragment"] != fragment) {
+                continue;
+            }
+            backlinks.push(Backlink {
+                page_path: page_path.to_string(),
+                subsection: link["subsection"].as_str().unwrap_or("").to_string(),
+                title: link["title"].as_str().unwrap_or("").to_string(),
+                href: link["href"].as_str().unwrap_or("").to_string(),
+                fragment: link["fragment"].as_str().unwrap_or("").to_string(),
+            });
+        }
+    }
+
+    backlinks.sort_by(|a, b| (&a.page_path, &a.subsection).cmp(&(&b.page_path, &b.subsection)));
+    backlinks
+}
+
+pub fn backlinks_json(backlinks: &[Backlink]) -> json::JsonValue {
+    let mut backlinks_json = json::array![];
+    for backlink in backlinks {
+        let _ = backlinks_json.push(json::object! {
+            "page": backlink.page_path.as_str(),
+            "subsection": backlink.subsection.as_str(),
+            "title": backlink.title.as_str(),
+            "href": backlink.href.as_str(),
+            "fragment": backlink.fragment.as_str(),
+        });
+    }
+    backlinks_json
+}
+
+/// List links coming in from other pages at the bottom of the page served.
+/// <div class="backlinks"><div>Links here</div><ul><li><a href="...">...</a></li></ul></div>
+/// It is put out of <div id="page_top_node"> so that it remains after javascript draws the page.
+/// The list is not saved in the page not to go stale as other pages link to or unlink from it,
+/// a list saved in pages before is replaced.
+/// Return None if source is to be sent as it is.
+pub fn static_insert(
+    stor_root: &str,
+    page_path: &str,
+    source: &[u8],
+    encoding: &'static encoding_rs::Encoding,
+) -> Option<Vec<u8>> {
+    const BACKLINKS_START: &[u8] = b"<div class=\"backlinks\">";
+    const BODY_END: &[u8] = b"</body>";
+    let find = |ptn: &[u8]| source.windows(ptn.len()).rposition(|v| v == ptn);
+
+    let body_end = find(BODY_END)?;
+    let saved = find(BACKLINKS_START).filter(|saved| *saved < body_end);
+    let backlinks_html = backlinks_html(stor_root, page_path);
+    if saved.is_none() && backlinks_html.is_empty() {
+        return None;
+    }
+
+    let mut inserted = source[..saved.unwrap_or(body_end)].to_vec();
+    inserted.extend_from_slice(&encoding.encode(&backlinks_html).0);
+    inserted.extend_from_slice(&source[body_end..]);
+    Some(inserted)
+}
+
+/// The html of links coming in to the page, "" if none.
+fn backlinks_html(stor_root: &str, page_path: &str) -> String {
+    let backlinks: Vec<Backlink> = backlinks(stor_root, page_path, None)
+        .into_iter()
+        .filter(|backlink| backlink.page_path != page_path)
+        .collect();
+    if backlinks.is_empty() {
+        return String::new();
+    }
+
+    let Ok(page_url) = url::Url::parse(&("http://127.0.0.1".to_string() + page_path)) else {
+        return String::new();
+    };
+
+    let attrs = &vec![("class", "backlinks")];
+    let backlinks_node = dom_utility::node_element("div", attrs);
+    let title_node = dom_utility::node_element("div", &vec![]);
+    title_node
+        .children
+        .borrow_mut()
+        .push(dom_utility::node_text("Links here"));
+    backlinks_node.children.borrow_mut().push(title_node);
+
+    let ul = dom_utility::node_element("ul", &vec![]);
+    for backlink in backlinks {
+        let mut source = backlink.page_path.clone();
+        if backlink.subsection.starts_with('#') {
+            source += &backlink.subsection;
+        }
+        let href = super::href_on(&page_url, &source)
+            .map(|(href, _)| href)
+            .unwrap_or(source);
+        let text = if backlink.title.is_empty() {
+            backlink.page_path.clone()
+        } else {
+            format!("{} ({})", backlink.title, backlink.page_path)
+        };
+
+        let li = dom_utility::node_element("li", &vec![]);
+        let a_node = dom_utility::node_element("a", &vec![("href", href.as_str())]);
+        a_node
+            .children
+            .borrow_mut()
+            .push(dom_utility::node_text(&text));
+        li.children.borrow_mut().push(a_node);
+        ul.children.borrow_mut().push(li);
+    }
+    backlinks_node.children.borrow_mut().push(ul);
+
+    // Only children of node_top are serialized.
+    let node_top = dom_utility::node_element("div", &vec![]);
+    node_top.children.borrow_mut().push(backlinks_node);
+    super::source_from_dom(node_top)
+        .ok()
+        .and_then(|v| String::from_utf8(v).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::storage::{self, Storage, StorageMem};
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn static_insert_current() {
+        let stor_root = "/mem_backlinks";
+        let mem = Arc::new(StorageMem::new(stor_root));
+        storage::register(stor_root, mem.clone());
+        let source = b"<html><body><div id=\"page_top_node\"></div><div class=\"backlinks\"><div>Links here</div><ul><li><a href=\"gone.html\">gone</a></li></ul></div></body></html>";
+
+        // A list saved before is removed when no page links now.
+        let inserted = static_insert(stor_root, "/a.html", source, encoding_rs::UTF_8).unwrap();
+        assert_eq!(
+            inserted,
+            b"<html><body><div id=\"page_top_node\"></div></body></html>"
+        );
+        assert_eq!(
+            static_insert(stor_root, "/a.html", &inserted, encoding_rs::UTF_8),
+            None
+        );
+
+        let index = json::object! {"pages": {"/b.html": [
+            {"subsection": "#s", "title": "\u{3042}", "href": "a.html", "target": "/a.html", "fragment": ""}
+        ]}};
+        mem.write_atomic(
+            std::path::Path::new(&super::super::file_path(stor_root, INDEX_FILE)),
+            index.dump().as_bytes(),
+        )
+        .unwrap();
+        let inserted = static_insert(stor_root, "/a.html", source, encoding_rs::SHIFT_JIS).unwrap();
+        let (html, _, _) = encoding_rs::SHIFT_JIS.decode(&inserted);
+        assert_eq!(
+            html,
+            "<html><body><div id=\"page_top_node\"></div><div class=\"backlinks\"><div>Links here</div><ul><li><a href=\"b.html#s\">\u{3042} (/b.html)</a></li></ul></div></body></html>"
+        );
+        storage::unregister(stor_root);
+    }
+}
@@ -1,31 +1,42 @@
 use super::dom_utility;
+use super::page_json::page_data::{Content, ContentType, Navi, PageData, Subsections};
 use markup5ever_rcdom::{Node, NodeData, RcDom}; // Handle, , NodeData, SerializableHandle
 use std::collections::HashSet;
 use std::rc::Rc;
 use tracing::{error, info}; // ,, warn
 
 pub fn page_dom_from_json(page_path: &str, page_json: &json::JsonValue) -> Result<RcDom, String> {
+    let page_data = PageData::from_json(page_json)?;
     let page_dom = super::to_dom(super::page_html_plain());
 
-    page_title_set(&page_dom, page_json);
+    page_title_set(&page_dom, &page_data);
 
     page_json_set(&page_dom, page_json)?;
 
-    if let Err(e) = page_html_static_set(&page_dom, page_path, page_json) {
+    if let Err(e) = page_html_static_set(&page_dom, page_path, &page_data, false) {
         error!("Failed to set html_static, {}", e,);
     };
 
     Ok(page_dom)
 }
 
-// Get page title from page_json and
+/// Create a standalone html page from page_json for a static site.
+/// It does not have the json span, javascript nor "static page" stamp.
+pub fn page_dom_export(page_path: &str, page_json: &json::JsonValue) -> Result<RcDom, String> {
+    let page_data = PageData::from_json(page_json)?;
+    let page_dom = super::to_dom(super::page_html_export());
+
+    page_title_set(&page_dom, &page_data);
+
+    page_html_static_set(&page_dom, page_path, &page_data, true)?;
+
+    Ok(page_dom)
+}
+
+// Get page title from page_data and
 // set it to page_dom.
-fn page_title_set(page_dom: &RcDom, page_json: &json::JsonValue) {
-    // Get page title or "".
-    let title_str = page_json["data"]["page"]["title"]
-        .as_str()
-        .or(Some(""))
-        .unwrap();
+fn page_title_set(page_dom: &RcDom, page_data: &PageData) {
+    let title_str = page_data.page.title.as_str();
 
     let title_ptn = dom_utility::node_element("title", &vec![]);
     if let Some(title_node) = dom_utility::child_match_first(&page_dom.document, &title_ptn, true) {
@@ -47,10 +58,12 @@ fn page_json_set(page_dom: &RcDom, page_json: &json::JsonValue) -> Result<(), St
 /// Create html elements from page_json data as static html contents
 /// so you can see the html page even if javascript does not draw
 /// html elements dynamically.
+/// export: true for a page of static site, without "static page" stamp and javascript.
 fn page_html_static_set(
     page_dom: &RcDom,
     page_path: &str,
-    page_json: &json::JsonValue,
+    page_data: &PageData,
+    export: bool,
 ) -> Result<(), String> {
     style_link_relative_set(page_dom, page_path);
 
@@ -62,28 +75,31 @@ fn page_html_static_set(
     let top_node = dom_utility::div_page_top_new();
 
     // navi
-    let navi_node = navi(page_json)?;
+    let navi_node = navi(&page_data.navi);
     top_node.children.borrow_mut().push(navi_node);
 
     // static stamp
-    let title_text = dom_utility::node_text("static page");
-    top_node.children.borrow_mut().push(title_text);
+    if !export {
+        let title_text = dom_utility::node_text("static page");
+        top_node.children.borrow_mut().push(title_text);
+    }
 
     // index
-    let index_node = index(page_json)?;
+    let index_node = index_ul(&page_data.subsection, &0)?;
     top_node.children.borrow_mut().push(index_node);
 
     // subsection
     let subsections_node = dom_utility::node_element("div", &vec![]);
 
-    let subsections_json = &page_json["data"]["subsection"]["data"];
-    if subsections_json.is_null() {
-        return Err("Failed to get subsection data!".to_string());
-    }
-
     // Check dublication of parent_index_key to avoid endlessloop.
     let mut parent_handled = HashSet::new();
-    subsections(subsections_json, &subsections_node, &mut parent_handled, &0)?;
+    subsections(
+        &page_data.subsection,
+        &subsections_node,
+        &mut parent_handled,
+        &0,
+        export,
+    )?;
     top_node.children.borrow_mut().push(subsections_node);
 
     // temporary space
@@ -139,39 +155,26 @@ fn style_link_relative_set(page_dom: &RcDom, page_path: &str) {
     }
 }
 
-fn navi(page_json: &json::JsonValue) -> Result<Rc<Node>, String> {
+fn navi(navis: &[Navi]) -> Rc<Node> {
     let ele_top = dom_utility::node_element("div", &vec![]);
 
-    let navis_json = match page_json["data"]["navi"] {
-        json::JsonValue::Array(ref vec) => vec,
-        _ => {
-            return Err("Failed to get navi data from page_json".to_string());
-        }
-    };
+    for (i, navi) in navis.iter().enumerate() {
+        let is_last = i + 1 == navis.len();
 
-    let mut navi_json_iter = navis_json.iter();
-    loop {
-        let navi_item_json = match navi_json_iter.next() {
-            Some(v) => v,
-            None => break,
-        };
-
-        let title = navi_item_json[0].as_str().or(Some("no title")).unwrap();
-
-        let navi_item = if 0 < navi_json_iter.size_hint().0 {
-            let href = navi_item_json[1].as_str().or(Some("")).unwrap();
-            let attrs = &vec![("href", href)];
+        // The last is this page, not a link.
+        let navi_item = if is_last {
+            dom_utility::node_text(&navi.title)
+        } else {
+            let attrs = &vec![("href", navi.href.as_str())];
             let navi_item = dom_utility::node_element("a", attrs);
-            let title_text = dom_utility::node_text(title);
+            let title_text = dom_utility::node_text(&navi.title);
             navi_item.children.borrow_mut().push(title_text);
             navi_item
-        } else {
-            dom_utility::node_text(title)
         };
 
         ele_top.children.borrow_mut().push(navi_item);
 
-        if navi_json_iter.size_hint().0 < 1 {
+        if is_last {
             continue;
         }
 
@@ -179,162 +182,103 @@ fn navi(page_json: &json::JsonValue) -> Result<Rc<Node>, String> {
         ele_top.children.borrow_mut().push(delimiter_text);
     }
 
-    Ok(ele_top)
-}
-
-fn index(page_json: &json::JsonValue) -> Result<Rc<Node>, String> {
-    let subsections_json = &page_json["data"]["subsection"]["data"];
-    if subsections_json.is_null() {
-        return Err("Failed to get subsection data!".to_string());
-    }
-
-    // ul / li
-    let index_ul = index_ul(subsections_json, &0)?;
-    Ok(index_ul)
+    ele_top
 }
 
-// index_key: usize(number)
-// Now, index type is string "0":page_json["data"]["subsection"]["data"]["0"]
-// but children index type is number :page_json["data"]["subsection"]["data"]["0"]["child"] = [1, 3, 4];
-// It is better all those to be numbers in future.
-//
-fn index_ul(subsections_json: &json::JsonValue, index_key: &usize) -> Result<Rc<Node>, String> {
-    let children_i = subsection_children_indexes(subsections_json, index_key)?;
+// ul / li of the index
+fn index_ul(subsections: &Subsections, id: &usize) -> Result<Rc<Node>, String> {
+    let parent = subsections
+        .get(id)
+        .ok_or(format!("Failed to get parent subsection: {}", id))?;
 
     let ul = dom_utility::node_element("ul", &vec![]);
 
-    for child_i in children_i.iter() {
-        let li = index_li(subsections_json, child_i)?;
+    for child_id in parent.child.iter() {
+        let li = index_li(subsections, child_id)?;
         ul.children.borrow_mut().push(li);
     }
     Ok(ul)
 }
 
-fn subsection_children_indexes(
-    subsections_json: &json::JsonValue,
-    index_key: &usize,
-) -> Result<Vec<usize>, String> {
-    let key = index_key.to_string();
-    let parent_json = &subsections_json[&key];
-    if parent_json.is_null() {
-        return Err(format!("Failed to get parent subsection: {}", index_key));
-    }
-
-    let mut children_i2 = vec![];
-
-    let children_i = match &parent_json["child"] {
-        json::JsonValue::Array(ref vec) => vec,
-        // case parent_json["child"] is not defined
-        _ => {
-            return Ok(children_i2);
-        }
-    };
-
-    for child_i in children_i.iter() {
-        let i = if child_i.is_number() {
-            child_i.as_usize().unwrap()
-        } else if child_i.is_string() {
-            child_i.as_usize().unwrap()
-        } else {
-            return Err(format!("Failed to get number of: {}", child_i));
-        };
-
-        children_i2.push(i);
-    }
-
-    Ok(children_i2)
-}
-
-fn index_li(subsections_json: &json::JsonValue, child_i: &usize) -> Result<Rc<Node>, String> {
-    let subsection_json = &subsections_json[child_i.to_string().as_str()];
+fn index_li(subsections: &Subsections, id: &usize) -> Result<Rc<Node>, String> {
+    let subsection = subsections
+        .get(id)
+        .ok_or(format!("Failed to get subsection of : {}", id))?;
 
     let li = dom_utility::node_element("li", &vec![]);
-    let href = match subsection_json["href"].as_str() {
-        Some(v) => v,
-        None => return Err(format!("Failed to get subsection href of : {}", child_i)),
-    };
-    let attrs = &vec![("href", href)];
+    let attrs = &vec![("href", subsection.href.as_str())];
     let a_node = dom_utility::node_element("a", attrs);
 
-    let title = match subsection_json["title"].as_str() {
-        Some(v) => v,
-        None => return Err(format!("Failed to get subsection title of : {}", child_i)),
-    };
-    let title_node = dom_utility::node_text(title);
+    let title_node = dom_utility::node_text(&subsection.title);
     a_node.children.borrow_mut().push(title_node);
 
     li.children.borrow_mut().push(a_node);
 
-    let children_ul = index_ul(subsections_json, child_i)?;
+    let children_ul = index_ul(subsections, id)?;
     li.children.borrow_mut().push(children_ul);
 
     Ok(li)
 }
 
 fn subsections(
-    subsections_json: &json::JsonValue,
+    subsections_data: &Subsections,
     subsections_node: &Rc<Node>,
     parent_handled: &mut HashSet<usize>,
-    parent_index_key: &usize,
+    parent_id: &usize,
+    export: bool,
 ) -> Result<(), String> {
-    if parent_handled.contains(parent_index_key) {
-        info!("Key duplicated {}", parent_index_key);
+    if parent_handled.contains(parent_id) {
+        info!("Key duplicated {}", parent_id);
         return Ok(());
     }
-    parent_handled.insert(*parent_index_key);
-
-    subsections_node.children.borrow_mut().push(navi_back());
-    let subsection_children_i = subsection_children_indexes(subsections_json, &parent_index_key)?;
-    for child_i in subsection_children_i.iter() {
-        let _ = subsection(subsections_json, &subsections_node, parent_handled, child_i);
+    parent_handled.insert(*parent_id);
+
+    subsections_node
+        .children
+        .borrow_mut()
+        .push(navi_back(export));
+    let parent = subsections_data
+        .get(parent_id)
+        .ok_or(format!("Failed to get parent subsection: {}", parent_id))?;
+    for child_id in parent.child.iter() {
+        let _ = subsection(
+            subsections_data,
+            subsections_node,
+            parent_handled,
+            child_id,
+            export,
+        );
     }
 
     Ok(())
 }
 
-/// If href starts with #, returns Ok(None). It is not error but not content of the page.
+/// If href does not start with #, returns Err. It is not content of the page.
 fn subsection(
-    subsections_json: &json::JsonValue,
+    subsections_data: &Subsections,
     subsections_node: &Rc<Node>,
     parent_handled: &mut HashSet<usize>,
     id: &usize,
+    export: bool,
 ) -> Result<(), String> {
-    let subsection_json = &subsections_json[id.to_string().as_str()];
+    let subsection_data = subsections_data
+        .get(id)
+        .ok_or(format!("Failed to get subsection of : {}", id))?;
 
-    let subsection_node = match subsection_node_new(subsection_json) {
-        Ok(v) => v,
-        Err(e) => {
-            // error!("{}", e);
-            return Err(e);
-        }
-    };
+    let subsection_node = subsection_node_new(&subsection_data.href)?;
 
     // title
     let attrs = &vec![("class", "subsectionTitle")];
     let title_node = dom_utility::node_element("div", attrs);
-    let title_str = subsection_json["title"]
-        .as_str()
-        .ok_or(format!("Failed to get title of index: {}", id))?;
-    let title_text = dom_utility::node_text(title_str);
+    let title_text = dom_utility::node_text(&subsection_data.title);
     title_node.children.borrow_mut().push(title_text);
     subsection_node.children.borrow_mut().push(title_node);
 
     // contents
     let contents_node = dom_utility::node_element("div", &vec![]);
 
-    // "content" : [ {"type" : "text", "value" : "sample"} ],
-    let contents_json = match subsection_json["content"] {
-        json::JsonValue::Array(ref v) => v,
-        _ => {
-            return Err(format!(
-                "Failed to get contents from subsection data of id: {}",
-                id
-            ));
-        }
-    };
-
-    for content_json in contents_json {
-        let content_node = content(&content_json)?;
+    for content_data in subsection_data.content.iter() {
+        let content_node = content(content_data)?;
         contents_node.children.borrow_mut().push(content_node);
     }
 
@@ -343,18 +287,20 @@ fn subsection(
     subsections_node.children.borrow_mut().push(subsection_node);
 
     // children
-    subsections(subsections_json, &subsections_node, parent_handled, &id)?;
+    subsections(
+        subsections_data,
+        subsections_node,
+        parent_handled,
+        id,
+        export,
+    )?;
 
     return Ok(());
 }
 
 /// Create subsection_node <div id="href_value">
 /// If href is not appropriate, return <div class="id_undefined">
-fn subsection_node_new(subsection_json: &json::JsonValue) -> Result<Rc<Node>, String> {
-    let Some(href) = subsection_json["href"].as_str() else {
-        return Err("Failed to get href".to_string());
-    };
-
+fn subsection_node_new(href: &str) -> Result<Rc<Node>, String> {
     if !href.starts_with("#") {
         return Err("href not start with #".to_string());
     }
@@ -372,37 +318,93 @@ fn subsection_node_new(subsection_json: &json::JsonValue) -> Result<Rc<Node>, St
     })
 }
 
-fn content(content_json: &json::JsonValue) -> Result<Rc<Node>, String> {
-    let content_value = content_json["value"]
-        .as_str()
-        .ok_or("Failed to get content value".to_string())?;
-
-    // &content_json["type"]: "html", "script", "text"
-    if &content_json["type"] == "text" {
-        return content_text(&content_value);
+fn content(content_data: &Content) -> Result<Rc<Node>, String> {
+    let content_value = content_data.value.as_str();
+
+    match &content_data.content_type {
+        ContentType::Text => content_text(content_value),
+        // "lang" is optional, eg: "rust"
+        ContentType::Script => match content_data.attr("lang").filter(|v| !v.trim().is_empty()) {
+            Some(lang) => content_script_highlight(content_value, lang),
+            None => content_script(content_value),
+        },
+        ContentType::Html => content_html(content_value),
+        ContentType::Markdown => content_markdown(content_value),
+        ContentType::Table => content_table(content_data),
+        ContentType::Other(_) => Err("type does not match".to_string()),
     }
+}
 
-    if &content_json["type"] == "script" {
-        return content_script(&content_value);
+fn content_html(content_value: &str) -> Result<Rc<Node>, String> {
+    let content_node = dom_utility::div_subsection_content_new();
+    let value_doms = to_dom_parts_sanitized(content_value);
+    // to_dom_parts always returns in vec even one node.
+    for value_dom in value_doms.into_iter() {
+        content_node.children.borrow_mut().push(value_dom);
     }
+    Ok(content_node)
+}
+
+/// Render markdown (CommonMark with tables, strikethrough, footnotes and task lists)
+/// into html. Html written in the markdown is left as it is as well as "html" content.
+fn content_markdown(content_value: &str) -> Result<Rc<Node>, String> {
+    let content_node = dom_utility::div_subsection_content_new();
+
+    // markdown top node
+    let attrs = &vec![("class", "markdown")];
+    let markdown_node = dom_utility::node_element("div", attrs);
 
-    if content_json["type"] == "html" {
-        return content_html(&content_value);
+    let value_doms = to_dom_parts_sanitized(&markdown_html(content_value));
+    for value_dom in value_doms.into_iter() {
+        markdown_node.children.borrow_mut().push(value_dom);
     }
 
-    Err("type does not match".to_string())
+    content_node.children.borrow_mut().push(markdown_node);
+
+    Ok(content_node)
 }
 
-fn content_html(content_value: &str) -> Result<Rc<Node>, String> {
+/// Render CSV or TSV into <table>, see content_table.rs for options.
+fn content_table(content_data: &Content) -> Result<Rc<Node>, String> {
     let content_node = dom_utility::div_subsection_content_new();
-    let value_doms = super::to_dom_parts(content_value);
-    // to_dom_parts always returns in vec even one node.
+
+    // table top node
+    let attrs = &vec![("class", "table")];
+    let table_node = dom_utility::node_element("div", attrs);
+
+    let table = super::content_table::Table::from_content(content_data);
+    let value_doms = super::to_dom_parts(&table.to_html());
     for value_dom in value_doms.into_iter() {
-        content_node.children.borrow_mut().push(value_dom);
+        table_node.children.borrow_mut().push(value_dom);
     }
+
+    content_node.children.borrow_mut().push(table_node);
+
     Ok(content_node)
 }
 
+pub fn markdown_html(content_value: &str) -> String {
+    let parser = pulldown_cmark::Parser::new_ext(content_value, super::markdown_options());
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, parser);
+    html
+}
+
+/// Html written by users may have scripts, those are removed by the allowlist.
+fn to_dom_parts_sanitized(html: &str) -> Vec<Rc<Node>> {
+    let mut removed = vec![];
+    super::html_sanitize::nodes_sanitize(super::dom_utility::to_dom_fragment(html), &mut removed)
+}
+
+/// Render a content as the static page does and return it in html.
+/// eg: {"type": "markdown", "value": "# title"}
+pub fn content_render(content_json: &json::JsonValue) -> Result<String, String> {
+    let content_data = Content::from_json(content_json).map_err(|e| format!("content{}", e))?;
+    let content_node = content(&content_data)?;
+    let html = super::source_from_dom(content_node).map_err(|e| e.to_string())?;
+    String::from_utf8(html).map_err(|e| e.to_string())
+}
+
 fn content_script(content_value: &str) -> Result<Rc<Node>, String> {
     let content_node = dom_utility::div_subsection_content_new();
 
@@ -413,7 +415,34 @@ fn content_script(content_value: &str) -> Result<Rc<Node>, String> {
     // to_dom_parts always returns in vec even one node.
     let content_value = text_in_html(content_value);
 
-    let value_doms = super::to_dom_parts(content_value.as_str());
+    let value_doms = to_dom_parts_sanitized(content_value.as_str());
+    for value_dom in value_doms.into_iter() {
+        script_node.children.borrow_mut().push(value_dom);
+    }
+
+    content_node.children.borrow_mut().push(script_node);
+
+    Ok(content_node)
+}
+
+/// Script with its language is highlighted in <pre>.
+/// The value is handled as code, "\<", "\>" are "<", ">" but html elements are not.
+/// <div class="script"><pre class="highlight lang-rust"><code>...</code></pre></div>
+fn content_script_highlight(content_value: &str, lang: &str) -> Result<Rc<Node>, String> {
+    let content_node = dom_utility::div_subsection_content_new();
+
+    // script top node
+    let attrs = &vec![("class", "script")];
+    let script_node = dom_utility::node_element("div", attrs);
+
+    let code = content_value.replace("\\<", "<").replace("\\>", ">");
+    let html = format!(
+        "<pre class=\"highlight lang-{}\"><code>{}</code></pre>",
+        super::highlight::html_escape(lang.trim()),
+        super::highlight::highlight(lang, &code)
+    );
+
+    let value_doms = super::to_dom_parts(&html);
     for value_dom in value_doms.into_iter() {
         script_node.children.borrow_mut().push(value_dom);
     }
@@ -431,7 +460,7 @@ fn content_text(content_value: &str) -> Result<Rc<Node>, String> {
 
     let content_value = text_in_html(content_value);
 
-    let value_doms = super::to_dom_parts(content_value.as_str());
+    let value_doms = to_dom_parts_sanitized(content_value.as_str());
     // to_dom_parts always returns in vec even one node.
     for value_dom in value_doms.into_iter() {
         content_node.children.borrow_mut().push(value_dom);
@@ -459,7 +488,7 @@ fn text_in_html(value: &str) -> String {
     value
 }
 
-fn text_angle_entity(content_value: &str) -> String {
+pub fn text_angle_entity(content_value: &str) -> String {
     let mut content_value2 = String::new();
 
     let mut i_pos = 0;
@@ -543,19 +572,22 @@ fn text_whitespace_pre(hay: &str) -> String {
 //     //
 // }
 
-fn navi_back() -> Rc<Node> {
+/// export: true not to have "back" that uses javascript.
+fn navi_back(export: bool) -> Rc<Node> {
     let navi_back_node = dom_utility::node_element("div", &vec![]);
 
-    // back
-    let attrs = &vec![("href", "javascript:history.back()")];
-    let back_node = dom_utility::node_element("a", &attrs);
-    let back_text = dom_utility::node_text("back");
-    back_node.children.borrow_mut().push(back_text);
-    navi_back_node.children.borrow_mut().push(back_node);
-
-    // space
-    let space_text = dom_utility::node_text(" ");
-    navi_back_node.children.borrow_mut().push(space_text);
+    if !export {
+        // back
+        let attrs = &vec![("href", "javascript:history.back()")];
+        let back_node = dom_utility::node_element("a", attrs);
+        let back_text = dom_utility::node_text("back");
+        back_node.children.borrow_mut().push(back_text);
+        navi_back_node.children.borrow_mut().push(back_node);
+
+        // space
+        let space_text = dom_utility::node_text(" ");
+        navi_back_node.children.borrow_mut().push(space_text);
+    }
 
     // top
     let attrs = &vec![("href", "#")];
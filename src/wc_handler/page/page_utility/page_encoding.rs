@@ -0,0 +1,64 @@
+use encoding_rs::{Encoding, UTF_8};
+use tracing::info;
+
+// Character encoding of page sources.
+// Pages are saved in UTF-8, older pages may be in Shift_JIS, EUC-JP or others.
+// The encoding is detected in the order of
+// BOM, <meta charset> (or http-equiv content type) and heuristics on the bytes.
+
+/// Decode the page source to a String and return the encoding detected.
+pub fn decode(source: &[u8]) -> (String, &'static Encoding) {
+    let encoding = detect(source);
+    // BOM is removed here as well.
+    let (text, _, _) = encoding.decode(source);
+    (text.into_owned(), encoding)
+}
+
+/// Encoding of the page source.
+pub fn detect(source: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _bom_len)) = Encoding::for_bom(source) {
+        return encoding;
+    }
+
+    let is_utf8 = std::str::from_utf8(source).is_ok();
+
+    if let Some(encoding) = meta_charset(source) {
+        // The page was converted to UTF-8 leaving the meta as it was.
+        if encoding != UTF_8 && is_utf8 && encoding.decode_without_bom_handling(source).1 {
+            info!(
+                "meta charset {} ignored, the source is UTF-8",
+                encoding.name()
+            );
+            return UTF_8;
+        }
+        return encoding;
+    }
+
+    if is_utf8 {
+        return UTF_8;
+    }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(source, true);
+    detector.guess(None, false)
+}
+
+/// Encoding declared in the head of html.
+/// <meta charset="Shift_JIS">
+/// <meta http-equiv="Content-Type" content="text/html; charset=EUC-JP">
+fn meta_charset(source: &[u8]) -> Option<&'static Encoding> {
+    // The declaration should be in the first 1024 bytes, but be lenient for pages
+    // with long scripts in head.
+    let head = &source[..source.len().min(4096)];
+    let reg = regex::bytes::Regex::new(r#"(?i)<meta\s[^>]*charset\s*=\s*["']?\s*([a-z0-9_:.\-]+)"#)
+        .unwrap();
+    let caps = reg.captures(head)?;
+    let encoding = Encoding::for_label(&caps[1])?;
+    // Only ASCII compatible encodings could declare themselves in ASCII.
+    // ie: "UTF-16" in meta means UTF-8 actually.
+    if encoding.is_ascii_compatible() {
+        Some(encoding)
+    } else {
+        Some(encoding.output_encoding())
+    }
+}
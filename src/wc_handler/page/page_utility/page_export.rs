@@ -0,0 +1,254 @@
+use super::Page;
+use markup5ever_rcdom::{Handle, NodeData};
+use std::collections::HashSet;
+use std::path::Path;
+use tracing::{error, info};
+
+// Export pages of a tree as a static site into out_dir.
+// out_dir is in export_root, see io_roots.rs.
+//
+// out_dir/wc.css
+// out_dir/wc_top.html
+// out_dir/child/child.html
+// out_dir/wc_export.json
+//
+// wc_export.json keeps rev of each page exported,
+// pages of the same rev are not exported again unless full is true.
+// {
+//   "top" : "/wc_top.html",
+//   "pages" : { "/wc_top.html" : 3, "/child/child.html" : 1 }
+// }
+
+const MANIFEST_FILE: &str = "/wc_export.json";
+const CSS_FILE: &str = "/wc.css";
+
+pub struct Export {
+    pub exported: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub excluded: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl Export {
+    pub fn to_json(&self) -> json::JsonValue {
+        json::object! {
+            "res": "exported",
+            "exported": self.exported.clone(),
+            "unchanged": self.unchanged.len(),
+            "excluded": self.excluded.clone(),
+            "removed": self.removed.clone(),
+        }
+    }
+}
+
+/// Export top_path and pages under it to out_dir in export_root as standalone html.
+/// Private pages and pages under them are excluded, moved pages are excluded.
+/// full: export all pages even if they were not changed from the last export.
+pub fn page_export(
+    stor_root: &str,
+    top_path: &str,
+    out_dir: &str,
+    full: bool,
+) -> Result<Export, String> {
+    let out_dir = &out_dir_resolve(stor_root, out_dir)?;
+
+    let manifest = manifest_read(out_dir);
+    let manifest = if manifest["top"] == top_path && !full {
+        manifest
+    } else {
+        json::object! {"top": top_path, "pages": {}}
+    };
+    let mut manifest_new = json::object! {"top": top_path, "pages": {}};
+
+    let mut export = Export {
+        exported: vec![],
+        unchanged: vec![],
+        excluded: vec![],
+        removed: vec![],
+    };
+
+    let mut handled = HashSet::new();
+    let mut page_path_s = vec![top_path.to_string()];
+
+    while let Some(page_path) = page_path_s.pop() {
+        if !handled.insert(page_path.clone()) {
+            continue;
+        }
+
+        let mut page = Page::new(stor_root, &page_path);
+        let Some(page_json) = page.json() else {
+            continue;
+        };
+
+        // Pages under a private page are not reached.
+        if page_json.is_private() {
+            export.excluded.push(page_path);
+            continue;
+        }
+
+        // Contents were moved, the destination is exported
+        // if it is in the tree.
+        if page_json.moved_to().is_some() {
+            export.excluded.push(page_path);
+            continue;
+        }
+
+        let rev = page_json.rev();
+
+        let mut child_path_s: Vec<String> = super::page_children_url(&mut page)
+            .iter()
+            .map(|url| url.path().to_string())
+            .collect();
+        // Keep the order of subsections popping from the last.
+        child_path_s.reverse();
+        page_path_s.append(&mut child_path_s);
+
+        let out_path = out_dir.to_string() + &page_path;
+        if rev.is_some()
+            && manifest["pages"][page_path.as_str()].as_usize() == rev
+            && Path::new(&out_path).is_file()
+        {
+            manifest_new["pages"][page_path.as_str()] = rev.into();
+            export.unchanged.push(page_path);
+            continue;
+        }
+
+        page_file_export(&mut page, &out_path)?;
+        manifest_new["pages"][page_path.as_str()] = rev.into();
+        export.exported.push(page_path);
+    }
+
+    // Remove pages exported before but not any more,
+    // eg: pages marked private after the last export.
+    for (page_path, _) in manifest["pages"].entries() {
+        if manifest_new["pages"].has_key(page_path) {
+            continue;
+        }
+        // Keys of the manifest on the disk should be in out_dir.
+        let out_path = match super::io_roots::path_join(Path::new(out_dir), page_path) {
+            Ok(v) if page_path.starts_with('/') => v,
+            _ => {
+                error!("Not removed, out of {}: {}", out_dir, page_path);
+                continue;
+            }
+        };
+        if std::fs::remove_file(&out_path).is_ok() {
+            info!("removed: {:?}", out_path);
+            export.removed.push(page_path.to_string());
+        }
+    }
+
+    css_copy(stor_root, out_dir)?;
+    manifest_write(out_dir, &manifest_new)?;
+
+    info!(
+        "export {} to {}: exported {}, unchanged {}, excluded {}, removed {}",
+        top_path,
+        out_dir,
+        export.exported.len(),
+        export.unchanged.len(),
+        export.excluded.len(),
+        export.removed.len()
+    );
+
+    Ok(export)
+}
+
+/// out_dir in export_root, it should not be in stor_root not to overwrite pages.
+/// Return the path of out_dir.
+pub fn out_dir_resolve(stor_root: &str, out_dir: &str) -> Result<String, String> {
+    let out_dir_path = super::io_roots::export_dir(out_dir)?;
+    let out_dir = out_dir_path
+        .to_str()
+        .ok_or(format!("Failed to get str from: {:?}", out_dir_path))?;
+
+    let stor_root = std::fs::canonicalize(stor_root)
+        .map_err(|e| format!("Failed to get path: {}, {}", stor_root, e))?;
+    let out_dir_path = std::path::absolute(out_dir)
+        .map_err(|e| format!("Failed to get path: {}, {}", out_dir, e))?;
+    // out_dir may not exist yet, canonicalize it if exists to resolve symlinks.
+    let out_dir_path = std::fs::canonicalize(&out_dir_path).unwrap_or(out_dir_path);
+    if out_dir_path.starts_with(&stor_root) || stor_root.starts_with(&out_dir_path) {
+        return Err(format!("out_dir overlaps stor_root: {}", out_dir));
+    }
+
+    Ok(out_dir.to_string())
+}
+
+fn page_file_export(page: &mut Page, out_path: &str) -> Result<(), String> {
+    let page_path = page.page_path().to_string();
+    let page_json = page
+        .json_value()
+        .ok_or(format!("Failed to get json_value on {}", &page_path))?;
+
+    let page_dom = super::page_dom_from_json::page_dom_export(&page_path, page_json)?;
+    if let Ok(page_url) = super::page_url(page) {
+        href_relative_set(&page_dom.document, &page_url);
+    }
+    let source = super::source_from_dom(page_dom.document.clone()).map_err(|e| format!("{}", e))?;
+
+    super::dir_build(Path::new(out_path), true)?;
+    super::fs_write(out_path, &source)?;
+    info!("exported: {}", out_path);
+
+    Ok(())
+}
+
+/// Links from the root of the storage like "/child/child.html"
+/// do not work on the static site placed anywhere,
+/// convert those to relative to page_url.
+fn href_relative_set(node: &Handle, page_url: &url::Url) {
+    if let NodeData::Element { name, attrs, .. } = &node.data {
+        if *name.local == *"a" {
+            for att in attrs.borrow_mut().iter_mut() {
+                if *att.name.local != *"href" {
+                    continue;
+                }
+                if !att.value.starts_with('/') || att.value.starts_with("//") {
+                    continue;
+                }
+                let relative = page_url
+                    .join(&att.value)
+                    .ok()
+                    .and_then(|href_url| page_url.make_relative(&href_url));
+                if let Some(relative) = relative {
+                    att.value = relative.into();
+                }
+            }
+        }
+    }
+
+    for child in node.children.borrow().iter() {
+        href_relative_set(child, page_url);
+    }
+}
+
+fn css_copy(stor_root: &str, out_dir: &str) -> Result<(), String> {
+    let from = super::file_path(stor_root, CSS_FILE);
+    let to = out_dir.to_string() + CSS_FILE;
+    match std::fs::copy(&from, &to) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            error!("Failed to copy {} to {}, {}", from, to, e);
+            Err(format!("Failed to copy {}", CSS_FILE))
+        }
+    }
+}
+
+fn manifest_read(out_dir: &str) -> json::JsonValue {
+    let manifest = std::fs::read_to_string(out_dir.to_string() + MANIFEST_FILE)
+        .ok()
+        .and_then(|v| json::parse(&v).ok());
+
+    match manifest {
+        Some(manifest) if manifest["pages"].is_object() => manifest,
+        _ => json::object! {"top": null, "pages": {}},
+    }
+}
+
+fn manifest_write(out_dir: &str, manifest: &json::JsonValue) -> Result<String, String> {
+    super::fs_write(
+        &(out_dir.to_string() + MANIFEST_FILE),
+        &manifest.dump().into_bytes(),
+    )
+}
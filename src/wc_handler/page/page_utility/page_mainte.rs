@@ -1,23 +1,81 @@
 use super::dom_utility;
-use super::fs_write;
 use super::json_from_dom;
 pub use super::page_child_new;
 pub use super::page_children_url;
 pub use super::page_json;
+use super::rev_store;
+use super::storage;
 use super::Page;
 use std::cell::RefCell;
 use std::rc::Rc;
-// use tracing::{error, info};
+use tracing::error;
 
+pub mod backup_policy;
+pub mod mainte_job;
 pub mod page_backup_clean;
 pub mod page_form_update;
+pub mod page_fsck;
+pub mod page_migrate;
+pub mod page_sanitize;
 
+pub struct MainteLog {
+    pub migrate: page_migrate::MigrateLog,
+    pub sanitize: page_sanitize::SanitizeLog,
+}
+
+impl MainteLog {
+    /// The report of migrations with "sanitized" and "sanitize_failed" of page_sanitize.
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut res = self.migrate.to_json();
+        let sanitize = self.sanitize.to_json();
+        res["sanitized"] = sanitize["sanitized"].clone();
+        res["sanitize_failed"] = sanitize["failed"].clone();
+        res
+    }
+}
+
+/// Return the report of migrations and sanitizing.
 pub fn page_mainte(
     page: &mut super::Page,
     recursive: bool,
     log: Option<Rc<RefCell<page_form_update::Log>>>,
-) {
-    page_form_update::page_form_update(page, recursive, log);
+) -> MainteLog {
+    // log of a job tells the stage and if the job is cancelled.
+    let stage_set = |stage| {
+        if let Some(log) = log.as_ref() {
+            log.borrow().stage_set(stage);
+        }
+    };
+    let cancelled = || log.as_ref().is_some_and(|log| log.borrow().cancelled());
+
+    stage_set("form_update");
+    page_form_update::page_form_update(page, recursive, log.clone());
+
+    stage_set("migrate");
+    let migrate_log = page_migrate::page_migrate(page, recursive, &cancelled);
+
+    // After migrations, contents are sanitized in the current version.
+    stage_set("sanitize");
+    let sanitize_log = page_sanitize::page_sanitize(page, recursive, &cancelled);
+
+    if !cancelled() {
+        stage_set("backup_clean");
+        let dry_run = false;
+        let mut backup_log = page_backup_clean::BackupCleanLog::new(dry_run);
+        page_backup_clean::page_backup_clean(page, recursive, &mut backup_log);
+
+        // Only if garbage_days is configured.
+        stage_set("garbage_purge");
+        let force = false;
+        if let Err(e) =
+            page_backup_clean::garbage_purge(page.stor_root(), None, force, &mut backup_log)
+        {
+            error!("{}", e);
+        }
+    }
 
-    page_backup_clean::page_backup_clean(page, recursive);
+    MainteLog {
+        migrate: migrate_log,
+        sanitize: sanitize_log,
+    }
 }
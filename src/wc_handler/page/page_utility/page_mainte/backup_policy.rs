@@ -0,0 +1,152 @@
+use tracing::{error, info};
+
+// Retention policy of backups, page.html.N and page_revN.html files.
+//
+// The policy is read from stor_root/wc_backup.json, keys not given are the defaults.
+// A subtree policy is for pages of which path starts with the key,
+// the longest key matched is used, keys not given in it are of "default".
+// {
+//   "default": {"keep_last": 10, "keep_days": 30, "daily_days": 0, "weekly_days": 0},
+//   "subtrees": {"/notes/": {"keep_last": 50, "weekly_days": 365}},
+//   "garbage_days": 30,
+//   "rev_store": "zlib"
+// }
+// keep_last: the last revs kept.
+// keep_days: revs modified in the days are kept.
+// daily_days, weekly_days: the last rev of each day or week in the days is kept.
+// garbage_days: files moved to the garbage are deleted after the days on purge,
+//   GARBAGE_DAYS_MIN at least, the garbage is not purged on mainte if not given.
+// rev_store: how revs are written, see rev_store.rs.
+//
+// The rev right before an original backup of page_form_update is always kept.
+
+const CONFIG_FILE: &str = "/wc_backup.json";
+
+/// wc_backup.json, Null if not configured.
+pub fn config_read(stor_root: &str) -> json::JsonValue {
+    let path = super::super::file_path(stor_root, CONFIG_FILE);
+    match super::super::storage::read_to_string(std::path::Path::new(&path)) {
+        Ok(v) => match json::parse(&v) {
+            Ok(v) => {
+                info!("backup config: {}", path);
+                v
+            }
+            Err(e) => {
+                error!("Failed to parse {}, the default is used: {}", path, e);
+                json::JsonValue::Null
+            }
+        },
+        // No config, the default is used.
+        Err(_) => json::JsonValue::Null,
+    }
+}
+
+/// Days of the grace period if garbage_days is not given.
+pub const GARBAGE_DAYS: usize = 30;
+
+/// The shortest grace period, not to purge backups just moved by mistake.
+pub const GARBAGE_DAYS_MIN: usize = 7;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Policy {
+    pub keep_last: usize,
+    pub keep_days: usize,
+    pub daily_days: usize,
+    pub weekly_days: usize,
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Policy {
+            keep_last: 10,
+            keep_days: 30,
+            daily_days: 0,
+            weekly_days: 0,
+        }
+    }
+}
+
+impl Policy {
+    /// Policy of base with the keys given in value.
+    fn from_json(value: &json::JsonValue, base: &Policy) -> Policy {
+        let field = |key: &str, base: usize| value[key].as_usize().unwrap_or(base);
+        Policy {
+            keep_last: field("keep_last", base.keep_last),
+            keep_days: field("keep_days", base.keep_days),
+            daily_days: field("daily_days", base.daily_days),
+            weekly_days: field("weekly_days", base.weekly_days),
+        }
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        json::object! {
+            "keep_last": self.keep_last,
+            "keep_days": self.keep_days,
+            "daily_days": self.daily_days,
+            "weekly_days": self.weekly_days,
+        }
+    }
+
+    /// Revs to keep out of revs with days since modified.
+    /// revs: (rev, days since the epoch when modified, days since modified)
+    /// rev_crt: the current rev of the page.
+    pub fn revs_keep(&self, revs: &[(usize, u64, usize)], rev_crt: usize) -> Vec<usize> {
+        let mut keep = vec![];
+        for (rev, day, age) in revs.iter() {
+            let last = rev_crt.saturating_sub(*rev) < self.keep_last;
+            let recent = *age < self.keep_days;
+            let daily = *age < self.daily_days && last_of(revs, *rev, |d| d == *day);
+            let weekly = *age < self.weekly_days && last_of(revs, *rev, |d| d / 7 == day / 7);
+            if last || recent || daily || weekly {
+                keep.push(*rev);
+            }
+        }
+        keep
+    }
+}
+
+/// If rev is the largest of revs on the day matched.
+fn last_of(revs: &[(usize, u64, usize)], rev: usize, day_match: impl Fn(u64) -> bool) -> bool {
+    revs.iter()
+        .filter(|(_, day, _)| day_match(*day))
+        .all(|(rev_other, _, _)| *rev_other <= rev)
+}
+
+pub struct Policies {
+    default: Policy,
+    // (path prefix, policy)
+    subtrees: Vec<(String, Policy)>,
+    pub garbage_days: Option<usize>,
+}
+
+impl Policies {
+    /// Read the policies from stor_root, the defaults if not configured.
+    pub fn load(stor_root: &str) -> Policies {
+        Policies::from_json(&config_read(stor_root))
+    }
+
+    fn from_json(config: &json::JsonValue) -> Policies {
+        let default = Policy::from_json(&config["default"], &Policy::default());
+        let mut subtrees: Vec<(String, Policy)> = config["subtrees"]
+            .entries()
+            .map(|(prefix, value)| (prefix.to_string(), Policy::from_json(value, &default)))
+            .collect();
+        // the longest first
+        subtrees.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+
+        Policies {
+            default,
+            subtrees,
+            garbage_days: config["garbage_days"].as_usize(),
+        }
+    }
+
+    /// Policy for the page.
+    pub fn policy(&self, page_path: &str) -> &Policy {
+        self.subtrees
+            .iter()
+            .find(|(prefix, _)| page_path.starts_with(prefix.as_str()))
+            .map(|(_, policy)| policy)
+            .unwrap_or(&self.default)
+    }
+}
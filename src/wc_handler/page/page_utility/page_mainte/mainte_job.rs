@@ -0,0 +1,235 @@
+use super::page_form_update::{self, Log};
+use super::Page;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+// Run page_mainte in a background thread as a job.
+// The job reports progress through page_form_update::Log, and stops
+// at the next page when cancelled.
+// One job runs on a subtree at a time, the subtree of a page is the pages
+// in the directory of the page and its descendants.
+//
+// status of a job:
+// {"job_id": 1, "mainte_url": "/notes/notes.html", "status": "running",
+//  "cancel": false, "stage": "form_update", "visited": 12, "updated": ["/notes/a.html"],
+//  "already": [...], "failed": [...], "encoding": [{"page", "encoding"}],
+//  "migration": {...}}
+// status: "running", "done", "cancelled", "failed"
+// cancel: true if cancel was requested, the job stops at the next page.
+// migration: the report of page_migrate and page_sanitize after the stages.
+
+/// Number of finished jobs kept for status.
+const JOBS_FINISHED_KEEP: usize = 20;
+
+static JOBS: Mutex<Vec<Arc<MainteJob>>> = Mutex::new(Vec::new());
+static JOB_ID_LAST: AtomicUsize = AtomicUsize::new(0);
+
+pub struct MainteJob {
+    id: usize,
+    page_path: String,
+    cancel: AtomicBool,
+    state: Mutex<JobState>,
+}
+
+struct JobState {
+    status: &'static str,
+    stage: &'static str,
+    visited: usize,
+    updated: Vec<String>,
+    already: Vec<String>,
+    failed: Vec<String>,
+    encodings: Vec<(String, String)>,
+    migration: Option<json::JsonValue>,
+}
+
+impl MainteJob {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state_lock().status == "running"
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    fn state_lock(&self) -> std::sync::MutexGuard<'_, JobState> {
+        // A panic in the job thread should not hide the status.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn stage_set(&self, stage: &'static str) {
+        self.state_lock().stage = stage;
+    }
+
+    /// A page handled in page_form_update, result: "updated", "already" or "failed".
+    pub fn visited(&self, page_path: &str, result: &str) {
+        let mut state = self.state_lock();
+        state.visited += 1;
+        let list = match result {
+            "updated" => &mut state.updated,
+            "already" => &mut state.already,
+            _ => &mut state.failed,
+        };
+        list.push(page_path.to_string());
+    }
+
+    pub fn encoding(&self, page_path: &str, encoding: &str) {
+        self.state_lock()
+            .encodings
+            .push((page_path.to_string(), encoding.to_string()));
+    }
+
+    fn finish(&self, status: &'static str, migration: Option<json::JsonValue>) {
+        let mut state = self.state_lock();
+        state.status = status;
+        state.migration = migration;
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        let state = self.state_lock();
+        let encodings: Vec<json::JsonValue> = state
+            .encodings
+            .iter()
+            .map(|(page, encoding)| json::object! {"page": page.as_str(), "encoding": encoding.as_str()})
+            .collect();
+        json::object! {
+            "job_id": self.id,
+            "mainte_url": self.page_path.as_str(),
+            "status": state.status,
+            "cancel": self.cancelled(),
+            "stage": state.stage,
+            "visited": state.visited,
+            "updated": state.updated.clone(),
+            "already": state.already.clone(),
+            "failed": state.failed.clone(),
+            "encoding": encodings,
+            "migration": state.migration.clone(),
+        }
+    }
+}
+
+/// "/notes/notes.html" to "/notes/"
+fn subtree_dir(page_path: &str) -> &str {
+    match page_path.rfind('/') {
+        Some(i) => &page_path[..=i],
+        None => "/",
+    }
+}
+
+fn subtree_overlap(page_path1: &str, page_path2: &str) -> bool {
+    let dir1 = subtree_dir(page_path1);
+    let dir2 = subtree_dir(page_path2);
+    dir1.starts_with(dir2) || dir2.starts_with(dir1)
+}
+
+fn jobs_lock() -> std::sync::MutexGuard<'static, Vec<Arc<MainteJob>>> {
+    JOBS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Start page_mainte on the page as a job.
+/// Err if a job is running on the subtree.
+pub fn mainte_start(
+    stor_root: &str,
+    page_path: &str,
+    recursive: bool,
+) -> Result<Arc<MainteJob>, String> {
+    let job = {
+        let mut jobs = jobs_lock();
+        if let Some(job) = jobs
+            .iter()
+            .find(|job| job.is_running() && subtree_overlap(&job.page_path, page_path))
+        {
+            return Err(format!(
+                "Mainte job {} is running on {}",
+                job.id, job.page_path
+            ));
+        }
+
+        let job = Arc::new(MainteJob {
+            id: JOB_ID_LAST.fetch_add(1, Ordering::Relaxed) + 1,
+            page_path: page_path.to_string(),
+            cancel: AtomicBool::new(false),
+            state: Mutex::new(JobState {
+                status: "running",
+                stage: "",
+                visited: 0,
+                updated: vec![],
+                already: vec![],
+                failed: vec![],
+                encodings: vec![],
+                migration: None,
+            }),
+        });
+        jobs.push(Arc::clone(&job));
+        jobs_prune(&mut jobs);
+        job
+    };
+
+    let stor_root = stor_root.to_string();
+    let job_thread = Arc::clone(&job);
+    std::thread::Builder::new()
+        .name(format!("mainte_job_{}", job.id))
+        .spawn(move || job_run(&stor_root, job_thread, recursive))
+        .map_err(|e| {
+            job.finish("failed", None);
+            format!("Failed to start mainte job: {}", e)
+        })?;
+
+    info!("mainte job {} started on {}", job.id, job.page_path);
+    Ok(job)
+}
+
+fn job_run(stor_root: &str, job: Arc<MainteJob>, recursive: bool) {
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        // Pages updated in the job are one commit in git.
+        let _git_operation = super::super::rev_git::operation(stor_root, "mainte");
+        let log = Rc::new(RefCell::new(Log::for_job(Arc::clone(&job))));
+        let mut page = Page::new(stor_root, &job.page_path);
+        let mainte_log = page.mainte(recursive, Some(Rc::clone(&log)));
+        page_form_update::tracing_page_save(&mut page, log);
+        mainte_log.to_json()
+    }));
+
+    match res {
+        Ok(migration) => {
+            let status = if job.cancelled() { "cancelled" } else { "done" };
+            job.finish(status, Some(migration));
+            info!("mainte job {} {}", job.id, status);
+        }
+        Err(_) => {
+            job.finish("failed", None);
+            error!("mainte job {} failed", job.id);
+        }
+    }
+}
+
+/// Remove finished jobs older than JOBS_FINISHED_KEEP.
+fn jobs_prune(jobs: &mut Vec<Arc<MainteJob>>) {
+    let finished = jobs.iter().filter(|job| !job.is_running()).count();
+    let mut remove = finished.saturating_sub(JOBS_FINISHED_KEEP);
+    jobs.retain(|job| {
+        if 0 < remove && !job.is_running() {
+            remove -= 1;
+            return false;
+        }
+        true
+    });
+}
+
+pub fn job(job_id: usize) -> Option<Arc<MainteJob>> {
+    jobs_lock().iter().find(|job| job.id == job_id).cloned()
+}
+
+pub fn jobs() -> Vec<Arc<MainteJob>> {
+    jobs_lock().clone()
+}
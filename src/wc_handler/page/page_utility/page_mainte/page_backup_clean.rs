@@ -1,21 +1,62 @@
+use super::backup_policy::{Policies, Policy, GARBAGE_DAYS, GARBAGE_DAYS_MIN};
 pub use super::page_children_url;
+use super::rev_store;
+use super::storage;
 use super::Page;
 use tracing::{error, info}; //  event, instrument, span, Level debug,, info_span, warn
                             //
 
-pub fn page_backup_clean(page: &mut Page, recursive: bool) {
-    // files_leave: number of files to leave; ie. not delete and keep
-    let files_leave = 10;
+// Move backups out of the retention policy (see backup_policy.rs) to stor_root/gabage,
+// and purge files in the garbage after the grace period.
+// A backup is moved to the same path under the garbage,
+// /notes/notes.html.3 to /gabage/notes/notes.html.3.
+// dry_run lists files to be moved or purged without changes.
 
-    // days atleas keep
-    let days_keep = 30;
-    // let days_keep = 900;
+const DIR_GABAGE: &str = "/gabage";
 
+/// Files moved or to be moved in dry_run, paths under stor_root.
+pub struct BackupCleanLog {
+    dry_run: bool,
+    moved: Vec<String>,
+    purged: Vec<String>,
+}
+
+impl BackupCleanLog {
+    pub fn new(dry_run: bool) -> BackupCleanLog {
+        BackupCleanLog {
+            dry_run,
+            moved: vec![],
+            purged: vec![],
+        }
+    }
+
+    /// {"dry_run": true, "moved": ["/a/a.html.3"], "purged": ["/gabage/b/b.html.2"]}
+    pub fn to_json(&self) -> json::JsonValue {
+        json::object! {
+            "dry_run": self.dry_run,
+            "moved": self.moved.clone(),
+            "purged": self.purged.clone(),
+        }
+    }
+}
+
+pub fn page_backup_clean(page: &mut Page, recursive: bool, log: &mut BackupCleanLog) {
+    let policies = Policies::load(page.stor_root());
+    page_backup_clean_policy(page, recursive, &policies, log);
+}
+
+fn page_backup_clean_policy(
+    page: &mut Page,
+    recursive: bool,
+    policies: &Policies,
+    log: &mut BackupCleanLog,
+) {
     let Ok(dir_gabage) = dir_gabage(page) else {
         return;
     };
 
-    let moved = match delete_html_dot_rev_s(page, files_leave, days_keep, &dir_gabage) {
+    let policy = policies.policy(page.page_path());
+    let moved = match delete_html_dot_rev_s(page, policy, &dir_gabage, log) {
         Ok(v) => v,
         Err(_) => return,
     };
@@ -24,26 +65,108 @@ pub fn page_backup_clean(page: &mut Page, recursive: bool) {
     // wc_top.html.7 style backup more than file_leave,
     // so all wc_top_rev2.html style are to be moved.
     if 0 < moved {
-        let _ = delete_rev_dot_html_s(page, &dir_gabage);
+        let _ = delete_rev_dot_html_s(page, &dir_gabage, log);
     }
 
     if recursive {
-        page_backup_clean_children(page, recursive);
+        page_backup_clean_children(page, recursive, policies, log);
     }
 }
 
-fn page_backup_clean_children(page: &mut Page, recursive: bool) {
+fn page_backup_clean_children(
+    page: &mut Page,
+    recursive: bool,
+    policies: &Policies,
+    log: &mut BackupCleanLog,
+) {
     let stor_root = page.stor_root().to_string();
     let child_url_s = page_children_url(page);
     for child_url in child_url_s {
         let mut child_page = super::Page::new(&stor_root, child_url.path());
-        page_backup_clean(&mut child_page, recursive);
+        page_backup_clean_policy(&mut child_page, recursive, policies, log);
     }
 }
 
+/// Delete files in the garbage moved more than the grace period ago.
+/// garbage_days: the grace period, garbage_days in wc_backup.json if None.
+/// Nothing is purged if neither is given and force is false.
+/// The grace period is GARBAGE_DAYS_MIN at least.
+pub fn garbage_purge(
+    stor_root: &str,
+    garbage_days: Option<usize>,
+    force: bool,
+    log: &mut BackupCleanLog,
+) -> Result<usize, String> {
+    let garbage_days = match garbage_days.or(Policies::load(stor_root).garbage_days) {
+        Some(v) => v,
+        None if force => GARBAGE_DAYS,
+        None => return Ok(0),
+    };
+    if garbage_days < GARBAGE_DAYS_MIN {
+        info!(
+            "garbage_days {} is less than {}, {} is used",
+            garbage_days, GARBAGE_DAYS_MIN, GARBAGE_DAYS_MIN
+        );
+    }
+    let garbage_days = garbage_days.max(GARBAGE_DAYS_MIN);
+
+    let mut purged = 0;
+    for path in garbage_files(stor_root) {
+        // mtime is when the file was moved to the garbage, see rename().
+        match duration_modified_days(&path) {
+            Ok(days) if garbage_days <= days => (),
+            Ok(_) => continue,
+            Err(e) => {
+                error!("Failed to get duration days: {} on {:?}", e, path);
+                continue;
+            }
+        }
+
+        if !log.dry_run {
+            if let Err(e) = storage::remove(&path) {
+                error!("Failed to purge {:?}: {}", path, e);
+                continue;
+            }
+        }
+        purged += 1;
+        let path = path.to_string_lossy();
+        log.purged
+            .push(path.strip_prefix(stor_root).unwrap_or(&path).to_string());
+    }
+
+    info!(
+        "gabage purged {} files older than {} days",
+        purged, garbage_days
+    );
+    Ok(purged)
+}
+
+/// Files in the garbage and in dirs of it.
+fn garbage_files(stor_root: &str) -> Vec<std::path::PathBuf> {
+    let mut files = vec![];
+    let mut dirs = vec![std::path::PathBuf::from(super::super::file_path(
+        stor_root, DIR_GABAGE,
+    ))];
+    while let Some(dir) = dirs.pop() {
+        // no garbage
+        let Ok(paths) = storage::list(&dir) else {
+            continue;
+        };
+        for path in paths {
+            match storage::metadata(&path) {
+                Ok(metadata) if metadata.is_dir => dirs.push(path),
+                Ok(_) => files.push(path),
+                Err(e) => error!("Failed to get metadata of {:?}: {}", path, e),
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
 fn duration_modified_days(path: &std::path::Path) -> Result<usize, String> {
-    let metadata = path.metadata().or_else(|e| Err(e.to_string()))?;
-    let modified = metadata.modified().or_else(|e| Err(e.to_string()))?;
+    let metadata = storage::metadata(path).map_err(|e| e.to_string())?;
+    let modified = metadata.modified;
     let now = std::time::SystemTime::now();
     let duration_modified = now
         .duration_since(modified)
@@ -54,8 +177,20 @@ fn duration_modified_days(path: &std::path::Path) -> Result<usize, String> {
     usize::try_from(duration_modified_days).or_else(|e| Err(e.to_string()))
 }
 
+/// Days since the epoch when the file was modified.
+fn modified_day(path: &std::path::Path) -> Result<u64, String> {
+    let modified = storage::metadata(path)
+        .map(|metadata| metadata.modified)
+        .map_err(|e| e.to_string())?;
+    let since_epoch = modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?;
+    Ok(since_epoch.as_secs() / (60 * 60 * 24))
+}
+
 /// This funciton rename may not need to a separate function.
 /// But to make rename procedure in one place, it easy to skip renaming for debug.
+/// The file moved is touched, its mtime tells when it was moved for garbage_purge.
 fn rename(path_rev: &std::path::Path, path_gabage: &std::path::Path) -> std::io::Result<()> {
     // DBG
     //let dbg = true;
@@ -71,7 +206,7 @@ fn rename(path_rev: &std::path::Path, path_gabage: &std::path::Path) -> std::io:
 
         // if path_rev exists, behave as rename was successed
         // path_rev.try_exists().and(Ok(()))
-        if path_rev.is_file() {
+        if storage::is_file(path_rev) {
             Ok(())
             // return Ok(());
         } else {
@@ -80,17 +215,48 @@ fn rename(path_rev: &std::path::Path, path_gabage: &std::path::Path) -> std::io:
             // return Err(err);
         }
     } else {
-        std::fs::rename(&path_rev, &path_gabage)
+        storage::rename(path_rev, path_gabage)?;
+        if let Err(e) = storage::set_modified(path_gabage, std::time::SystemTime::now()) {
+            // It is purged earlier than the grace period.
+            error!("Failed to touch {:?}: {}", path_gabage, e);
+        }
+        Ok(())
     }
 }
 
-/// ex. wc_top.html.7
+/// Move the file to the garbage, or only log it in dry_run.
+fn gabage_move(
+    page: &Page,
+    path_rev: &std::path::Path,
+    dir_gabage: &std::path::Path,
+    log: &mut BackupCleanLog,
+) -> Result<(), ()> {
+    // The path under stor_root, not to mix up backups of pages of the same name.
+    let path_in_root = path_rev.strip_prefix(page.stor_root()).map_err(|_| {
+        error!("Not in stor_root: {:?}", path_rev);
+    })?;
+    let path_gabage = dir_gabage.join(path_in_root);
+
+    if !log.dry_run {
+        super::super::dir_build(&path_gabage, true).map_err(|e| error!("{}", e))?;
+        if let Err(e) = rename(path_rev, &path_gabage) {
+            error!("{} on {:?}", e, path_rev);
+            return Err(());
+        }
+    }
+
+    let path_rev = path_rev.to_string_lossy();
+    let path_rev = path_rev.strip_prefix(page.stor_root()).unwrap_or(&path_rev);
+    log.moved.push(path_rev.to_string());
+    Ok(())
+}
+
+/// ex. wc_top.html.7, or wc_top.html.7.z compressed
 fn delete_html_dot_rev_s(
     page: &mut Page,
-    files_leave: usize,
-    days_keep: usize,
+    policy: &Policy,
     dir_gabage: &std::path::Path,
-    // ) -> Result<(), ()> {
+    log: &mut BackupCleanLog,
 ) -> Result<usize, ()> {
     let rev = match page.rev() {
         Ok(v) => v,
@@ -100,15 +266,42 @@ fn delete_html_dot_rev_s(
         }
     };
 
-    let Some(rev_sub) = rev.checked_sub(files_leave) else {
-        // return Ok(());
-        return Ok(0);
-    };
+    // (rev, day modified, days since modified) of existing files.
+    // rev_store::revs() lists existing files only, it avoids error message
+    // at duration_modified_days call later.
+    let mut revs = vec![];
+    let mut paths_rev = std::collections::HashMap::new();
+    for (rev_app, path_rev) in rev_store::revs(page, rev) {
+        match (modified_day(&path_rev), duration_modified_days(&path_rev)) {
+            (Ok(day), Ok(age)) => {
+                revs.push((rev_app, day, age));
+                paths_rev.insert(rev_app, path_rev);
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                error!("Failed to get duration days: {} on {:?}", e, path_rev);
+            }
+        }
+    }
+
+    let revs_keep = policy.revs_keep(&revs, rev);
 
+    let page_revs = rev_store::PageRevs::new(page);
     let mut moved_rev = vec![];
-    for rev_app in { 0..=rev_sub }.rev() {
-        if let Ok(()) = delete_html_dot_rev(page, rev_app, days_keep, dir_gabage) {
-            moved_rev.push(rev_app);
+    for (rev_app, _, _) in revs.iter().rev() {
+        if revs_keep.contains(rev_app) {
+            continue;
+        }
+        if is_before_original_backup(page, &page_revs, *rev_app) {
+            info!(
+                "Keep {} rev:{}, before the original backup",
+                page.file_path(),
+                rev_app
+            );
+            continue;
+        }
+        let path_rev = &paths_rev[rev_app];
+        if let Ok(()) = gabage_move(page, path_rev, dir_gabage, log) {
+            moved_rev.push(*rev_app);
         };
     }
 
@@ -121,55 +314,32 @@ fn delete_html_dot_rev_s(
     Ok(moved_rev.len())
 }
 
-/// Delete page file with rev as a backup.
-/// Return
-fn delete_html_dot_rev(
-    page: &mut Page,
-    rev: usize,
-    days_keep: usize,
-    dir_gabage: &std::path::Path,
-) -> Result<(), ()> {
-    let path_rev = page.path_rev_form(rev);
-
-    // this is_file() filter avoids error message at duration_modified_days call later.
-    if !path_rev.is_file() {
-        return Err(());
-    }
-
-    let modified_days = match duration_modified_days(&path_rev) {
-        Ok(v) => v,
-        Err(e) => {
-            error!("Failed to get duration days: {} on {:?}", e, path_rev);
-            // continue;
-            return Err(());
-        }
+/// If the next rev is the original backup of page_form_update,
+/// the backup of a page in an old form followed by the page converted.
+fn is_before_original_backup(page: &Page, page_revs: &rev_store::PageRevs, rev: usize) -> bool {
+    let Some(rev_next) = rev.checked_add(1) else {
+        return false;
     };
-
-    if modified_days < days_keep {
-        info!("Less than days_keep ({})", days_keep);
-        return Err(());
-    }
-
-    // file;
-    // dir_gabage;
-
-    let Some(filename_rev) = path_rev.file_name() else {
-        return Err(());
+    let Some(rev_converted) = rev.checked_add(2) else {
+        return false;
     };
-    let path_gabage = dir_gabage.join(filename_rev);
+    !has_page_json(page, page_revs, rev_next)
+        && page_revs.exists(page, rev_next)
+        && has_page_json(page, page_revs, rev_converted)
+}
 
-    if let Err(e) = rename(&path_rev, &path_gabage) {
-        error!("{} on {:?}", e, &path_rev);
-        Err(())
-    } else {
-        // moved_rev.push(rev);
-        Ok(())
-    }
+/// If the rev is a page of the current form, with page json in span.
+fn has_page_json(page: &Page, page_revs: &rev_store::PageRevs, rev: usize) -> bool {
+    let Ok(source) = page_revs.read(page, rev) else {
+        return false;
+    };
+    let ptn = b"id=\"page_json_str\"";
+    source.windows(ptn.len()).any(|window| window == ptn)
 }
 
 fn dir_gabage(page: &mut Page) -> Result<std::path::PathBuf, ()> {
     let dir_gabage = String::from(page.stor_root());
-    let dir_gabage = dir_gabage + "/gabage";
+    let dir_gabage = dir_gabage + DIR_GABAGE;
     let dir_gabage = std::path::PathBuf::from(&dir_gabage);
 
     // page_utility::dir_build required a path for a file as an argument
@@ -185,7 +355,11 @@ fn dir_gabage(page: &mut Page) -> Result<std::path::PathBuf, ()> {
 }
 
 // computing_iroiro_rev2.html
-fn delete_rev_dot_html_s(page: &mut Page, dir_gabage: &std::path::Path) -> Result<usize, ()> {
+fn delete_rev_dot_html_s(
+    page: &mut Page,
+    dir_gabage: &std::path::Path,
+    log: &mut BackupCleanLog,
+) -> Result<usize, ()> {
     //
     let mut rev: usize = 0;
     // if rename(move) error happens more than probes times,
@@ -197,21 +371,7 @@ fn delete_rev_dot_html_s(page: &mut Page, dir_gabage: &std::path::Path) -> Resul
 
     let mut moved_rev = vec![];
     loop {
-        // // parent + file_stem + "_rev" + rev + (.) + extension
-        // let path_rev = match path_with_rev_dot(page, rev) {
-        //     Ok(v) => v,
-        //     Err(_) => break,
-        // };
-
-        // let Some(filename_rev) = path_rev.file_name() else {
-        //     break;
-        // };
-        // let path_gabage = dir_gabage.join(filename_rev);
-
-        // if let Err(_e) = rename(&path_rev, &path_gabage) {
-        if let Err(_) = delete_rev_dot_html(page, rev, dir_gabage) {
-            // error!("{} on {:?}", e, &path_rev);
-        } else {
+        if delete_rev_dot_html(page, rev, dir_gabage, log).is_ok() {
             moved_rev.push(rev);
             rev_max = match rev.checked_add(probes) {
                 Some(v) => v,
@@ -245,13 +405,15 @@ fn delete_rev_dot_html(
     page: &mut Page,
     rev: usize,
     dir_gabage: &std::path::Path,
+    log: &mut BackupCleanLog,
 ) -> Result<(), ()> {
     // parent + file_stem + "_rev" + rev + (.) + extension
     let path_rev = path_with_rev_dot(page, rev)?;
+    if !storage::is_file(&path_rev) {
+        return Err(());
+    }
 
-    let filename_rev = path_rev.file_name().ok_or(())?;
-    let path_gabage = dir_gabage.join(filename_rev);
-    rename(&path_rev, &path_gabage).or(Err(()))
+    gabage_move(page, &path_rev, dir_gabage, log)
 }
 
 /// parent + file_stem + "_rev" + rev + (.) + extension
@@ -1,13 +1,15 @@
 use super::dom_utility;
-use super::fs_write;
 use super::json_from_dom;
+use super::mainte_job::MainteJob;
 use super::page_child_new;
 pub use super::page_children_url;
 use super::page_json;
+use super::rev_store;
 use super::Page;
 use std::cell::RefCell;
 use std::collections::HashSet;
 use std::rc::Rc;
+use std::sync::Arc;
 use tracing::error;
 use tracing::info; // {error, event, info, instrument, span, Level, Node} // {event, info, instrument, span, Level, Node, info}
 
@@ -15,11 +17,22 @@ use tracing::info; // {error, event, info, instrument, span, Level, Node} // {ev
 pub fn page_form_update(page: &mut Page, recursive: bool, log: Option<Rc<RefCell<Log>>>) {
     let (log_org, log) = log_ini(log);
 
+    if log.borrow().cancelled() {
+        return;
+    }
+
     // alredy handled
     if log.borrow().handled(page) {
         return;
     }
 
+    // Pages not in UTF-8 are saved in UTF-8 even if those are in the current style.
+    let encoding = page.encoding();
+    if let Some(encoding) = encoding.filter(|encoding| *encoding != encoding_rs::UTF_8) {
+        log.borrow_mut().encoding(page, encoding.name());
+    }
+    let is_utf8 = encoding.is_none_or(|encoding| encoding == encoding_rs::UTF_8);
+
     let page_dom = match page.dom() {
         Some(v) => v,
         None => {
@@ -34,7 +47,7 @@ pub fn page_form_update(page: &mut Page, recursive: bool, log: Option<Rc<RefCell
 
     // page_top found
     // It is current page style, not for upgrade
-    if dom_utility::get_div_page_top(&page_node).is_some() {
+    if dom_utility::get_div_page_top(page_node).is_some() && is_utf8 {
         log.borrow_mut().already(page);
         return;
     }
@@ -113,7 +126,11 @@ pub struct Log {
     already: Vec<String>,
     updated: Vec<String>,
     failed: Vec<String>,
+    // (page_path, encoding name) of pages not in UTF-8.
+    encodings: Vec<(String, String)>,
     handled: HashSet<String>,
+    // Progress is reported to the job, and the job may be cancelled.
+    job: Option<Arc<MainteJob>>,
 }
 
 impl Log {
@@ -122,7 +139,25 @@ impl Log {
             already: vec![],
             updated: vec![],
             failed: vec![],
+            encodings: vec![],
             handled: HashSet::new(),
+            job: None,
+        }
+    }
+
+    pub fn for_job(job: Arc<MainteJob>) -> Log {
+        let mut log = Log::new();
+        log.job.replace(job);
+        log
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.job.as_ref().is_some_and(|job| job.cancelled())
+    }
+
+    pub fn stage_set(&self, stage: &'static str) {
+        if let Some(job) = self.job.as_ref() {
+            job.stage_set(stage);
         }
     }
 
@@ -137,6 +172,9 @@ impl Log {
         let page_path = page.page_path();
         self.already.push(page_path.to_string());
         self.handled.insert(page_path.to_string());
+        if let Some(job) = self.job.as_ref() {
+            job.visited(page_path, "already");
+        }
     }
 
     pub fn updated(&mut self, page: &mut Page) {
@@ -146,6 +184,9 @@ impl Log {
         let page_path = page.page_path();
         self.updated.push(page_path.to_string());
         self.handled.insert(page_path.to_string());
+        if let Some(job) = self.job.as_ref() {
+            job.visited(page_path, "updated");
+        }
     }
 
     pub fn failed(&mut self, page: &mut Page) {
@@ -155,6 +196,21 @@ impl Log {
         let page_path = page.page_path();
         self.failed.push(page_path.to_string());
         self.handled.insert(page_path.to_string());
+        if let Some(job) = self.job.as_ref() {
+            job.visited(page_path, "failed");
+        }
+    }
+
+    /// Original encoding of the page, it is recorded apart from the result.
+    pub fn encoding(&mut self, page: &mut Page, encoding: &str) {
+        if self.handled(page) {
+            return;
+        }
+        self.encodings
+            .push((page.page_path().to_string(), encoding.to_string()));
+        if let Some(job) = self.job.as_ref() {
+            job.encoding(page.page_path(), encoding);
+        }
     }
 
     fn updated_list(&self) -> Vec<String> {
@@ -173,6 +229,13 @@ impl Log {
         mv
     }
 
+    fn encoding_list(&self) -> Vec<String> {
+        self.encodings
+            .iter()
+            .map(|(path, encoding)| format!("{}: {}", path, encoding))
+            .collect()
+    }
+
     fn failed_list(&self) -> Vec<String> {
         let mut mv = vec![];
         for path in &self.failed {
@@ -212,15 +275,13 @@ fn last_rev_of_files(page: &mut super::Page, rev: Option<usize>) -> Option<usize
     // fm: rev + 1
     let fm = rev.checked_add(1).or(Some(usize::MAX)).unwrap();
     let to = rev.checked_add(100).or(Some(usize::MAX)).unwrap();
+    let page_revs = rev_store::PageRevs::new(page);
     for rev in fm..to {
         // rev_last = rev;
         // let path_rev = page.file_path() + "." + rev.to_string().as_str();
-        let path_rev = page.path_rev_form(rev);
-        if let Ok(exists) = std::fs::exists(&path_rev) {
-            if exists {
-                rev_last = rev;
-                continue;
-            }
+        if page_revs.exists(page, rev) {
+            rev_last = rev;
+            continue;
         }
         break;
     }
@@ -237,32 +298,24 @@ fn page_org_backup(page: &mut Page, rev_crt: &usize) -> Option<usize> {
 
     // let path_rev_uped = page.file_path() + "." + rev_uped.to_string().as_str();
     let path_rev_uped = page.path_rev_form(rev_uped);
-    let path_rev_uped_ref = match path_rev_uped.to_str() {
-        Some(v) => v,
-        None => {
-            error!("Failed to get str from: {:?}", &path_rev_uped);
-            return None;
-        }
-    };
 
-    let source = page.source()?;
-    // match super::fs_write(&path_rev_uped, source) {
-    match fs_write(path_rev_uped_ref, source) {
+    let source = page.source()?.clone();
+    match rev_store::rev_write(page, rev_uped, &source) {
         Ok(_) => {
-            // info!("Original backup: {}", &path_rev_uped_ref);
+            // info!("Original backup: {:?}", &path_rev_uped);
             //
             Some(rev_uped)
         }
         Err(e) => {
-            error!("Failed, Original backup: {}, {}", &path_rev_uped_ref, e);
+            error!("Failed, Original backup: {:?}, {}", &path_rev_uped, e);
             None
         }
     }
 }
 
-fn tracing_page_save(page_some: &mut Page, log: Rc<RefCell<Log>>) {
+pub fn tracing_page_save(page_some: &mut Page, log: Rc<RefCell<Log>>) {
     let stor_root = page_some.stor_root();
-    let mut page_top = Page::new(stor_root, "/wc_top.html");
+    let mut page_top = Page::new(stor_root, super::super::PAGE_TOP_PATH);
 
     let file_name = "page_form_updated_log.html";
     let page_path = "/".to_string() + file_name;
@@ -287,7 +340,7 @@ fn tracing_page_save(page_some: &mut Page, log: Rc<RefCell<Log>>) {
     };
 
     let subsection_top = &mut subsections_json["0"];
-    subsection_top["child"] = json::array![1, 2, 3];
+    subsection_top["child"] = json::array![1, 2, 3, 4];
 
     // failed
     let id = 1;
@@ -326,7 +379,19 @@ fn tracing_page_save(page_some: &mut Page, log: Rc<RefCell<Log>>) {
     let _ = contents[0].insert::<json::JsonValue>("value", list.into());
     subsections_json[id.to_string().as_str()] = subsection;
 
-    page_json["data"]["subsection"]["id"]["id_next"] = 4.into();
+    // encoding, the original encoding of pages saved in UTF-8
+    let id = 4;
+    let mut subsection = subsection_new("encoding");
+    subsection["id"] = id.into();
+    let json::JsonValue::Array(contents) = &mut subsection["content"] else {
+        return;
+    };
+    let mut list = log.borrow().encoding_list();
+    let list = list_str(&mut list);
+    let _ = contents[0].insert::<json::JsonValue>("value", list.into());
+    subsections_json[id.to_string().as_str()] = subsection;
+
+    page_json["data"]["subsection"]["id"]["id_next"] = 5.into();
 
     if let Err(e) = page_form_update_log.json_replace_save(page_json) {
         error!("{}", &e);
@@ -0,0 +1,502 @@
+use super::page_children_url;
+use super::page_json;
+use super::page_json::page_data::Navi;
+use super::Page;
+use std::collections::{HashMap, HashSet};
+use tracing::{error, info};
+
+/// Check consistency of the page tree from page walking children by page_children_url.
+/// If fix is true, repair what is safely repairable and save the page with a new rev.
+///
+/// Checks:
+/// page_json: page json is malformed for page_data, it is read as it is.
+/// navi: navi of a child page should be as same as navi of its parent page.
+/// subsection_link: subsection href links to a file that does not exist.
+/// moved_to: moved_to chain loops or ends at a file that does not exist.
+/// subsection_id: id of subsection duplicated or does not match its key.
+/// subsection_child: child id of subsection does not exist.
+/// id_next: id_next is not greater than existing ids.
+/// attachment: a file in the directory of attachments is not referred to by the page.
+pub fn page_fsck(page: &mut Page, fix: bool) -> Fsck {
+    let mut fsck = Fsck::new(fix);
+    page_fsck_page(page, None, &mut fsck);
+    info!(
+        "fsck {} pages, {} findings",
+        fsck.handled.len(),
+        fsck.findings.len()
+    );
+    fsck
+}
+
+pub struct Fsck {
+    fix: bool,
+    findings: Vec<Finding>,
+    handled: HashSet<String>,
+}
+
+struct Finding {
+    page_path: String,
+    kind: &'static str,
+    detail: String,
+    fixed: bool,
+}
+
+impl Fsck {
+    fn new(fix: bool) -> Fsck {
+        Fsck {
+            fix,
+            findings: vec![],
+            handled: HashSet::new(),
+        }
+    }
+
+    fn found(&mut self, page: &Page, kind: &'static str, detail: String, fixed: bool) {
+        info!("fsck {}: {}: {}", page.page_path(), kind, detail);
+        self.findings.push(Finding {
+            page_path: page.page_path().to_string(),
+            kind,
+            detail,
+            fixed,
+        });
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut findings = json::array![];
+        for finding in &self.findings {
+            let _ = findings.push(json::object! {
+                "page": finding.page_path.as_str(),
+                "kind": finding.kind,
+                "detail": finding.detail.as_str(),
+                "fixed": finding.fixed,
+            });
+        }
+
+        json::object! {
+            "res": "fsck",
+            "pages": self.handled.len(),
+            "findings": findings,
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "{} pages, {} findings\n",
+            self.handled.len(),
+            self.findings.len()
+        );
+        for finding in &self.findings {
+            text = text
+                + &format!(
+                    "{}: {}: {}",
+                    finding.page_path, finding.kind, finding.detail
+                );
+            if finding.fixed {
+                text += " (fixed)";
+            }
+            text += "\n";
+        }
+        text
+    }
+}
+
+/// navi_expected: navi the page should have as a child of its parent.
+/// It is None for the top of the checking.
+fn page_fsck_page(page: &mut Page, navi_expected: Option<Vec<Navi>>, fsck: &mut Fsck) {
+    if !fsck.handled.insert(page.page_path().to_string()) {
+        return;
+    }
+
+    if page.json().is_none() {
+        fsck.found(
+            page,
+            "page_json",
+            "Failed to get page_json".to_string(),
+            false,
+        );
+        return;
+    }
+
+    if let Some(Err(e)) = page.json().map(|page_json| page_json.page_data()) {
+        fsck.found(page, "page_json", e, false);
+    }
+
+    let mut fixed = false;
+
+    if let Some(navi_expected) = navi_expected {
+        fixed |= fsck_navi(page, navi_expected, fsck);
+    }
+
+    fsck_moved_to(page, fsck);
+    fsck_subsection_link(page, fsck);
+    fsck_attachment(page, fsck);
+    fixed |= fsck_subsection_id(page, fsck);
+    fixed |= fsck_subsection_parent(page, fsck);
+
+    if fixed {
+        fsck_page_save(page, fsck);
+    }
+
+    page_fsck_children(page, fsck);
+}
+
+fn page_fsck_children(page: &mut Page, fsck: &mut Fsck) {
+    let stor_root = page.stor_root().to_string();
+    let Ok(parent_url) = super::super::page_url(page) else {
+        return;
+    };
+
+    for child_url in page_children_url(page) {
+        let mut child_page = Page::new(&stor_root, child_url.path());
+        // subsection_link reports the page that does not exist.
+        let Some(child_json) = child_page.json() else {
+            continue;
+        };
+        // Malformed one is checked without navi.
+        let child_title = child_json
+            .page_data()
+            .map(|page_data| page_data.page.title)
+            .ok();
+        let Some(child_title) = child_title else {
+            page_fsck_page(&mut child_page, None, fsck);
+            continue;
+        };
+
+        let navi_expected = navi_child_expected(page, &parent_url, &child_url, &child_title);
+        page_fsck_page(&mut child_page, navi_expected, fsck);
+    }
+}
+
+/// Navi of a child page created from the parent page as page_child_new does.
+fn navi_child_expected(
+    parent_page: &mut Page,
+    parent_url: &url::Url,
+    child_url: &url::Url,
+    child_title: &str,
+) -> Option<Vec<Navi>> {
+    let mut navi = super::super::child_navi(parent_page, parent_url, child_url).ok()?;
+    navi.push(Navi {
+        title: child_title.to_string(),
+        href: "".to_string(),
+    });
+    Some(navi)
+}
+
+/// Return true if fixed.
+fn fsck_navi(page: &mut Page, navi_expected: Vec<Navi>, fsck: &mut Fsck) -> bool {
+    let Ok(page_url) = super::super::page_url(page) else {
+        return false;
+    };
+    let Some(Ok(mut page_data)) = page.json().map(|page_json| page_json.page_data()) else {
+        return false;
+    };
+    let navi = &page_data.navi;
+
+    let detail = if navi.len() != navi_expected.len() {
+        Some(format!(
+            "navi has {} items, expected {}",
+            navi.len(),
+            navi_expected.len()
+        ))
+    } else {
+        // Compare hrefs of parents, the last one is the page itself.
+        (0..navi.len().saturating_sub(1)).find_map(|i| {
+            let href = navi_href_path(&page_url, &navi[i].href);
+            let href_expected = navi_href_path(&page_url, &navi_expected[i].href);
+            if href == href_expected {
+                None
+            } else {
+                Some(format!(
+                    "navi[{}] links to {}, expected {}",
+                    i,
+                    href.unwrap_or_default(),
+                    href_expected.unwrap_or_default()
+                ))
+            }
+        })
+    };
+
+    let Some(detail) = detail else {
+        return false;
+    };
+
+    if !fsck.fix {
+        fsck.found(page, "navi", detail, false);
+        return false;
+    }
+
+    page_data.navi = navi_expected;
+    let Some(page_json) = page.json_mut() else {
+        return false;
+    };
+    page_json.page_data_replace(&page_data);
+    fsck.found(page, "navi", detail, true);
+    true
+}
+
+fn navi_href_path(page_url: &url::Url, href: &str) -> Option<String> {
+    page_url.join(href).ok().map(|url| url.path().to_string())
+}
+
+fn fsck_moved_to(page: &mut Page, fsck: &mut Fsck) {
+    match page.moved_location() {
+        Ok(None) => (),
+        Ok(Some(location)) => {
+            let path = location.split('#').next().unwrap_or("");
+            if !page_file_exists(page.stor_root(), path) {
+                let detail = format!("moved_to ends at no file: {}", location);
+                fsck.found(page, "moved_to", detail, false);
+            }
+        }
+        Err(e) => fsck.found(page, "moved_to", e, false),
+    }
+}
+
+fn fsck_subsection_link(page: &mut Page, fsck: &mut Fsck) {
+    let Ok(page_url) = super::super::page_url(page) else {
+        return;
+    };
+    let stor_root = page.stor_root().to_string();
+
+    let Some(subsections) = page.json().and_then(|page_json| page_json.subsections()) else {
+        return;
+    };
+
+    let mut missing = vec![];
+    for (id, subsection) in subsections.iter() {
+        let Some(href) = subsection["href"].as_str() else {
+            continue;
+        };
+        if href.is_empty() || href.starts_with('#') {
+            continue;
+        }
+
+        let Ok(href_url) = page_url.join(href) else {
+            continue;
+        };
+        // Not a page on this storage.
+        if href_url.host() != page_url.host() || !href_url.path().contains(".htm") {
+            continue;
+        }
+
+        if !page_file_exists(&stor_root, href_url.path()) {
+            missing.push(format!("subsection {} links to no file: {}", id, href));
+        }
+    }
+
+    for detail in missing {
+        fsck.found(page, "subsection_link", detail, false);
+    }
+}
+
+/// Unreferenced attachments are only reported,
+/// those may be linked from other pages by absolute hrefs.
+fn fsck_attachment(page: &mut Page, fsck: &mut Fsck) {
+    for href in super::super::attachment::attachments_unreferenced(page) {
+        let detail = format!("unreferenced attachment: {}", href);
+        fsck.found(page, "attachment", detail, false);
+    }
+}
+
+/// Return true if fixed.
+fn fsck_subsection_id(page: &mut Page, fsck: &mut Fsck) -> bool {
+    let Some(check) = page.json().and_then(subsection_id_check) else {
+        return false;
+    };
+    let SubsectionIdCheck {
+        key_invalid,
+        duplicated,
+        id_mismatch,
+        child_missing,
+        id_max,
+        id_next_low,
+    } = check;
+
+    let fix = fsck.fix;
+
+    for key in key_invalid {
+        fsck.found(
+            page,
+            "subsection_id",
+            format!("key not a number: {}", key),
+            false,
+        );
+    }
+    for detail in duplicated {
+        fsck.found(page, "subsection_id", detail, fix);
+    }
+    for (_, detail) in id_mismatch.iter() {
+        fsck.found(page, "subsection_id", detail.clone(), fix);
+    }
+    for (key_id, missing) in child_missing.iter() {
+        let detail = format!("subsection {} has no child of {:?}", key_id, missing);
+        fsck.found(page, "subsection_child", detail, fix);
+    }
+    if let Some(detail) = id_next_low.as_ref() {
+        fsck.found(page, "id_next", detail.clone(), fix);
+    }
+
+    let found = !id_mismatch.is_empty() || !child_missing.is_empty() || id_next_low.is_some();
+    if !fix || !found {
+        return false;
+    }
+
+    let Some(value) = page.json_mut().and_then(|page_json| page_json.value_mut()) else {
+        return false;
+    };
+    let subsections = &mut value["data"]["subsection"]["data"];
+
+    // Duplicated ids are fixed as well setting key as its id.
+    for (key_id, _) in id_mismatch {
+        subsections[key_id.to_string().as_str()]["id"] = key_id.into();
+    }
+
+    for (key_id, missing) in child_missing {
+        let subsection = &mut subsections[key_id.to_string().as_str()];
+        let child: Vec<json::JsonValue> = subsection["child"]
+            .members()
+            .filter(|child| page_json::to_usize(child).is_ok_and(|child| !missing.contains(&child)))
+            .cloned()
+            .collect();
+        subsection["child"] = json::JsonValue::Array(child);
+    }
+
+    if id_next_low.is_some() {
+        value["data"]["subsection"]["id"]["id_next"] = (id_max + 1).into();
+    }
+
+    true
+}
+
+/// "parent" of a subsection should be the subsection having it in "child".
+/// Return true if fixed.
+fn fsck_subsection_parent(page: &mut Page, fsck: &mut Fsck) -> bool {
+    // Malformed one was reported by page_fsck_page.
+    let Some(Ok(page_data)) = page.json().map(|page_json| page_json.page_data()) else {
+        return false;
+    };
+    let mismatches = page_data.parent_mismatches();
+    if mismatches.is_empty() {
+        return false;
+    }
+
+    let fix = fsck.fix;
+    let mut page_data = page_data;
+    for (id, parent) in mismatches.iter() {
+        let detail = match page_data.subsection.get_mut(id) {
+            Some(subsection) => {
+                let detail = format!(
+                    "subsection {} has parent {} but is a child of {}",
+                    id, subsection.parent, parent
+                );
+                subsection.parent = *parent;
+                detail
+            }
+            None => continue,
+        };
+        fsck.found(page, "subsection_parent", detail, fix);
+    }
+    if !fix {
+        return false;
+    }
+
+    let Some(page_json) = page.json_mut() else {
+        return false;
+    };
+    page_json.page_data_replace(&page_data);
+    true
+}
+
+struct SubsectionIdCheck {
+    key_invalid: Vec<String>,
+    duplicated: Vec<String>,
+    // (key, detail)
+    id_mismatch: Vec<(usize, String)>,
+    // (key, child ids not exist)
+    child_missing: Vec<(usize, Vec<usize>)>,
+    id_max: usize,
+    id_next_low: Option<String>,
+}
+
+fn subsection_id_check(page_json: &page_json::PageJson) -> Option<SubsectionIdCheck> {
+    let subsections = page_json.subsections()?;
+
+    let mut key_invalid = vec![];
+    let mut id_mismatch = vec![];
+    let mut child_missing = vec![];
+    let mut ids: HashMap<usize, Vec<String>> = HashMap::new();
+    let mut id_max = 0;
+
+    for (key, subsection) in subsections.iter() {
+        let Ok(key_id) = key.parse::<usize>() else {
+            key_invalid.push(key.to_string());
+            continue;
+        };
+        id_max = id_max.max(key_id);
+
+        match page_json::to_usize(&subsection["id"]) {
+            Ok(id) => {
+                ids.entry(id).or_default().push(key.to_string());
+                if id != key_id {
+                    id_mismatch.push((key_id, format!("subsection {} has id {}", key, id)));
+                }
+            }
+            Err(_) => {
+                id_mismatch.push((key_id, format!("subsection {} has no id", key)));
+            }
+        }
+
+        let missing: Vec<usize> = subsection["child"]
+            .members()
+            .filter_map(|child| page_json::to_usize(child).ok())
+            .filter(|child| subsections.get(&child.to_string()).is_none())
+            .collect();
+        if !missing.is_empty() {
+            child_missing.push((key_id, missing));
+        }
+    }
+
+    let mut duplicated: Vec<String> = ids
+        .iter()
+        .filter(|(_, keys)| 1 < keys.len())
+        .map(|(id, keys)| format!("id {} used by subsections {}", id, keys.join(", ")))
+        .collect();
+    duplicated.sort();
+
+    let id_next = page_json.subsection_id_next();
+    let id_next_low = match id_next {
+        Some(id_next) if id_max < id_next => None,
+        _ => Some(format!(
+            "id_next {} is not greater than id {}",
+            id_next.map_or("none".to_string(), |id_next| id_next.to_string()),
+            id_max
+        )),
+    };
+
+    Some(SubsectionIdCheck {
+        key_invalid,
+        duplicated,
+        id_mismatch,
+        child_missing,
+        id_max,
+        id_next_low,
+    })
+}
+
+fn fsck_page_save(page: &mut Page, fsck: &mut Fsck) {
+    if let Err(e) = page.rev_replace_one_up() {
+        error!("{}", e);
+        fsck.found(page, "save", e, false);
+        return;
+    }
+
+    if page.file_save_and_rev().is_err() {
+        let detail = format!("Failed to save: {}", page.file_path());
+        error!("{}", detail);
+        fsck.found(page, "save", detail, false);
+    }
+}
+
+fn page_file_exists(stor_root: &str, page_path: &str) -> bool {
+    let file_path = super::super::file_path(stor_root, page_path);
+    super::super::storage::is_file(std::path::Path::new(&file_path))
+}
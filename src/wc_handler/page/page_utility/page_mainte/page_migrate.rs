@@ -0,0 +1,145 @@
+use super::json_from_dom;
+use super::page_json;
+use super::page_json::migration;
+use super::rev_store;
+use super::Page;
+use tracing::{error, info};
+
+// Rewrite pages to the current version of page json, see page_json/migration.rs.
+// Each migration is saved with a new rev, and the page before migrations is kept
+// as the rev file of its rev, so the page of each version is left.
+
+pub struct MigrateLog {
+    // (page_path, version from, version to)
+    migrated: Vec<(String, String, String)>,
+    already: usize,
+    // (page_path, error)
+    failed: Vec<(String, String)>,
+    // (page_path, error) of page json malformed for page_data, fsck tells more.
+    malformed: Vec<(String, String)>,
+}
+
+impl MigrateLog {
+    fn new() -> MigrateLog {
+        MigrateLog {
+            migrated: vec![],
+            already: 0,
+            failed: vec![],
+            malformed: vec![],
+        }
+    }
+
+    /// {"version": "0.0.5", "migrated": [{"page", "from", "to"}], "already": 3,
+    ///  "failed": [{"page", "error"}], "malformed": [{"page", "error"}]}
+    pub fn to_json(&self) -> json::JsonValue {
+        let migrated: Vec<json::JsonValue> = self
+            .migrated
+            .iter()
+            .map(|(page_path, from, to)| {
+                json::object! {"page": page_path.as_str(), "from": from.as_str(), "to": to.as_str()}
+            })
+            .collect();
+        let errors = |errors: &Vec<(String, String)>| -> Vec<json::JsonValue> {
+            errors
+                .iter()
+                .map(|(page_path, e)| json::object! {"page": page_path.as_str(), "error": e.as_str()})
+                .collect()
+        };
+
+        json::object! {
+            "version": migration::VERSION,
+            "migrated": migrated,
+            "already": self.already,
+            "failed": errors(&self.failed),
+            "malformed": errors(&self.malformed),
+        }
+    }
+}
+
+/// Migrate the page, and pages under it if recursive.
+/// Pages left are not migrated if cancelled() gets true.
+pub fn page_migrate(page: &mut Page, recursive: bool, cancelled: &dyn Fn() -> bool) -> MigrateLog {
+    let stor_root = page.stor_root().to_string();
+    let page_paths = if recursive {
+        super::super::page_tree_paths(&stor_root, page.page_path())
+    } else {
+        vec![page.page_path().to_string()]
+    };
+
+    let mut log = MigrateLog::new();
+    for page_path in page_paths.iter() {
+        if cancelled() {
+            info!("migration cancelled at {}", page_path);
+            break;
+        }
+        match page_migrate_page(&stor_root, page_path) {
+            Ok(None) => log.already += 1,
+            Ok(Some(from)) => {
+                log.migrated
+                    .push((page_path.clone(), from, migration::VERSION.to_string()));
+            }
+            Err(e) => {
+                error!("Failed to migrate {}: {}", page_path, e);
+                log.failed.push((page_path.clone(), e));
+                continue;
+            }
+        }
+        let mut page = Page::new(&stor_root, page_path);
+        if let Some(Err(e)) = page.json().map(|page_json| page_json.page_data()) {
+            log.malformed.push((page_path.clone(), e));
+        }
+    }
+
+    info!(
+        "migrated: {}, already: {}, failed: {}, malformed: {}",
+        log.migrated.len(),
+        log.already,
+        log.failed.len(),
+        log.malformed.len()
+    );
+    log
+}
+
+/// Return the version migrated from, None if the page is the current version.
+fn page_migrate_page(stor_root: &str, page_path: &str) -> Result<Option<String>, String> {
+    // Page json as it is in the file, not migrated on read.
+    let mut page = Page::new(stor_root, page_path);
+    let source = page
+        .source()
+        .cloned()
+        .ok_or(format!("Failed to read {}", page_path))?;
+    let mut value = page
+        .dom()
+        .and_then(|dom| json_from_dom(&dom.document))
+        .ok_or("Failed to get page json".to_string())?;
+
+    let migrations = migration::migrations(&value)?;
+    if migrations.is_empty() {
+        return Ok(None);
+    }
+    let from = migration::version(&value).unwrap_or_default().to_string();
+    let mut rev = page_json::to_usize(&value["data"]["page"]["rev"])
+        .map_err(|_| "Failed to get rev".to_string())?;
+
+    // The page before migrations.
+    if !rev_store::rev_exists(&page, rev) {
+        rev_store::rev_write(&page, rev, &source)?;
+    }
+
+    for migration in migrations.iter() {
+        migration.apply(&mut value)?;
+        rev += 1;
+        value["data"]["page"]["rev"] = rev.into();
+
+        let mut page_migrated = Page::from_json(stor_root, page_path, &value)?;
+        page_migrated
+            .file_save_and_rev()
+            .map_err(|_| format!("Failed to save {} in {}", page_path, migration.to))?;
+        info!(
+            "{} migrated from {} to {}",
+            page_path, migration.from, migration.to
+        );
+    }
+
+    Ok(Some(from))
+}
@@ -0,0 +1,153 @@
+use super::super::html_sanitize;
+use super::Page;
+use tracing::{error, info};
+
+// Sanitize contents drawn as html (see html_sanitize::contents_sanitize) stored
+// before html_sanitize or with an older allowlist.
+// The JS client renders contents of page json as they are in the file,
+// so a page with something removed is saved with a new rev,
+// the page before is left as the rev file of its rev.
+
+pub struct SanitizeLog {
+    // (page_path, rev saved, [{"id", "removed"}])
+    sanitized: Vec<(String, usize, json::JsonValue)>,
+    // (page_path, error)
+    failed: Vec<(String, String)>,
+}
+
+impl SanitizeLog {
+    fn new() -> SanitizeLog {
+        SanitizeLog {
+            sanitized: vec![],
+            failed: vec![],
+        }
+    }
+
+    /// {"sanitized": [{"page", "rev", "subsections": [{"id", "removed"}]}],
+    ///  "failed": [{"page", "error"}]}
+    pub fn to_json(&self) -> json::JsonValue {
+        let sanitized: Vec<json::JsonValue> = self
+            .sanitized
+            .iter()
+            .map(|(page_path, rev, subsections)| {
+                json::object! {"page": page_path.as_str(), "rev": *rev, "subsections": subsections.clone()}
+            })
+            .collect();
+        let failed: Vec<json::JsonValue> = self
+            .failed
+            .iter()
+            .map(|(page_path, e)| json::object! {"page": page_path.as_str(), "error": e.as_str()})
+            .collect();
+
+        json::object! {
+            "sanitized": sanitized,
+            "failed": failed,
+        }
+    }
+}
+
+/// Sanitize the page, and pages under it if recursive.
+/// Pages left are not sanitized if cancelled() gets true.
+pub fn page_sanitize(
+    page: &mut Page,
+    recursive: bool,
+    cancelled: &dyn Fn() -> bool,
+) -> SanitizeLog {
+    let stor_root = page.stor_root().to_string();
+    let page_paths = if recursive {
+        super::super::page_tree_paths(&stor_root, page.page_path())
+    } else {
+        vec![page.page_path().to_string()]
+    };
+
+    let mut log = SanitizeLog::new();
+    for page_path in page_paths.iter() {
+        if cancelled() {
+            info!("sanitize cancelled at {}", page_path);
+            break;
+        }
+        match page_sanitize_page(&stor_root, page_path) {
+            Ok(None) => (),
+            Ok(Some((rev, subsections))) => {
+                log.sanitized.push((page_path.clone(), rev, subsections));
+            }
+            Err(e) => {
+                error!("Failed to sanitize {}: {}", page_path, e);
+                log.failed.push((page_path.clone(), e));
+            }
+        }
+    }
+
+    info!(
+        "sanitized: {}, failed: {}",
+        log.sanitized.len(),
+        log.failed.len()
+    );
+    log
+}
+
+/// Return the rev saved and what removed, None if nothing is removed.
+fn page_sanitize_page(
+    stor_root: &str,
+    page_path: &str,
+) -> Result<Option<(usize, json::JsonValue)>, String> {
+    let mut page = Page::new(stor_root, page_path);
+    let mut value = page
+        .json_value()
+        .cloned()
+        .ok_or(format!("Failed to get page json of {}", page_path))?;
+
+    let subsections = html_sanitize::page_json_sanitize(&mut value);
+    if subsections.is_empty() {
+        return Ok(None);
+    }
+
+    let rev = page.json_replace_save(value)?;
+    info!("{} sanitized in rev {}", page_path, rev);
+    Ok(Some((rev, subsections)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::storage::{self, StorageMem};
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn page_sanitize_stored() {
+        let stor_root = "/mem_page_sanitize";
+        storage::register(stor_root, Arc::new(StorageMem::new(stor_root)));
+        // Stored before html_sanitize, as it is in the page json.
+        let mut page_json = super::super::page_json::page_json_plain();
+        page_json["data"]["subsection"]["data"]["0"]["content"] = json::array![
+            {"type": "html", "value": "<p>a</p><img src=\"x.png\" onerror=\"alert(1)\">"},
+            {"type": "text", "value": "\\<script\\>b"},
+            {"type": "text", "value": "c<script>d</script>"}
+        ];
+        let mut page = Page::from_json(stor_root, "/wc_top.html", &page_json).unwrap();
+        page.file_save_and_rev().unwrap();
+
+        let log = page_sanitize(&mut page, false, &|| false).to_json();
+        assert_eq!(log["sanitized"][0]["page"], "/wc_top.html");
+        assert_eq!(log["sanitized"][0]["rev"], 2);
+        assert_eq!(log["sanitized"][0]["subsections"][0]["id"], "0");
+
+        let mut page = Page::new(stor_root, "/wc_top.html");
+        let contents =
+            page.json_value().unwrap()["data"]["subsection"]["data"]["0"]["content"].clone();
+        let html = contents[0]["value"].as_str().unwrap();
+        assert!(
+            html.contains("<p>a</p>") && !html.contains("onerror"),
+            "{}",
+            html
+        );
+        // Escaped, it is shown as text.
+        assert_eq!(contents[1]["value"], "\\<script\\>b");
+        assert_eq!(contents[2]["value"], "c");
+
+        // Nothing to remove the second time.
+        let log = page_sanitize(&mut page, false, &|| false).to_json();
+        assert!(log["sanitized"].is_empty());
+        storage::unregister(stor_root);
+    }
+}
@@ -394,11 +394,16 @@ fn page_move_subsection_content(
     let mut dest_contents = json::array![];
     for org_content in org_contents {
         // "content" : [ {"type" : "text", "value" : "sample"} ],
-        let mut dest_content = json::object! {};
-        dest_content["type"] = org_content["type"].clone();
+        // Other fields like "lang" of "script" are taken over as they are.
+        let mut dest_content = org_content.clone();
 
         let org_content_value = org_content["value"].as_str().or(Some("")).unwrap();
-        let dest_content_value = page_move_content_href_convert(org_content_value, org_url);
+        let mut dest_content_value = page_move_content_href_convert(org_content_value, org_url);
+        if org_content["type"] == "markdown" {
+            dest_content_value = markdown_href_convert(&dest_content_value, &mut |org_href| {
+                super::href_on(org_url, org_href).map(|(dest_href, _is_child)| dest_href)
+            });
+        }
         dest_content["value"] = dest_content_value.into();
 
         dest_contents.push(dest_content).or_else(|e| {
@@ -416,15 +421,24 @@ fn page_move_subsection_content(
 // fn dest_page_save(stor_root: &str, page_moving: &PageMoving) {
 fn dest_page_save(stor_root: &str, page_moving: &PageMoving) -> Result<(), String> {
     for org_path in page_moving.org_path_list() {
-        let (_org_url, dest_url, dest_json) = match page_moving.get(org_path) {
+        let (org_url, dest_url, dest_json) = match page_moving.get(org_path) {
             Some(v) => v,
             None => {
                 error!("{}", format!("No page2Moving for {}", org_path));
                 continue;
             }
         };
+
+        // Attachments are carried along, hrefs to those are converted in dest_json.
+        let mut dest_json = dest_json.clone();
+        if let Err(e) =
+            super::attachment::attachments_move(stor_root, &org_url, &dest_url, &mut dest_json)
+        {
+            error!("{}", e);
+        }
+
         // let mut dest_page = Page::from_json(stor_root, dest_url.path(), dest_json);
-        let mut dest_page = Page::from_json(stor_root, dest_url.path(), dest_json)?;
+        let mut dest_page = Page::from_json(stor_root, dest_url.path(), &dest_json)?;
         if dest_page.dir_build().is_err() {
             continue;
         }
@@ -464,40 +478,129 @@ fn page_org_page_moved(
     };
 
     let mut org_page = Page::new(stor_root, org_url.path());
-    let org_json = org_page
-        .json_value()
-        .ok_or(format!("Failed to get page_json.data of {}", org_url))?;
-
-    let mut org_json_uped = org_json.clone();
+    let mut org_data = org_page
+        .json()
+        .ok_or(format!("Failed to get page_json.data of {}", org_url))?
+        .page_data()?;
 
     // moved_to
-    org_json_uped["data"]["page"]["moved_to"] = dest_url.as_str().into();
+    org_data.page.moved_to = dest_url.to_string();
 
     // title
-    let title = org_json["data"]["page"]["title"]
-        .as_str()
-        .or(Some(""))
-        .unwrap();
-    let title = format!("Moved({}) to {}", title, dest_url);
+    let title = format!("Moved({}) to {}", org_data.page.title, dest_url);
 
     // navi
-    let navi = match &mut org_json_uped["data"]["navi"] {
-        json::JsonValue::Array(ref mut v) => v,
-        _ => return Err(format!("Failed to vet navi data of : {}", org_url)),
-    };
+    let navi_last = org_data
+        .navi
+        .last_mut()
+        .ok_or(format!("Failed to vet navi data of : {}", org_url))?;
+    navi_last.title = title;
+
+    Ok((org_page, org_data.to_json()))
+}
+
+/// convert href="xxx" in org_content by super::href_on
+fn page_move_content_href_convert(org_content: &str, org_url: &url::Url) -> String {
+    content_href_convert(org_content, &mut |org_href| {
+        super::href_on(org_url, org_href).map(|(dest_href, _is_child)| dest_href)
+    })
+}
 
-    if navi.len() == 0 {
-        return Err(format!("Failed to vet navi data of : {}", org_url));
+/// Return href values of href="xxx" in content.
+pub fn content_hrefs(content: &str) -> Vec<String> {
+    let mut hrefs = vec![];
+    content_href_convert(content, &mut |href| {
+        hrefs.push(href.to_string());
+        None
+    });
+    hrefs
+}
+
+/// Return destinations of links in markdown: [title](xxx), [name]: xxx
+pub fn markdown_hrefs(content: &str) -> Vec<String> {
+    let mut hrefs = vec![];
+    markdown_href_convert(content, &mut |href| {
+        hrefs.push(href.to_string());
+        None
+    });
+    hrefs
+}
+
+/// Convert destinations of links in markdown by convert.
+/// [title](xxx), ![alt](xxx), [title](<xxx>) and reference definitions [name]: xxx
+/// Links in fenced code blocks are left as they are.
+/// If convert returns None, the link is left as it is.
+pub fn markdown_href_convert(
+    org_content: &str,
+    convert: &mut dyn FnMut(&str) -> Option<String>,
+) -> String {
+    let reg_link = regex::Regex::new(
+        r#"(?m)(?P<pre>\]\(\s*|^ {0,3}\[[^\]\n]+\]:[ \t]*)(?P<dest><[^<>\n]*>|[^\s()<>]+)"#,
+    )
+    .unwrap();
+
+    let mut content = String::new();
+    let mut chunk = String::new();
+    let mut fence: Option<String> = None;
+
+    for line in org_content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let line_fence: String = trimmed
+            .chars()
+            .take_while(|c| *c == '`' || *c == '~')
+            .collect();
+
+        match fence.as_ref() {
+            // The end of the fenced code block.
+            Some(open) if line_fence.starts_with(open.as_str()) => {
+                fence = None;
+                content += line;
+            }
+            Some(_) => content += line,
+            // The start of a fenced code block.
+            None if 3 <= line_fence.len() => {
+                content += &markdown_chunk_convert(&reg_link, &chunk, convert);
+                chunk.clear();
+                fence = Some(line_fence);
+                content += line;
+            }
+            None => chunk += line,
+        }
     }
+    content += &markdown_chunk_convert(&reg_link, &chunk, convert);
 
-    let pos_last = navi.len() - 1;
-    navi[pos_last][0] = title.into();
+    content
+}
 
-    Ok((org_page, org_json_uped))
+fn markdown_chunk_convert(
+    reg_link: &regex::Regex,
+    chunk: &str,
+    convert: &mut dyn FnMut(&str) -> Option<String>,
+) -> String {
+    reg_link
+        .replace_all(chunk, |caps: &regex::Captures| {
+            let pre = &caps["pre"];
+            let dest = &caps["dest"];
+            let (org_href, bracket) = match dest.strip_prefix('<').and_then(|v| v.strip_suffix('>'))
+            {
+                Some(v) => (v, true),
+                None => (dest, false),
+            };
+            match convert(org_href) {
+                Some(href) if bracket => format!("{}<{}>", pre, href),
+                Some(href) => format!("{}{}", pre, href),
+                None => caps[0].to_string(),
+            }
+        })
+        .to_string()
 }
 
-/// convert href="xxx" in org_content by super::href_on
-fn page_move_content_href_convert(org_content: &str, org_url: &url::Url) -> String {
+/// Convert href="xxx" in org_content by convert.
+/// If convert returns None, the href="xxx" is left as it is.
+pub fn content_href_convert(
+    org_content: &str,
+    convert: &mut dyn FnMut(&str) -> Option<String>,
+) -> String {
     let mut index: usize = 0;
     let mut content = String::from(org_content);
 
@@ -514,9 +617,9 @@ fn page_move_content_href_convert(org_content: &str, org_url: &url::Url) -> Stri
         }
         let (href_start, href_end, href_value_start, href_value_end) = href_pos.unwrap();
 
-        // Convert href value for moving.
+        // Convert href value.
         let org_href = &content[href_value_start..href_value_end];
-        let op_href_move = super::href_on(org_url, org_href);
+        let op_href_move = convert(org_href);
 
         // Failed to convert href valuye.
         // Leave the href="xxx" as it is.
@@ -526,7 +629,7 @@ fn page_move_content_href_convert(org_content: &str, org_url: &url::Url) -> Stri
             continue;
         }
 
-        let (dest_href, _is_child) = op_href_move.unwrap();
+        let dest_href = op_href_move.unwrap();
 
         // make href="converted_href_value"
         // put a space before "href=".
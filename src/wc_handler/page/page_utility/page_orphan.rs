@@ -0,0 +1,210 @@
+use super::page_json::page_data::Navi;
+use super::Page;
+use std::collections::HashSet;
+use std::path::Path;
+use tracing::{error, info};
+
+/// A page file that no parent subsection links to.
+pub struct Orphan {
+    pub page_path: String,
+    pub title: String,
+    pub rev: Option<usize>,
+}
+
+/// Compare every html file under stor_root with the pages reachable from top_path
+/// and return the files not reachable as orphans.
+pub fn page_orphans(stor_root: &str, top_path: &str) -> Vec<Orphan> {
+    let reachable: HashSet<String> = super::page_tree_paths(stor_root, top_path)
+        .into_iter()
+        .collect();
+
+    let mut orphans = vec![];
+    for page_path in page_files(stor_root) {
+        if reachable.contains(&page_path) {
+            continue;
+        }
+
+        let mut page = Page::new(stor_root, &page_path);
+        let title = page
+            .json()
+            .and_then(|page_json| page_json.page_data().ok())
+            .map(|page_data| page_data.page.title)
+            .unwrap_or_default();
+        let rev = page.rev().ok();
+
+        orphans.push(Orphan {
+            page_path,
+            title,
+            rev,
+        });
+    }
+
+    info!("orphans: {}", orphans.len());
+
+    orphans
+}
+
+pub fn orphans_json(orphans: &[Orphan]) -> json::JsonValue {
+    let mut orphans_json = json::array![];
+    for orphan in orphans {
+        let _ = orphans_json.push(json::object! {
+            "page": orphan.page_path.as_str(),
+            "title": orphan.title.as_str(),
+            "rev": orphan.rev,
+        });
+    }
+    orphans_json
+}
+
+/// Return page paths of html files under stor_root.
+/// Backup files (page.html.rev, page_rev2.html) and garbage are not included.
+/// eg: "/wc_top.html", "/Computing/computing_index.html"
+pub fn page_files(stor_root: &str) -> Vec<String> {
+    let mut page_paths = vec![];
+    page_files_dir(Path::new(stor_root), "", &mut page_paths);
+    page_paths.sort();
+    page_paths
+}
+
+fn page_files_dir(dir: &Path, dir_path: &str, page_paths: &mut Vec<String>) {
+    let paths = match super::storage::list(dir) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Failed to read dir: {:?}, {}", dir, e);
+            return;
+        }
+    };
+
+    let reg_rev = regex::Regex::new(r#"_rev[0-9]+\.html?$"#).unwrap();
+
+    for path in paths {
+        let Some(file_name) = path.file_name().and_then(|file_name| file_name.to_str()) else {
+            continue;
+        };
+        let file_name = file_name.to_string();
+        // hidden files and dirs
+        if file_name.starts_with('.') {
+            continue;
+        }
+
+        let page_path = dir_path.to_string() + "/" + &file_name;
+
+        if super::storage::metadata(&path).is_ok_and(|metadata| metadata.is_dir) {
+            // garbage of backup clean
+            if dir_path.is_empty() && file_name == "gabage" {
+                continue;
+            }
+            // attachments of a page, html uploaded is not a page.
+            if super::attachment::is_files_dir(&path) {
+                continue;
+            }
+            page_files_dir(&path, &page_path, page_paths);
+            continue;
+        }
+
+        if !(file_name.ends_with(".html") || file_name.ends_with(".htm")) {
+            continue;
+        }
+
+        if reg_rev.is_match(&file_name) {
+            continue;
+        }
+
+        page_paths.push(page_path);
+    }
+}
+
+/// Attach orphan_url under parent_page.
+/// Create a subsection linking to the orphan in parent_page
+/// and rebuild navi of the orphan as a child of parent_page.
+/// title: title of the subsection, title of the orphan if None.
+/// orphan_url should be in the directory of parent_page or under it
+/// so that it can be found by page_children_url.
+pub fn page_orphan_attach(
+    parent_page: &mut Page,
+    orphan_url: &url::Url,
+    title: Option<&str>,
+) -> Result<(), String> {
+    let parent_url = super::page_url(parent_page)?;
+    let orphan_url = parent_url
+        .join(orphan_url.path())
+        .or(Err(format!("Failed to join orphan_url: {}", orphan_url)))?;
+
+    let (href, is_child) = super::href_on(&parent_url, orphan_url.as_str())
+        .ok_or(format!("Failed to get href of: {}", orphan_url))?;
+    if !is_child {
+        return Err(format!(
+            "{} is not under the directory of {}",
+            orphan_url.path(),
+            parent_page.page_path()
+        ));
+    }
+
+    let mut orphan_page = Page::new(parent_page.stor_root(), orphan_url.path());
+    let mut orphan_data = orphan_page
+        .json()
+        .ok_or(format!("Failed to get page_json of {}", orphan_url.path()))?
+        .page_data()?;
+    let orphan_title = orphan_data.page.title.clone();
+
+    if parent_page.json().is_none() {
+        return Err(format!(
+            "Failed to get page_json of {}",
+            parent_page.page_path()
+        ));
+    }
+
+    let already = page_children_paths(parent_page);
+    if already.contains(orphan_url.path()) {
+        return Err(format!(
+            "{} is already linked from {}",
+            orphan_url.path(),
+            parent_page.page_path()
+        ));
+    }
+
+    // navi of the orphan as page_child_new does
+    let mut navi = super::child_navi(parent_page, &parent_url, &orphan_url)
+        .or(Err("Failed to get navi".to_string()))?;
+    navi.push(Navi {
+        title: orphan_title.clone(),
+        href: "".to_string(),
+    });
+
+    // subsection linking to the orphan
+    let title = title
+        .map(|title| title.trim())
+        .filter(|title| !title.is_empty())
+        .unwrap_or(orphan_title.as_str());
+    let parent_json = parent_page
+        .json_mut()
+        .ok_or("Failed to get page_json.".to_string())?;
+    let mut subsection = parent_json
+        .subsection_new(&0)
+        .ok_or("Failed to create subsection".to_string())?;
+    subsection.title_set(title);
+    subsection.href_set(&href);
+    super::page_save_rev_up(parent_page)?;
+
+    orphan_data.navi = navi;
+    orphan_page
+        .json_mut()
+        .ok_or("Failed to get page_json.".to_string())?
+        .page_data_replace(&orphan_data);
+    super::page_save_rev_up(&mut orphan_page)?;
+
+    info!(
+        "orphan {} attached under {}",
+        orphan_url.path(),
+        parent_page.page_path()
+    );
+
+    Ok(())
+}
+
+fn page_children_paths(page: &mut Page) -> HashSet<String> {
+    super::page_children_url(page)
+        .iter()
+        .map(|url| url.path().to_string())
+        .collect()
+}
@@ -0,0 +1,326 @@
+use super::page_json;
+use super::subsection_move::{rev_check, subsections_href_convert, subtree_copy, Anchors, IdMap};
+use super::Page;
+use tracing::{error, info};
+
+// Promote a subsection to its own child page, and demote a child page back.
+//
+// subsection_promote on the page the subsection is in:
+// {"rev": 3, "id": 5, "href": "sub/sub.html", "title": "Sub"}
+// The subsection with its descendants goes to the new child page,
+// the subsection stays as a link to the child page without contents.
+// title is of the subsection if not given.
+// res: {"res": "subsection_promoted", "href": "sub/sub.html", "ids": {"5": 1}, "rev": 4}
+//
+// page_demote on the parent page:
+// {"rev": 4, "id": 5, "child_rev": 2, "delete": false}
+// id is of the subsection linking to the child page.
+// Subsections of the child page are put under the subsection,
+// the subsection links to those with an anchor like "#sub".
+// The child page is left with moved_to to the anchor, or deleted if "delete" is true.
+// res: {"res": "page_demoted", "href": "#sub", "ids": {"1": 9}, "rev": 5}
+//
+// Links to the subsections moved in the parent page are converted as well,
+// and attachments those refer to are copied to the page they go.
+
+/// Promote the subsection to a child page.
+pub fn subsection_promote(
+    page: &mut Page,
+    page_url: &url::Url,
+    json_post: &json::JsonValue,
+) -> Result<json::JsonValue, String> {
+    let id = page_json::to_usize(&json_post["id"]).map_err(|_| "Failed to get id".to_string())?;
+    if id == 0 {
+        return Err("The root subsection can not be promoted".to_string());
+    }
+    let href = json_post["href"]
+        .as_str()
+        .ok_or("Failed to get href".to_string())?;
+    rev_check(page, &json_post["rev"])?;
+
+    let mut page_data = page
+        .json()
+        .ok_or(format!("Failed to get page_json: {}", page_url.path()))?
+        .page_data()
+        .map_err(|e| format!("{}: {}", page_url.path(), e))?;
+    let title = match json_post["title"].as_str() {
+        Some(title) => title.to_string(),
+        None => page_data
+            .subsection
+            .get(&id)
+            .ok_or(format!("No subsection of id {}", id))?
+            .title
+            .clone(),
+    };
+
+    let mut child_page = super::page_child_new(page, &title, href)
+        .map_err(|_| format!("Failed to create a child page on {}", href))?;
+    let child_url = page_url
+        .join(href)
+        .map_err(|e| format!("Failed to join href: {}, {}", href, e))?;
+    // href from the parent page in the form of this system.
+    let link = super::href_on(page_url, child_url.as_str())
+        .map(|(link, _is_child)| link)
+        .ok_or(format!("Failed to get href of {}", child_url.path()))?;
+
+    let mut child_data = child_page
+        .json()
+        .ok_or(format!("Failed to get page_json: {}", child_url.path()))?
+        .page_data()
+        .map_err(|e| format!("{}: {}", child_url.path(), e))?;
+    let (id_map, anchors) = subtree_copy(
+        &page_data,
+        page_url,
+        &id,
+        &mut child_data,
+        &child_url,
+        &0,
+        None,
+    )?;
+    // Attachments the subsections refer to are copied along.
+    let ids: Vec<usize> = id_map.values().copied().collect();
+    if let Err(e) =
+        super::attachment::attachments_carry(page_url, &mut child_page, &mut child_data, &ids)
+    {
+        error!("{}", e);
+    }
+    child_page
+        .json_mut()
+        .ok_or(format!("Failed to get page_json: {}", child_url.path()))?
+        .page_data_replace(&child_data);
+
+    // The subsection stays as a link, its descendants go.
+    let ids = page_data.subsection.descendants(&id);
+    page_data
+        .subsection
+        .data
+        .retain(|subsection| subsection.id == id || !ids.contains(&subsection.id));
+    {
+        let subsection = page_data
+            .subsection
+            .get_mut(&id)
+            .ok_or(format!("No subsection of id {}", id))?;
+        subsection.href = link.clone();
+        subsection.content = vec![];
+        subsection.child = vec![];
+    }
+
+    // "#abc" moved to the child page to "sub/sub.html#abc"
+    subsections_href_convert(&mut page_data, &mut |org_href: &str| {
+        let href_url = page_url.join(org_href).ok()?;
+        if href_url.path() != page_url.path() {
+            return None;
+        }
+        let anchor = anchors.get(&format!("#{}", href_url.fragment()?))?;
+        Some(format!("{}{}", link, anchor))
+    });
+    page.json_mut()
+        .ok_or(format!("Failed to get page_json: {}", page_url.path()))?
+        .page_data_replace(&page_data);
+
+    child_page.dir_build()?;
+    child_page
+        .file_save_and_rev()
+        .map_err(|_| format!("Failed to save: {}", child_page.file_path()))?;
+    // The page may be changed by others while the promotion is built.
+    if let Err(e) = super::pages_save_rev_checked(&mut [page]) {
+        error!("{}", e);
+        // Not to leave the new page having the same subsections.
+        let child_file = child_page.file_path();
+        if let Err(e) = super::storage::remove(std::path::Path::new(&child_file)) {
+            error!("Failed to remove {}: {}", child_file, e);
+        }
+        super::page_search::index_update(&mut Page::new(page.stor_root(), child_url.path()));
+        super::page_backlinks::index_update(&mut Page::new(page.stor_root(), child_url.path()));
+        return Err(format!("{}, the promotion was undone", e));
+    }
+
+    let rev = page.rev().unwrap_or_default();
+    info!(
+        "subsection {} of {} promoted to {}",
+        id,
+        page_url.path(),
+        child_url.path()
+    );
+
+    Ok(json::object! {
+        "res": "subsection_promoted",
+        "href": link,
+        "ids": ids_json(&id_map),
+        "rev": rev,
+    })
+}
+
+/// Demote the child page linked from the subsection into the page.
+pub fn page_demote(
+    page: &mut Page,
+    page_url: &url::Url,
+    json_post: &json::JsonValue,
+) -> Result<json::JsonValue, String> {
+    let stor_root = page.stor_root().to_string();
+    let id = page_json::to_usize(&json_post["id"]).map_err(|_| "Failed to get id".to_string())?;
+    let delete = json_post["delete"].as_bool().unwrap_or(false);
+    rev_check(page, &json_post["rev"])?;
+
+    let mut page_data = page
+        .json()
+        .ok_or(format!("Failed to get page_json: {}", page_url.path()))?
+        .page_data()
+        .map_err(|e| format!("{}: {}", page_url.path(), e))?;
+    let link = page_data
+        .subsection
+        .get(&id)
+        .map(|subsection| subsection.href.clone())
+        .filter(|href| !href.is_empty())
+        .ok_or(format!("No href of subsection {}", id))?;
+    let child_url = page_url
+        .join(&link)
+        .map_err(|e| format!("Failed to join href: {}, {}", link, e))?;
+    match super::href_on(page_url, &link) {
+        Some((_, true)) if child_url.fragment().is_none() => (),
+        _ => return Err(format!("Not a link to a child page: {}", link)),
+    }
+
+    let mut child_page = Page::new(&stor_root, child_url.path());
+    if !json_post["child_rev"].is_null() {
+        rev_check(&mut child_page, &json_post["child_rev"])?;
+    }
+    // Grandchildren follow the moved_to stub but not a deleted page.
+    if delete && !super::page_children_url(&mut child_page).is_empty() {
+        return Err(format!(
+            "{} has child pages, demote it without delete",
+            child_url.path()
+        ));
+    }
+
+    let child_json = child_page
+        .json()
+        .ok_or(format!("Failed to get page_json: {}", child_url.path()))?;
+    if child_json.moved_to().is_some() {
+        return Err(format!("The page was moved: {}", child_url.path()));
+    }
+    let child_data = child_json
+        .page_data()
+        .map_err(|e| format!("{}: {}", child_url.path(), e))?;
+    let top_ids: Vec<usize> = child_data
+        .subsection
+        .get(&0)
+        .map(|subsection| subsection.child.clone())
+        .unwrap_or_default();
+
+    let mut id_map = IdMap::new();
+    let mut anchors = Anchors::new();
+    for top_id in top_ids.iter() {
+        let (ids, anchors_top) = subtree_copy(
+            &child_data,
+            &child_url,
+            top_id,
+            &mut page_data,
+            page_url,
+            &id,
+            None,
+        )?;
+        id_map.extend(ids);
+        anchors.extend(anchors_top);
+    }
+    let ids: Vec<usize> = id_map.values().copied().collect();
+    if let Err(e) = super::attachment::attachments_carry(&child_url, page, &mut page_data, &ids) {
+        error!("{}", e);
+    }
+
+    // Anchor of the subsection in place of the link, "sub/sub.html" to "#sub"
+    // not to be the same as those moved in.
+    let stem = child_url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .map(|name| name.split('.').next().unwrap_or(name).to_string())
+        .unwrap_or_default();
+    let anchor = format!("#{}", stem);
+    let anchor_used = page_data
+        .subsection
+        .data
+        .iter()
+        .any(|subsection| subsection.href == anchor);
+    let anchor = if anchor_used {
+        format!("{}-{}", anchor, id)
+    } else {
+        anchor
+    };
+
+    page_data
+        .subsection
+        .get_mut(&id)
+        .ok_or(format!("No subsection of id {}", id))?
+        .href = anchor.clone();
+
+    // "sub/sub.html#abc" to "#abc" and "sub/sub.html" to the anchor.
+    subsections_href_convert(&mut page_data, &mut |org_href: &str| {
+        let href_url = page_url.join(org_href).ok()?;
+        if href_url.path() != child_url.path() {
+            return None;
+        }
+        match href_url.fragment() {
+            Some(fragment) => {
+                let fragment = format!("#{}", fragment);
+                Some(anchors.get(&fragment).cloned().unwrap_or(fragment))
+            }
+            None => Some(anchor.clone()),
+        }
+    });
+    page.json_mut()
+        .ok_or(format!("Failed to get page_json: {}", page_url.path()))?
+        .page_data_replace(&page_data);
+
+    super::pages_save_rev_checked(&mut [page])?;
+
+    if delete {
+        let child_file = child_page.file_path();
+        super::storage::remove(std::path::Path::new(&child_file))
+            .map_err(|e| format!("Failed to remove {}: {}", child_file, e))?;
+        // The page removed is removed from indexes.
+        super::page_search::index_update(&mut Page::new(&stor_root, child_url.path()));
+        super::page_backlinks::index_update(&mut Page::new(&stor_root, child_url.path()));
+        info!("{} deleted", child_file);
+    } else {
+        let child_json = child_page
+            .json_mut()
+            .ok_or(format!("Failed to get page_json: {}", child_url.path()))?;
+        let mut child_data = child_json.page_data()?;
+        child_data.page.moved_to = format!("{}{}", page_url.path(), anchor);
+        // Attachments the subsections refer to are copied along.
+        let ids: Vec<usize> = id_map.values().copied().collect();
+        if let Err(e) =
+            super::attachment::attachments_carry(page_url, &mut child_page, &mut child_data, &ids)
+        {
+            error!("{}", e);
+        }
+        child_page
+            .json_mut()
+            .ok_or(format!("Failed to get page_json: {}", child_url.path()))?
+            .page_data_replace(&child_data);
+        super::pages_save_rev_checked(&mut [&mut child_page])?;
+    }
+
+    let rev = page.rev().unwrap_or_default();
+    info!(
+        "{} demoted to subsection {} of {}",
+        child_url.path(),
+        id,
+        page_url.path()
+    );
+
+    Ok(json::object! {
+        "res": "page_demoted",
+        "href": anchor,
+        "ids": ids_json(&id_map),
+        "rev": rev,
+    })
+}
+
+fn ids_json(id_map: &IdMap) -> json::JsonValue {
+    let mut ids = json::object! {};
+    for (id_org, id_new) in id_map.iter() {
+        ids[id_org.to_string().as_str()] = (*id_new).into();
+    }
+    ids
+}
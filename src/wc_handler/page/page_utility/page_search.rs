@@ -0,0 +1,388 @@
+use super::index_file::IndexFile;
+use super::page_json;
+use super::page_json::page_data::{Content, ContentType};
+use super::Page;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+use tracing::info;
+
+// Search index is saved in stor_root as json.
+//
+// {
+//   "pages" : {
+//     "/page.html" : {
+//       "title" : "page title",
+//       "rev" : 3,
+//       "docs" : [
+//         // href "" is for the page title.
+//         { "href" : "", "title" : "page title", "text" : "", "tf" : {"page": 3, "title": 3} },
+//         { "href" : "#subsection1", "title" : "subsection title", "text" : "contents as plain text", "tf" : {"token": 1} }
+//       ]
+//     }
+//   }
+// }
+//
+// Each doc is a subsection and tf is the count of each token in it.
+// Tokens of titles are weighted by TITLE_WEIGHT.
+// The index is updated page by page on saving the page, see index_file.rs.
+// Private pages are not indexed as they are not exported, a page made private
+// is removed from the index on saving.
+
+const INDEX_FILE: &str = "/wc_search_index.json";
+
+/// Weight of tokens in titles
+const TITLE_WEIGHT: usize = 3;
+
+/// Number of charactors around the matched word in a snippet.
+const SNIPPET_BEFORE: usize = 30;
+const SNIPPET_AFTER: usize = 70;
+
+pub const SEARCH_LIMIT: usize = 50;
+
+static INDEX: IndexFile = IndexFile::new(INDEX_FILE);
+
+/// Update the search index with the page.
+/// If the page was moved, it is removed from the index.
+pub fn index_update(page: &mut Page) {
+    let page_path = page.page_path().to_string();
+    let stor_root = page.stor_root().to_string();
+
+    let page_index = match page.json() {
+        Some(page_json) if page_json.moved_to().is_none() => page_index(page_json),
+        _ => None,
+    };
+
+    INDEX.update(&stor_root, |index| match page_index {
+        Some(page_index) if index["pages"][page_path.as_str()] != page_index => {
+            index["pages"][page_path.as_str()] = page_index;
+            true
+        }
+        Some(_) => false,
+        None => !index["pages"].remove(&page_path).is_null(),
+    });
+}
+
+/// Rebuild the search index from pages reachable from top_path.
+/// Return number of pages indexed.
+pub fn index_rebuild(stor_root: &str, top_path: &str) -> Result<usize, String> {
+    let mut pages = json::object! {};
+
+    for page_path in super::page_tree_paths(stor_root, top_path) {
+        let mut page = Page::new(stor_root, &page_path);
+        let page_index = match page.json() {
+            Some(page_json) if page_json.moved_to().is_none() => page_index(page_json),
+            _ => None,
+        };
+        if let Some(page_index) = page_index {
+            pages[page_path.as_str()] = page_index;
+        }
+    }
+
+    let num = pages.len();
+    let index = json::object! {"pages": pages};
+
+    INDEX.replace(stor_root, index)?;
+
+    info!("search index rebuilt: {} pages", num);
+    Ok(num)
+}
+
+fn page_index(page_json: &page_json::PageJson) -> Option<json::JsonValue> {
+    let page_data = page_json.page_data().ok()?;
+    if page_data.page.private == Some(true) {
+        return None;
+    }
+    let title = page_data.page.title.as_str();
+
+    let mut docs = json::array![];
+    let _ = docs.push(doc_index("", title, ""));
+
+    for subsection in page_data.subsection.data.iter() {
+        // 0 is the root of subsections, not a content.
+        if subsection.id == 0 {
+            continue;
+        }
+        let text = contents_text(&subsection.content);
+        let _ = docs.push(doc_index(&subsection.href, &subsection.title, &text));
+    }
+
+    Some(json::object! {
+        "title": title,
+        "rev": page_data.page.rev,
+        "docs": docs,
+    })
+}
+
+fn doc_index(href: &str, title: &str, text: &str) -> json::JsonValue {
+    let mut tf: HashMap<String, usize> = HashMap::new();
+    for token in tokens(title) {
+        *tf.entry(token).or_default() += TITLE_WEIGHT;
+    }
+    for token in tokens(text) {
+        *tf.entry(token).or_default() += 1;
+    }
+
+    let mut tf_json = json::object! {};
+    for (token, count) in tf {
+        tf_json[token.as_str()] = count.into();
+    }
+
+    json::object! {
+        "href": href,
+        "title": title,
+        "text": text,
+        "tf": tf_json,
+    }
+}
+
+/// Contents of a subsection as a plain text.
+fn contents_text(contents: &[Content]) -> String {
+    let mut text = String::new();
+    for content in contents {
+        let value = content.value.as_str();
+        let value = match content.content_type {
+            ContentType::Script => text_unescape(value),
+            ContentType::Markdown => html_strip(&super::page_dom_from_json::markdown_html(value)),
+            ContentType::Table => super::content_table::Table::from_content(content).to_text(),
+            // "html", "text" may contain html elements.
+            _ => html_strip(&text_unescape(value)),
+        };
+        text = text + &value + "\n";
+    }
+    whitespace_collapse(&text)
+}
+
+/// \< to <, \> to >
+fn text_unescape(value: &str) -> String {
+    value.replace("\\<", "<").replace("\\>", ">")
+}
+
+fn html_strip(value: &str) -> String {
+    static TAG: OnceLock<regex::Regex> = OnceLock::new();
+    let reg = TAG.get_or_init(|| regex::Regex::new(r#"<[^<>]*>"#).unwrap());
+    let value = reg.replace_all(value, " ");
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+fn whitespace_collapse(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// CJK charactors are not separated by spaces,
+/// they are tokenized as n-gram (bigram).
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}' // Hiragana, Katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}' // Hangul
+        | '\u{F900}'..='\u{FAFF}' // CJK Compatibility Ideographs
+        | '\u{FF66}'..='\u{FF9F}' // Halfwidth Katakana
+    )
+}
+
+/// Split text into tokens in lowercase.
+/// Words of alphabets and numbers are tokens,
+/// runs of CJK charactors become bigrams, a single CJK charactor is a token as it is.
+/// eg: "Rust の検索" : "rust", "の検", "検索"
+pub fn tokens(text: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut word = String::new();
+    let mut cjk: Vec<char> = vec![];
+
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if is_cjk(c) {
+            word_push(&mut tokens, &mut word);
+            cjk.push(c);
+        } else if c.is_alphanumeric() {
+            cjk_push(&mut tokens, &mut cjk);
+            word.push(c);
+        } else {
+            word_push(&mut tokens, &mut word);
+            cjk_push(&mut tokens, &mut cjk);
+        }
+    }
+    word_push(&mut tokens, &mut word);
+    cjk_push(&mut tokens, &mut cjk);
+
+    tokens
+}
+
+fn word_push(tokens: &mut Vec<String>, word: &mut String) {
+    if !word.is_empty() {
+        tokens.push(std::mem::take(word));
+    }
+}
+
+fn cjk_push(tokens: &mut Vec<String>, cjk: &mut Vec<char>) {
+    if cjk.len() == 1 {
+        tokens.push(cjk[0].to_string());
+    }
+    for bigram in cjk.windows(2) {
+        tokens.push(bigram.iter().collect());
+    }
+    cjk.clear();
+}
+
+/// A subsection matched with a query.
+pub struct Hit {
+    page_path: String,
+    page_title: String,
+    href: String,
+    title: String,
+    snippet: String,
+    score: f64,
+}
+
+/// Search the query in the index and return hits ranked by score.
+/// All tokens of the query should be in a subsection.
+pub fn search(stor_root: &str, query: &str, limit: usize) -> Vec<Hit> {
+    let query_tokens: Vec<String> = {
+        let mut handled = HashSet::new();
+        tokens(query)
+            .into_iter()
+            .filter(|token| handled.insert(token.clone()))
+            .collect()
+    };
+    if query_tokens.is_empty() {
+        return vec![];
+    }
+
+    let index = INDEX.read(stor_root);
+
+    // df: number of docs that contains the token
+    let mut docs_num = 0;
+    let mut df: HashMap<&str, usize> = HashMap::new();
+    for (_, page_index) in index["pages"].entries() {
+        for doc in page_index["docs"].members() {
+            docs_num += 1;
+            for token in query_tokens.iter() {
+                if !doc["tf"][token.as_str()].is_null() {
+                    *df.entry(token.as_str()).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    let mut hits = vec![];
+    for (page_path, page_index) in index["pages"].entries() {
+        for doc in page_index["docs"].members() {
+            let Some(score) = doc_score(doc, &query_tokens, &df, docs_num) else {
+                continue;
+            };
+            let text = doc["text"].as_str().unwrap_or("");
+            hits.push(Hit {
+                page_path: page_path.to_string(),
+                page_title: page_index["title"].as_str().unwrap_or("").to_string(),
+                href: doc["href"].as_str().unwrap_or("").to_string(),
+                title: doc["title"].as_str().unwrap_or("").to_string(),
+                snippet: snippet(text, query),
+                score,
+            });
+        }
+    }
+
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit);
+    hits
+}
+
+/// Return None if any of query_tokens is not in the doc.
+fn doc_score(
+    doc: &json::JsonValue,
+    query_tokens: &[String],
+    df: &HashMap<&str, usize>,
+    docs_num: usize,
+) -> Option<f64> {
+    let mut score = 0.0;
+    for token in query_tokens {
+        let tf = doc["tf"][token.as_str()].as_usize()?;
+        let df = *df.get(token.as_str())?;
+        let idf = (1.0 + docs_num as f64 / df as f64).ln();
+        score += tf as f64 * idf;
+    }
+    Some(score)
+}
+
+/// Cut text around the first word of the query found in the text.
+fn snippet(text: &str, query: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let chars_lower: Vec<char> = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+
+    let found = query
+        .split_whitespace()
+        .map(|word| word.to_lowercase().chars().collect::<Vec<char>>())
+        .filter(|word| !word.is_empty())
+        .find_map(|word| {
+            chars_lower
+                .windows(word.len())
+                .position(|window| window == word.as_slice())
+                .map(|start| (start, start + word.len()))
+        });
+
+    let (start, end) = match found {
+        Some((start, end)) => (start.saturating_sub(SNIPPET_BEFORE), end + SNIPPET_AFTER),
+        None => (0, SNIPPET_BEFORE + SNIPPET_AFTER),
+    };
+    let end = end.min(chars.len());
+
+    let mut snippet: String = chars[start..end].iter().collect();
+    if 0 < start {
+        snippet.insert_str(0, "...");
+    }
+    if end < chars.len() {
+        snippet.push_str("...");
+    }
+    snippet
+}
+
+pub fn hits_json(hits: &[Hit]) -> json::JsonValue {
+    let mut hits_json = json::array![];
+    for hit in hits {
+        let _ = hits_json.push(json::object! {
+            "page": hit.page_path.as_str(),
+            "page_title": hit.page_title.as_str(),
+            "href": hit.href.as_str(),
+            "title": hit.title.as_str(),
+            "snippet": hit.snippet.as_str(),
+            "score": hit.score,
+        });
+    }
+    hits_json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::storage::{self, StorageMem};
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn private_not_indexed() {
+        let stor_root = "/mem_search_private";
+        storage::register(stor_root, Arc::new(StorageMem::new(stor_root)));
+        let mut page_json = page_json::page_json_plain();
+        page_json["data"]["page"]["title"] = "secret plans".into();
+        let mut page = Page::from_json(stor_root, "/wc_top.html", &page_json).unwrap();
+        page.file_save_and_rev().unwrap();
+        assert_eq!(search(stor_root, "secret", SEARCH_LIMIT).len(), 1);
+
+        page_json["data"]["page"]["private"] = true.into();
+        page_json["data"]["page"]["rev"] = 2.into();
+        let mut page = Page::from_json(stor_root, "/wc_top.html", &page_json).unwrap();
+        page.file_save_and_rev().unwrap();
+        assert!(search(stor_root, "secret", SEARCH_LIMIT).is_empty());
+
+        assert_eq!(index_rebuild(stor_root, "/wc_top.html"), Ok(0));
+        assert!(search(stor_root, "secret", SEARCH_LIMIT).is_empty());
+        storage::unregister(stor_root);
+    }
+}
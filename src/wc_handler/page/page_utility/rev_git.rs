@@ -0,0 +1,300 @@
+use super::Page;
+use std::cell::RefCell;
+use std::process::Command;
+use std::sync::Mutex;
+use tracing::{error, info};
+
+// Revs in a git repository in stor_root, "rev_store": "git" of rev_store.rs.
+//
+// A page saved is committed instead of writing page.html.N.
+// Pages saved in an operation, a POST request or a mainte job, are one commit
+// when the operation ends. The commit message tells the pages and revs:
+//   json_save /notes/notes.html rev 12
+// or for more pages:
+//   page_move /a.html and 2 more
+//
+//   /a.html rev 5
+//   /b/b.html rev 3
+//   /b.html rev 8
+// An original backup of page_form_update is committed at once, not to be
+// merged into the page converted.
+//
+// Revs are listed and read from the history of the page with the messages.
+// The repository is stor_root/.git, made by git init on start if not exists.
+// Git runs on it with --git-dir and --work-tree, not on a repository
+// stor_root is in, and commits only the pages of the operation.
+
+/// Git commands on a repository are one at a time, for the index.lock.
+static GIT_LOCK: Mutex<()> = Mutex::new(());
+
+struct Operation {
+    stor_root: String,
+    op: String,
+    depth: usize,
+    // (page_path, rev)
+    pages: Vec<(String, usize)>,
+}
+
+thread_local! {
+    static OPERATION: RefCell<Option<Operation>> = const { RefCell::new(None) };
+}
+
+/// Pages saved until this is dropped are one commit.
+/// An operation in an operation is merged into the outer one.
+pub struct GitOperation {
+    // Nothing to commit if git is not the store.
+    active: bool,
+}
+
+/// Begin an operation, op: "json_save", "page_new", "page_move", "mainte" or others.
+pub fn operation(stor_root: &str, op: &str) -> GitOperation {
+    if !super::rev_store::is_git() {
+        return GitOperation { active: false };
+    }
+    OPERATION.with(|operation| {
+        let mut operation = operation.borrow_mut();
+        match operation.as_mut() {
+            Some(operation) => operation.depth += 1,
+            None => {
+                *operation = Some(Operation {
+                    stor_root: stor_root.to_string(),
+                    op: op.to_string(),
+                    depth: 1,
+                    pages: vec![],
+                })
+            }
+        }
+    });
+    GitOperation { active: true }
+}
+
+impl Drop for GitOperation {
+    fn drop(&mut self) {
+        if !self.active {
+            return;
+        }
+        let operation = OPERATION.with(|operation| {
+            let mut operation = operation.borrow_mut();
+            let depth = operation.as_mut().map(|operation| {
+                operation.depth -= 1;
+                operation.depth
+            });
+            match depth {
+                Some(0) => operation.take(),
+                _ => None,
+            }
+        });
+        if let Some(operation) = operation {
+            if operation.pages.is_empty() {
+                return;
+            }
+            if let Err(e) = commit(&operation.stor_root, &operation.op, &operation.pages) {
+                error!("{}", e);
+            }
+        }
+    }
+}
+
+/// The page was saved with the rev.
+/// It is committed when the operation ends, or at once if not in an operation.
+pub fn page_saved(page: &Page, rev: usize) -> Result<String, String> {
+    let page_path = page.page_path().to_string();
+    let pending = OPERATION.with(|operation| match operation.borrow_mut().as_mut() {
+        Some(operation) => {
+            operation.pages.retain(|(path, _)| path != &page_path);
+            operation.pages.push((page_path.clone(), rev));
+            true
+        }
+        None => false,
+    });
+    if pending {
+        return Ok(format!("{} rev {} to commit", page_path, rev));
+    }
+    commit(page.stor_root(), "save", &[(page_path, rev)])
+}
+
+/// Commit the page file as it is now as the rev, for an original backup.
+pub fn backup_commit(page: &Page, rev: usize) -> Result<String, String> {
+    commit(
+        page.stor_root(),
+        "backup",
+        &[(page.page_path().to_string(), rev)],
+    )
+}
+
+fn git(stor_root: &str, args: &[&str]) -> Result<std::process::Output, String> {
+    Command::new("git")
+        .arg("-C")
+        .arg(stor_root)
+        .args(["--git-dir=.git", "--work-tree=."])
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git {:?}: {}", args, e))
+}
+
+/// Run git and return stdout, Err if git failed.
+fn git_ok(stor_root: &str, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = git(stor_root, args)?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+fn git_lock() -> std::sync::MutexGuard<'static, ()> {
+    GIT_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// git init stor_root if stor_root/.git is not a repository.
+pub fn repo_init(stor_root: &str) -> Result<(), String> {
+    let _lock = git_lock();
+    if git_ok(stor_root, &["rev-parse", "--git-dir"]).is_ok() {
+        return Ok(());
+    }
+    git_ok(stor_root, &["init", "--quiet"])?;
+    // Commits need a name, wc_note if the user has not any.
+    if git_ok(stor_root, &["config", "user.name"]).is_err() {
+        git_ok(stor_root, &["config", "user.name", "wc_note"])?;
+        git_ok(stor_root, &["config", "user.email", "wc_note@localhost"])?;
+    }
+    info!("git init: {}", stor_root);
+    Ok(())
+}
+
+/// "/notes/notes.html" to "notes/notes.html"
+fn path_in_repo(page_path: &str) -> &str {
+    page_path.trim_start_matches('/')
+}
+
+fn commit_message(op: &str, pages: &[(String, usize)]) -> String {
+    match pages {
+        [(page_path, rev)] => format!("{} {} rev {}", op, page_path, rev),
+        _ => {
+            let mut message = format!("{} {} and {} more\n", op, pages[0].0, pages.len() - 1);
+            for (page_path, rev) in pages.iter() {
+                message += &format!("\n{} rev {}", page_path, rev);
+            }
+            message
+        }
+    }
+}
+
+/// args -- paths
+fn with_paths<'a>(args: &[&'a str], paths: &[&'a str]) -> Vec<&'a str> {
+    [args, &["--"], paths].concat()
+}
+
+fn commit(stor_root: &str, op: &str, pages: &[(String, usize)]) -> Result<String, String> {
+    let _lock = git_lock();
+    // Other files staged by the user are not committed.
+    let paths: Vec<&str> = pages
+        .iter()
+        .map(|(page_path, _)| path_in_repo(page_path))
+        .collect();
+
+    git_ok(stor_root, &with_paths(&["add", "--all"], &paths))?;
+
+    // Nothing changed, the rev is the one committed before.
+    let unchanged = git(
+        stor_root,
+        &with_paths(&["diff", "--cached", "--quiet"], &paths),
+    )?
+    .status
+    .success();
+    if unchanged {
+        return Ok(format!("Nothing to commit: {:?}", pages));
+    }
+
+    let message = commit_message(op, pages);
+    git_ok(
+        stor_root,
+        &with_paths(&["commit", "--quiet", "--message", &message], &paths),
+    )?;
+    let subject = message.lines().next().unwrap_or_default().to_string();
+    info!("git commit: {}", subject);
+    Ok(subject)
+}
+
+/// A rev in the history of a page.
+pub struct GitRev {
+    pub rev: usize,
+    pub commit: String,
+    // seconds since the epoch
+    pub time: u64,
+}
+
+/// Revs of the page in the history, the newest first.
+pub fn revs(stor_root: &str, page_path: &str) -> Vec<GitRev> {
+    let output = {
+        let _lock = git_lock();
+        git_ok(
+            stor_root,
+            &[
+                "log",
+                "--format=%H%x1f%ct%x1f%B%x1e",
+                "--",
+                path_in_repo(page_path),
+            ],
+        )
+    };
+    let output = match output {
+        Ok(v) => String::from_utf8_lossy(&v).to_string(),
+        Err(e) => {
+            error!("{}", e);
+            return vec![];
+        }
+    };
+
+    let reg = regex::Regex::new(r"(?m)(/\S+) rev (\d+)$").unwrap();
+    let mut revs = vec![];
+    for record in output.split('\x1e') {
+        let mut fields = record.trim().splitn(3, '\x1f');
+        let (Some(commit), Some(time), Some(message)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let rev = reg
+            .captures_iter(message)
+            .find(|caps| &caps[1] == page_path)
+            .and_then(|caps| caps[2].parse::<usize>().ok());
+        if let Some(rev) = rev {
+            revs.push(GitRev {
+                rev,
+                commit: commit.to_string(),
+                time: time.parse().unwrap_or_default(),
+            });
+        }
+    }
+    revs
+}
+
+/// Source of the page of the rev in the history.
+/// To read many revs of a page, rev_store::PageRevs reads the history once.
+pub fn rev_read(stor_root: &str, page_path: &str, rev: usize) -> Result<Vec<u8>, String> {
+    let commit = revs(stor_root, page_path)
+        .into_iter()
+        .find(|git_rev| git_rev.rev == rev)
+        .map(|git_rev| git_rev.commit)
+        .ok_or(format!("No rev {} of {} in git", rev, page_path))?;
+    commit_read(stor_root, page_path, &commit)
+}
+
+/// Source of the page in the commit.
+pub fn commit_read(stor_root: &str, page_path: &str, commit: &str) -> Result<Vec<u8>, String> {
+    let object = format!("{}:./{}", commit, path_in_repo(page_path));
+    let _lock = git_lock();
+    git_ok(stor_root, &["show", &object])
+}
+
+/// Size of the page in the commit.
+pub fn size(stor_root: &str, page_path: &str, commit: &str) -> Option<usize> {
+    let object = format!("{}:./{}", commit, path_in_repo(page_path));
+    let _lock = git_lock();
+    let output = git_ok(stor_root, &["cat-file", "-s", &object]).ok()?;
+    String::from_utf8_lossy(&output).trim().parse().ok()
+}
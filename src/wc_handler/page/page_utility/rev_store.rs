@@ -0,0 +1,243 @@
+use super::storage;
+use super::Page;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tracing::{error, info};
+
+// Store of revs, the backups of a page saved on each rev.
+//
+// A rev is stored as "page.html.N" as it is, or compressed in zlib as "page.html.N.z".
+// Revs are read in both forms whichever the store is, the store decides how new revs
+// are written. It is "rev_store" in stor_root/wc_backup.json read on start.
+// "plain": as it is (default), "zlib": compressed,
+// "git": committed to a git repository in stor_root, see rev_git.rs.
+// Revs in files are read in git as well, for pages saved before git.
+//
+// GET of "page.html.N" gets the rev in any form.
+
+/// Suffix of a rev compressed.
+const ZLIB_SUFFIX: &str = ".z";
+
+const ZLIB_LEVEL: u8 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RevStore {
+    Plain,
+    Zlib,
+    Git,
+}
+
+static REV_STORE: OnceLock<RevStore> = OnceLock::new();
+
+/// Read the store from stor_root, it is done once on start.
+pub fn store_load(stor_root: &str) {
+    let config = super::page_mainte::backup_policy::config_read(stor_root);
+    let store = match config["rev_store"].as_str() {
+        Some("zlib") => RevStore::Zlib,
+        Some("git") => match super::rev_git::repo_init(stor_root) {
+            Ok(()) => RevStore::Git,
+            Err(e) => {
+                error!("Failed to use git, plain is used: {}", e);
+                RevStore::Plain
+            }
+        },
+        Some("plain") | None => RevStore::Plain,
+        Some(v) => {
+            error!("Unknown rev_store: {}, plain is used", v);
+            RevStore::Plain
+        }
+    };
+    info!("rev_store: {:?}", store);
+    let _ = REV_STORE.set(store);
+}
+
+fn store() -> RevStore {
+    *REV_STORE.get_or_init(|| RevStore::Plain)
+}
+
+pub fn is_git() -> bool {
+    store() == RevStore::Git
+}
+
+/// "page.html.N" to "page.html.N.z"
+fn path_zlib(path_rev: &std::path::Path) -> PathBuf {
+    let mut path = path_rev.as_os_str().to_os_string();
+    path.push(ZLIB_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// Path of the file of the rev in the form it exists, None if the rev does not exist.
+pub fn rev_path(page: &Page, rev: usize) -> Option<PathBuf> {
+    let path_rev = page.path_rev_form(rev);
+    if storage::is_file(&path_rev) {
+        return Some(path_rev);
+    }
+    let path_rev = path_zlib(&path_rev);
+    if storage::is_file(&path_rev) {
+        return Some(path_rev);
+    }
+    None
+}
+
+pub fn rev_exists(page: &Page, rev: usize) -> bool {
+    PageRevs::new(page).exists(page, rev)
+}
+
+/// Revs of a page to look up many of them, the history in git is read once.
+pub struct PageRevs {
+    // rev: commit, empty if not git.
+    commits: HashMap<usize, String>,
+}
+
+impl PageRevs {
+    pub fn new(page: &Page) -> PageRevs {
+        let mut commits = HashMap::new();
+        if is_git() {
+            for git_rev in super::rev_git::revs(page.stor_root(), page.page_path()) {
+                // The newest first, the last commit of a rev is kept.
+                commits.entry(git_rev.rev).or_insert(git_rev.commit);
+            }
+        }
+        PageRevs { commits }
+    }
+
+    pub fn exists(&self, page: &Page, rev: usize) -> bool {
+        rev_path(page, rev).is_some() || self.commits.contains_key(&rev)
+    }
+
+    pub fn read(&self, page: &Page, rev: usize) -> Result<Vec<u8>, String> {
+        if let Some(path_rev) = rev_path(page, rev) {
+            return file_read(&path_rev);
+        }
+        match self.commits.get(&rev) {
+            Some(commit) => super::rev_git::commit_read(page.stor_root(), page.page_path(), commit),
+            None => Err(format!("No rev {} of {}", rev, page.file_path())),
+        }
+    }
+}
+
+/// Source of a page of which path ends with rev, "page.html.N".
+pub fn rev_page_read(page: &Page) -> Result<Vec<u8>, String> {
+    let path_rev = page.file_path();
+    let res = file_read(std::path::Path::new(&path_rev));
+    if res.is_ok() || !is_git() {
+        return res;
+    }
+    let (page_path, rev) = page
+        .page_path()
+        .rsplit_once('.')
+        .ok_or(format!("No rev in {}", page.page_path()))?;
+    let rev = rev
+        .parse::<usize>()
+        .map_err(|_| format!("No rev in {}", page.page_path()))?;
+    super::rev_git::rev_read(page.stor_root(), page_path, rev)
+}
+
+/// Read the file of a rev, "page.html.N" or "page.html.N.z" if the former does not exist.
+pub fn file_read(path_rev: &std::path::Path) -> Result<Vec<u8>, String> {
+    match storage::read(path_rev) {
+        Ok(v) if is_zlib(path_rev) => inflate(&v, path_rev),
+        Ok(v) => Ok(v),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !is_zlib(path_rev) => {
+            let path_zlib = path_zlib(path_rev);
+            let v = storage::read(&path_zlib)
+                .map_err(|_| format!("Failed to read: {:?}, {:?}", path_rev, e.kind()))?;
+            inflate(&v, &path_zlib)
+        }
+        Err(e) => Err(format!("Failed to read: {:?}, {:?}", path_rev, e.kind())),
+    }
+}
+
+fn is_zlib(path: &std::path::Path) -> bool {
+    path.to_string_lossy().ends_with(ZLIB_SUFFIX)
+}
+
+fn inflate(data: &[u8], path: &std::path::Path) -> Result<Vec<u8>, String> {
+    miniz_oxide::inflate::decompress_to_vec_zlib(data)
+        .map_err(|e| format!("Failed to inflate {:?}: {:?}", path, e.status))
+}
+
+/// Write the source as the rev in the form of the store.
+/// The rev in the other form is removed not to have two of the rev.
+/// In git, the page file is committed as the rev, the source should be of the file.
+/// Return the path written.
+pub fn rev_write(page: &Page, rev: usize, source: &[u8]) -> Result<String, String> {
+    let path_plain = page.path_rev_form(rev);
+    let path_zlib = path_zlib(&path_plain);
+    let (path_rev, path_other, data) = match store() {
+        RevStore::Git => return super::rev_git::backup_commit(page, rev),
+        RevStore::Plain => (path_plain, path_zlib, source.to_vec()),
+        RevStore::Zlib => (
+            path_zlib,
+            path_plain,
+            miniz_oxide::deflate::compress_to_vec_zlib(source, ZLIB_LEVEL),
+        ),
+    };
+
+    let path_rev = path_rev
+        .to_str()
+        .ok_or(format!("Failed to get str from: {:?}", path_rev))?;
+    let res = super::fs_write(path_rev, &data)?;
+
+    if storage::is_file(&path_other) {
+        if let Err(e) = storage::remove(&path_other) {
+            error!("Failed to remove {:?}: {}", path_other, e);
+        }
+    }
+    Ok(res)
+}
+
+/// Revs existing in files of the page up to rev_last, (rev, path).
+/// Revs in git are not, they are not cleaned as backups.
+pub fn revs(page: &Page, rev_last: usize) -> Vec<(usize, PathBuf)> {
+    (0..=rev_last)
+        .filter_map(|rev| rev_path(page, rev).map(|path| (rev, path)))
+        .collect()
+}
+
+/// The history of revs of the page.
+/// {"rev": 12, "revs": [{"rev": 11, "size": 5032, "stored": 1410, "modified": 1760000000}]}
+/// size: of the page, stored: of the file, modified: seconds since the epoch.
+/// In git, revs in git come first with "commit" instead of "stored".
+pub fn revs_json(page: &mut Page) -> Result<json::JsonValue, String> {
+    let rev_crt = page
+        .rev()
+        .map_err(|_| format!("Failed to get rev: {}", page.file_path()))?;
+
+    let mut revs_json = json::JsonValue::new_array();
+    if is_git() {
+        for git_rev in super::rev_git::revs(page.stor_root(), page.page_path()) {
+            let size = super::rev_git::size(page.stor_root(), page.page_path(), &git_rev.commit);
+            let _ = revs_json.push(json::object! {
+                "rev": git_rev.rev,
+                "size": size,
+                "commit": git_rev.commit,
+                "modified": git_rev.time,
+            });
+        }
+    }
+    for (rev, path) in revs(page, rev_crt).iter().rev() {
+        let metadata = storage::metadata(path)
+            .map_err(|e| format!("Failed to get metadata of {:?}: {}", path, e))?;
+        let size = if is_zlib(path) {
+            file_read(path).map(|v| v.len()).unwrap_or_default()
+        } else {
+            metadata.len as usize
+        };
+        let modified = metadata
+            .modified
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let _ = revs_json.push(json::object! {
+            "rev": *rev,
+            "size": size,
+            "stored": metadata.len,
+            "modified": modified,
+        });
+    }
+
+    Ok(json::object! {"rev": rev_crt, "revs": revs_json})
+}
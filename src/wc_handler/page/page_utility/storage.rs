@@ -0,0 +1,333 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::info;
+
+pub mod storage_fs;
+pub mod storage_mem;
+pub use storage_fs::StorageFs;
+pub use storage_mem::StorageMem;
+
+// Storage of pages and files under stor_root.
+//
+// Page and page_utility read and write files through Storage, not std::fs.
+// A storage is registered for a stor_root, a path is handled by the storage of
+// the stor_root the path is in, StorageFs if none is registered.
+// ie: embedding wc_note on other backends, or tests on StorageMem.
+//
+//   storage::register("mem_root", Arc::new(StorageMem::new("mem_root")));
+//   let mut page = Page::new("mem_root", "/wc_top.html");
+//
+// Paths are as they are on the filesystem, stor_root + page_path.
+// Exports to out of stor_root, dirs of markdown import and rev_store "git"
+// are on the filesystem.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metadata {
+    pub is_dir: bool,
+    pub len: u64,
+    pub modified: SystemTime,
+}
+
+impl Metadata {
+    pub fn is_file(&self) -> bool {
+        !self.is_dir
+    }
+}
+
+pub trait Storage: Send + Sync {
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>>;
+
+    /// Write the whole file or nothing, not to leave a part of it on failure.
+    fn write_atomic(&self, path: &Path, contents: &[u8]) -> std::io::Result<()>;
+
+    /// Paths of files and dirs in the dir.
+    fn list(&self, dir: &Path) -> std::io::Result<Vec<PathBuf>>;
+
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()>;
+
+    fn metadata(&self, path: &Path) -> std::io::Result<Metadata>;
+
+    /// Create the dir, and its parents if recursive.
+    fn mkdir(&self, dir: &Path, recursive: bool) -> std::io::Result<()>;
+
+    fn remove(&self, path: &Path) -> std::io::Result<()>;
+
+    fn set_modified(&self, path: &Path, modified: SystemTime) -> std::io::Result<()>;
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok_and(|metadata| metadata.is_file())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+}
+
+// (stor_root, storage)
+type Storages = Vec<(PathBuf, Arc<dyn Storage>)>;
+
+static STORAGES: Mutex<Storages> = Mutex::new(Vec::new());
+
+fn storages_lock() -> std::sync::MutexGuard<'static, Storages> {
+    STORAGES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Use the storage for paths in stor_root, it replaces one registered before.
+pub fn register(stor_root: &str, storage: Arc<dyn Storage>) {
+    let stor_root = PathBuf::from(stor_root);
+    let mut storages = storages_lock();
+    storages.retain(|(root, _)| root != &stor_root);
+    info!("storage registered: {:?}", stor_root);
+    storages.push((stor_root, storage));
+}
+
+pub fn unregister(stor_root: &str) {
+    let stor_root = PathBuf::from(stor_root);
+    storages_lock().retain(|(root, _)| root != &stor_root);
+}
+
+/// Storage of the path, of the deepest stor_root the path is in.
+pub fn storage(path: &Path) -> Arc<dyn Storage> {
+    storages_lock()
+        .iter()
+        .filter(|(stor_root, _)| path.starts_with(stor_root))
+        .max_by_key(|(stor_root, _)| stor_root.components().count())
+        .map(|(_, storage)| Arc::clone(storage))
+        .unwrap_or_else(|| Arc::new(StorageFs))
+}
+
+pub fn read(path: &Path) -> std::io::Result<Vec<u8>> {
+    storage(path).read(path)
+}
+
+pub fn read_to_string(path: &Path) -> std::io::Result<String> {
+    let contents = read(path)?;
+    String::from_utf8(contents).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    storage(path).write_atomic(path, contents)
+}
+
+pub fn list(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    storage(dir).list(dir)
+}
+
+pub fn rename(from: &Path, to: &Path) -> std::io::Result<()> {
+    storage(from).rename(from, to)
+}
+
+pub fn metadata(path: &Path) -> std::io::Result<Metadata> {
+    storage(path).metadata(path)
+}
+
+pub fn mkdir(dir: &Path, recursive: bool) -> std::io::Result<()> {
+    storage(dir).mkdir(dir, recursive)
+}
+
+pub fn remove(path: &Path) -> std::io::Result<()> {
+    storage(path).remove(path)
+}
+
+pub fn set_modified(path: &Path, modified: SystemTime) -> std::io::Result<()> {
+    storage(path).set_modified(path, modified)
+}
+
+pub fn is_file(path: &Path) -> bool {
+    storage(path).is_file(path)
+}
+
+pub fn exists(path: &Path) -> bool {
+    storage(path).exists(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::page_mainte::page_backup_clean::{
+        garbage_purge, page_backup_clean, BackupCleanLog,
+    };
+    use super::super::{page_child_new, page_save_rev_up, page_url, pages_save_rev_checked, Page};
+    use super::*;
+    use std::io::ErrorKind;
+
+    /// StorageMem registered for stor_root with the top page saved as rev 1.
+    fn mem_top(stor_root: &str) -> (Arc<StorageMem>, Page) {
+        let mem = Arc::new(StorageMem::new(stor_root));
+        register(stor_root, mem.clone());
+        let mut page_json = crate::wc_handler::page::page_json::page_json_plain();
+        page_json["data"]["page"]["title"] = "top".into();
+        let mut top = Page::from_json(stor_root, "/wc_top.html", &page_json).unwrap();
+        top.file_save_and_rev().unwrap();
+        (mem, Page::new(stor_root, "/wc_top.html"))
+    }
+
+    fn mem_files(mem: &StorageMem, dir: &str) -> Vec<String> {
+        let mut files = vec![];
+        let mut dirs = vec![PathBuf::from(dir)];
+        while let Some(dir) = dirs.pop() {
+            for path in mem.list(&dir).unwrap() {
+                if mem.metadata(&path).unwrap().is_dir {
+                    dirs.push(path);
+                } else {
+                    files.push(path.to_string_lossy().to_string());
+                }
+            }
+        }
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn mem_mkdir_on_file() {
+        let mem = StorageMem::new("/m");
+        mem.write_atomic(Path::new("/m/a"), b"a").unwrap();
+        let kind = |r: std::io::Result<()>| r.unwrap_err().kind();
+        assert_eq!(
+            kind(mem.mkdir(Path::new("/m/a"), true)),
+            ErrorKind::AlreadyExists
+        );
+        assert_eq!(
+            kind(mem.mkdir(Path::new("/m/a"), false)),
+            ErrorKind::AlreadyExists
+        );
+        assert_eq!(
+            kind(mem.mkdir(Path::new("/m/a/b/c"), true)),
+            ErrorKind::NotADirectory
+        );
+        assert!(!mem.exists(Path::new("/m/a/b")));
+        mem.mkdir(Path::new("/m/b/c"), true).unwrap();
+        assert!(mem.metadata(Path::new("/m/b")).unwrap().is_dir);
+    }
+
+    #[test]
+    fn mem_rename_onto_dir() {
+        let mem = StorageMem::new("/m");
+        mem.write_atomic(Path::new("/m/a"), b"a").unwrap();
+        mem.mkdir(Path::new("/m/d/e"), true).unwrap();
+        mem.mkdir(Path::new("/m/f"), false).unwrap();
+        let kind = |r: std::io::Result<()>| r.unwrap_err().kind();
+        assert_eq!(
+            kind(mem.rename(Path::new("/m/a"), Path::new("/m/d"))),
+            ErrorKind::IsADirectory
+        );
+        assert_eq!(
+            kind(mem.rename(Path::new("/m/d"), Path::new("/m/a"))),
+            ErrorKind::NotADirectory
+        );
+        assert_eq!(
+            kind(mem.rename(Path::new("/m/d"), Path::new("/m/d/e/d"))),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            kind(mem.rename(Path::new("/m/f"), Path::new("/m/d"))),
+            ErrorKind::DirectoryNotEmpty
+        );
+        assert_eq!(mem.read(Path::new("/m/a")).unwrap(), b"a");
+
+        // An empty dir is replaced.
+        mem.rename(Path::new("/m/d"), Path::new("/m/f")).unwrap();
+        assert!(mem.metadata(Path::new("/m/f/e")).unwrap().is_dir);
+        assert!(!mem.exists(Path::new("/m/d")));
+    }
+
+    #[test]
+    fn mem_page_save_rev() {
+        let stor_root = "/mem_page_save_rev";
+        let (mem, mut top) = mem_top(stor_root);
+        page_save_rev_up(&mut top).unwrap();
+
+        let mut top = Page::new(stor_root, "/wc_top.html");
+        assert_eq!(top.rev(), Ok(2));
+        let files = mem_files(&mem, stor_root);
+        for file in ["/wc_top.html", "/wc_top.html.1", "/wc_top.html.2"] {
+            assert!(
+                files.contains(&format!("{}{}", stor_root, file)),
+                "{:?}",
+                files
+            );
+        }
+        assert!(!files.iter().any(|file| file.ends_with(".tmp")));
+        unregister(stor_root);
+    }
+
+    #[test]
+    fn mem_page_save_rev_checked() {
+        let stor_root = "/mem_page_save_rev_checked";
+        let (_mem, mut top) = mem_top(stor_root);
+        let mut other = Page::new(stor_root, "/wc_top.html");
+        assert_eq!(other.rev(), Ok(1));
+        page_save_rev_up(&mut top).unwrap();
+
+        // other was read at rev 1, the file is rev 2 now.
+        assert!(pages_save_rev_checked(&mut [&mut other]).is_err());
+        assert_eq!(Page::new(stor_root, "/wc_top.html").rev(), Ok(2));
+        pages_save_rev_checked(&mut [&mut top]).unwrap();
+        assert_eq!(Page::new(stor_root, "/wc_top.html").rev(), Ok(3));
+        unregister(stor_root);
+    }
+
+    #[test]
+    fn mem_page_move() {
+        let stor_root = "/mem_page_move";
+        let (mem, mut top) = mem_top(stor_root);
+        let mut child = page_child_new(&mut top, "a", "a/a.html").unwrap();
+        child.dir_build().unwrap();
+        child.file_save_and_rev().unwrap();
+
+        let top_url = page_url(&mut top).unwrap();
+        let child_url = page_url(&mut child).unwrap();
+        let dest_url = top_url.join("b/b.html").unwrap();
+        child.page_move(child_url, dest_url, Some(top_url)).unwrap();
+
+        let mut moved = Page::new(stor_root, "/b/b.html");
+        assert!(mem.is_file(moved.path()));
+        assert_eq!(
+            moved.json().and_then(|page_json| page_json.moved_to()),
+            None
+        );
+        let mut org = Page::new(stor_root, "/a/a.html");
+        assert!(org.moved_location().unwrap().is_some());
+        unregister(stor_root);
+    }
+
+    #[test]
+    fn mem_backup_clean_purge() {
+        let stor_root = "/mem_backup_clean";
+        let (mem, mut top) = mem_top(stor_root);
+        mem.write_atomic(
+            &PathBuf::from(format!("{}/wc_backup.json", stor_root)),
+            br#"{"default": {"keep_last": 2, "keep_days": 0}}"#,
+        )
+        .unwrap();
+        for _ in 0..3 {
+            page_save_rev_up(&mut top).unwrap();
+        }
+
+        let mut log = BackupCleanLog::new(false);
+        page_backup_clean(&mut Page::new(stor_root, "/wc_top.html"), false, &mut log);
+        let files = mem_files(&mem, stor_root);
+        let garbage: Vec<&String> = files.iter().filter(|f| f.contains("/gabage/")).collect();
+        assert_eq!(
+            garbage,
+            [
+                &format!("{}/gabage/wc_top.html.1", stor_root),
+                &format!("{}/gabage/wc_top.html.2", stor_root)
+            ]
+        );
+        assert!(files.contains(&format!("{}/wc_top.html.4", stor_root)));
+
+        // Not purged in the grace period.
+        let mut log = BackupCleanLog::new(false);
+        assert_eq!(garbage_purge(stor_root, Some(0), false, &mut log), Ok(0));
+        let days_ago = SystemTime::now() - std::time::Duration::from_secs(60 * 60 * 24 * 10);
+        for path in garbage {
+            mem.set_modified(Path::new(path), days_ago).unwrap();
+        }
+        assert_eq!(garbage_purge(stor_root, Some(0), false, &mut log), Ok(2));
+        assert!(mem_files(&mem, stor_root)
+            .iter()
+            .all(|f| !f.contains("/gabage/")));
+        unregister(stor_root);
+    }
+}
@@ -0,0 +1,73 @@
+use super::{Metadata, Storage};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+// Storage on the filesystem with std::fs, the default.
+
+// Temporary files of write_atomic are unique with it in a process.
+static TMP_ID_LAST: AtomicUsize = AtomicUsize::new(0);
+
+pub struct StorageFs;
+
+impl Storage for StorageFs {
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    /// Write to a temporary file ".{name}.{pid}.{N}.tmp" in the same dir and rename it to the path.
+    /// The temporary file is next to the path, not next to the file a symlink points to,
+    /// so a symlinked page is replaced by a regular file and the file it pointed to is left as it was.
+    fn write_atomic(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        let file_name = path
+            .file_name()
+            .ok_or(std::io::Error::from(std::io::ErrorKind::InvalidInput))?;
+        let mut file_name_tmp = std::ffi::OsString::from(".");
+        file_name_tmp.push(file_name);
+        file_name_tmp.push(format!(
+            ".{}.{}.tmp",
+            std::process::id(),
+            TMP_ID_LAST.fetch_add(1, Ordering::Relaxed)
+        ));
+        let path_tmp = path.with_file_name(file_name_tmp);
+
+        std::fs::write(&path_tmp, contents)?;
+        std::fs::rename(&path_tmp, path).inspect_err(|_| {
+            let _ = std::fs::remove_file(&path_tmp);
+        })
+    }
+
+    fn list(&self, dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+        std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        std::fs::rename(from, to)
+    }
+
+    fn metadata(&self, path: &Path) -> std::io::Result<Metadata> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(Metadata {
+            is_dir: metadata.is_dir(),
+            len: metadata.len(),
+            modified: metadata.modified()?,
+        })
+    }
+
+    fn mkdir(&self, dir: &Path, recursive: bool) -> std::io::Result<()> {
+        std::fs::DirBuilder::new().recursive(recursive).create(dir)
+    }
+
+    fn remove(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn set_modified(&self, path: &Path, modified: SystemTime) -> std::io::Result<()> {
+        std::fs::File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(modified)
+    }
+}
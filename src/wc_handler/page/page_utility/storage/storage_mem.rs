@@ -0,0 +1,219 @@
+use super::{Metadata, Storage};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+// Storage in memory, for tests and embedding without files.
+// Dirs are made by mkdir as on the filesystem, a file is written in a dir existing.
+
+pub struct StorageMem {
+    entries: Mutex<Entries>,
+}
+
+struct Entries {
+    // path: (contents, modified)
+    files: BTreeMap<PathBuf, (Vec<u8>, SystemTime)>,
+    dirs: BTreeSet<PathBuf>,
+}
+
+impl StorageMem {
+    /// Storage with the dir stor_root only.
+    pub fn new(stor_root: &str) -> StorageMem {
+        let mut dirs = BTreeSet::new();
+        dirs.insert(PathBuf::from(stor_root));
+        StorageMem {
+            entries: Mutex::new(Entries {
+                files: BTreeMap::new(),
+                dirs,
+            }),
+        }
+    }
+
+    fn entries(&self) -> MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn not_found(path: &Path) -> Error {
+    Error::new(ErrorKind::NotFound, format!("{:?}", path))
+}
+
+fn error(kind: ErrorKind, path: &Path) -> Error {
+    Error::new(kind, format!("{:?}", path))
+}
+
+impl Entries {
+    fn parent_exists(&self, path: &Path) -> bool {
+        path.parent()
+            .is_some_and(|parent| self.dirs.contains(parent))
+    }
+}
+
+impl Storage for StorageMem {
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        self.entries()
+            .files
+            .get(path)
+            .map(|(contents, _)| contents.clone())
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn write_atomic(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        let mut entries = self.entries();
+        if !entries.parent_exists(path) {
+            return Err(not_found(path));
+        }
+        if entries.dirs.contains(path) {
+            return Err(error(ErrorKind::IsADirectory, path));
+        }
+        entries
+            .files
+            .insert(path.to_path_buf(), (contents.to_vec(), SystemTime::now()));
+        Ok(())
+    }
+
+    fn list(&self, dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+        let entries = self.entries();
+        if !entries.dirs.contains(dir) {
+            return Err(not_found(dir));
+        }
+        let in_dir = |path: &&PathBuf| path.parent() == Some(dir);
+        Ok(entries
+            .dirs
+            .iter()
+            .filter(in_dir)
+            .chain(entries.files.keys().filter(in_dir))
+            .cloned()
+            .collect())
+    }
+
+    /// A dir is renamed with the files and dirs in it.
+    /// As rename(2), a file or an empty dir at to is replaced by the same kind only.
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        let mut entries = self.entries();
+        if !entries.parent_exists(to) {
+            return Err(not_found(to));
+        }
+        if entries.files.contains_key(from) {
+            if entries.dirs.contains(to) {
+                return Err(error(ErrorKind::IsADirectory, to));
+            }
+            if let Some(file) = entries.files.remove(from) {
+                entries.files.insert(to.to_path_buf(), file);
+            }
+            return Ok(());
+        }
+        if !entries.dirs.contains(from) {
+            return Err(not_found(from));
+        }
+        if from == to {
+            return Ok(());
+        }
+        if to.starts_with(from) {
+            return Err(error(ErrorKind::InvalidInput, to));
+        }
+        if entries.files.contains_key(to) {
+            return Err(error(ErrorKind::NotADirectory, to));
+        }
+        if entries.dirs.contains(to) {
+            let in_to = |path: &&PathBuf| path.parent() == Some(to);
+            if entries.dirs.iter().any(|p| in_to(&p)) || entries.files.keys().any(|p| in_to(&p)) {
+                return Err(error(ErrorKind::DirectoryNotEmpty, to));
+            }
+            entries.dirs.remove(to);
+        }
+
+        let moved = |path: &Path| to.join(path.strip_prefix(from).unwrap_or(path));
+        let dirs: Vec<PathBuf> = entries
+            .dirs
+            .iter()
+            .filter(|path| path.starts_with(from))
+            .cloned()
+            .collect();
+        for dir in dirs {
+            entries.dirs.remove(&dir);
+            entries.dirs.insert(moved(&dir));
+        }
+        let files: Vec<PathBuf> = entries
+            .files
+            .keys()
+            .filter(|path| path.starts_with(from))
+            .cloned()
+            .collect();
+        for path in files {
+            if let Some(file) = entries.files.remove(&path) {
+                entries.files.insert(moved(&path), file);
+            }
+        }
+        Ok(())
+    }
+
+    fn metadata(&self, path: &Path) -> std::io::Result<Metadata> {
+        let entries = self.entries();
+        if let Some((contents, modified)) = entries.files.get(path) {
+            return Ok(Metadata {
+                is_dir: false,
+                len: contents.len() as u64,
+                modified: *modified,
+            });
+        }
+        if entries.dirs.contains(path) {
+            return Ok(Metadata {
+                is_dir: true,
+                len: 0,
+                modified: SystemTime::UNIX_EPOCH,
+            });
+        }
+        Err(not_found(path))
+    }
+
+    fn mkdir(&self, dir: &Path, recursive: bool) -> std::io::Result<()> {
+        let mut entries = self.entries();
+        if entries.dirs.contains(dir) {
+            if recursive {
+                return Ok(());
+            }
+            return Err(error(ErrorKind::AlreadyExists, dir));
+        }
+        if entries.files.contains_key(dir) {
+            return Err(error(ErrorKind::AlreadyExists, dir));
+        }
+        if !recursive && !entries.parent_exists(dir) {
+            return Err(not_found(dir));
+        }
+        // As create_dir_all, no dir is made under a file.
+        let ancestors: Vec<&Path> = dir
+            .ancestors()
+            .take_while(|ancestor| {
+                !ancestor.as_os_str().is_empty() && !entries.dirs.contains(*ancestor)
+            })
+            .collect();
+        if let Some(file) = ancestors
+            .iter()
+            .find(|ancestor| entries.files.contains_key(**ancestor))
+        {
+            return Err(error(ErrorKind::NotADirectory, file));
+        }
+        for ancestor in ancestors {
+            entries.dirs.insert(ancestor.to_path_buf());
+        }
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> std::io::Result<()> {
+        self.entries()
+            .files
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn set_modified(&self, path: &Path, modified: SystemTime) -> std::io::Result<()> {
+        let mut entries = self.entries();
+        let file = entries.files.get_mut(path).ok_or_else(|| not_found(path))?;
+        file.1 = modified;
+        Ok(())
+    }
+}
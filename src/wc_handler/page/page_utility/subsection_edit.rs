@@ -0,0 +1,187 @@
+use super::page_json;
+use super::Page;
+use tracing::info;
+
+// Edit a subsection of a page by its id instead of saving the whole page json.
+// Each request has "rev" of the page the client has,
+// it fails if the page was changed by others, and the page is saved with a new rev.
+//
+// subsection_create  : {"rev": 3, "parent": 0, "index": 1, "title": "t", "href": "#t", "content": []}
+// subsection_update  : {"rev": 3, "id": 5, "title": "t", "href": "#t", "content": []}
+// subsection_delete  : {"rev": 3, "id": 5}
+// subsection_reorder : {"rev": 3, "id": 5, "parent": 0, "index": 0}
+//
+// index is the position in children of the parent, the last if not given.
+// title, href and content of subsection_update are optional, only those given are changed.
+//
+// res: {"res": "subsection_updated", "id": 5, "rev": 4, "sanitized": []}
+
+/// Create a subsection under "parent".
+pub fn subsection_create(
+    page: &mut Page,
+    json_post: &json::JsonValue,
+) -> Result<json::JsonValue, String> {
+    rev_check(page, json_post)?;
+    let parent_id = id_get(&json_post["parent"]).unwrap_or(0);
+    let index = json_post["index"].as_usize();
+
+    let page_json = page_json_mut(page)?;
+    let id = {
+        let mut subsection = page_json
+            .subsection_new(&parent_id)
+            .ok_or(format!("Failed to create a subsection under {}", parent_id))?;
+        subsection.title_set(json_post["title"].as_str().unwrap_or(""));
+        subsection.href_set(json_post["href"].as_str().unwrap_or(""));
+        subsection.id()
+    };
+    // subsection_new puts it at the last of children.
+    if index.is_some() {
+        page_json.subsection_place(&id, &parent_id, index)?;
+    }
+
+    let sanitized = contents_set(page_json, &id, &json_post["content"])?;
+
+    let rev = page_save(page)?;
+    info!("subsection {} created on {}", id, page.page_path());
+    Ok(json::object! {"res": "subsection_created", "id": id, "rev": rev, "sanitized": sanitized})
+}
+
+/// Update title, href and content of the subsection given.
+pub fn subsection_update(
+    page: &mut Page,
+    json_post: &json::JsonValue,
+) -> Result<json::JsonValue, String> {
+    rev_check(page, json_post)?;
+    let id = id_get(&json_post["id"]).ok_or("Failed to get id".to_string())?;
+    if id == 0 {
+        return Err("The root subsection can not be updated".to_string());
+    }
+
+    let page_json = page_json_mut(page)?;
+    {
+        let mut subsection = page_json
+            .subsection_by_id(&id)
+            .ok_or(format!("No subsection of id {}", id))?;
+        if let Some(title) = json_post["title"].as_str() {
+            subsection.title_set(title);
+        }
+        if let Some(href) = json_post["href"].as_str() {
+            subsection.href_set(href);
+        }
+    }
+
+    let sanitized = if json_post["content"].is_null() {
+        json::array![]
+    } else {
+        contents_set(page_json, &id, &json_post["content"])?
+    };
+
+    let rev = page_save(page)?;
+    info!("subsection {} updated on {}", id, page.page_path());
+    Ok(json::object! {"res": "subsection_updated", "id": id, "rev": rev, "sanitized": sanitized})
+}
+
+/// Delete the subsection with its descendants.
+pub fn subsection_delete(
+    page: &mut Page,
+    json_post: &json::JsonValue,
+) -> Result<json::JsonValue, String> {
+    rev_check(page, json_post)?;
+    let id = id_get(&json_post["id"]).ok_or("Failed to get id".to_string())?;
+
+    let deleted = page_json_mut(page)?.subsection_delete(&id)?;
+
+    let rev = page_save(page)?;
+    info!("subsection {:?} deleted on {}", deleted, page.page_path());
+    Ok(json::object! {"res": "subsection_deleted", "id": id, "deleted": deleted, "rev": rev})
+}
+
+/// Move the subsection at "index" of children of "parent" in the page.
+/// parent: the current parent if not given.
+pub fn subsection_reorder(
+    page: &mut Page,
+    json_post: &json::JsonValue,
+) -> Result<json::JsonValue, String> {
+    rev_check(page, json_post)?;
+    let id = id_get(&json_post["id"]).ok_or("Failed to get id".to_string())?;
+    let index = json_post["index"].as_usize();
+
+    let page_json = page_json_mut(page)?;
+    let parent_id = match id_get(&json_post["parent"]) {
+        Some(parent_id) => parent_id,
+        None => page_json
+            .subsection_parent_id(&id)
+            .ok_or(format!("Failed to get parent of subsection {}", id))?,
+    };
+    page_json.subsection_place(&id, &parent_id, index)?;
+
+    let rev = page_save(page)?;
+    info!("subsection {} reordered on {}", id, page.page_path());
+    Ok(json::object! {"res": "subsection_reordered", "id": id, "parent": parent_id, "rev": rev})
+}
+
+/// rev of json_post should be the same as the page.
+fn rev_check(page: &mut Page, json_post: &json::JsonValue) -> Result<(), String> {
+    let page_path = page.page_path().to_string();
+    let page_json = page
+        .json()
+        .ok_or(format!("Failed to get page_json: {}", page_path))?;
+    if page_json.moved_to().is_some() {
+        return Err(format!("The page was moved: {}", page_path));
+    }
+
+    let rev = page_json
+        .rev()
+        .ok_or(format!("Failed to get rev of {}", page_path))?;
+    let rev_post = page_json::to_usize(&json_post["rev"])
+        .map_err(|_| "Failed to get rev from the request".to_string())?;
+    if rev != rev_post {
+        return Err(format!("rev not match {} : {}", rev, rev_post));
+    }
+    Ok(())
+}
+
+fn id_get(id: &json::JsonValue) -> Option<usize> {
+    page_json::to_usize(id).ok()
+}
+
+fn page_json_mut(page: &mut Page) -> Result<&mut page_json::PageJson, String> {
+    let page_path = page.page_path().to_string();
+    page.json_mut()
+        .ok_or(format!("Failed to get page_json: {}", page_path))
+}
+
+/// Replace contents of the subsection, html contents are sanitized.
+/// Return what sanitized.
+fn contents_set(
+    page_json: &mut page_json::PageJson,
+    id: &usize,
+    contents: &json::JsonValue,
+) -> Result<json::JsonValue, String> {
+    if !contents.is_null() && !contents.is_array() {
+        return Err("content should be an array".to_string());
+    }
+
+    let mut contents = if contents.is_null() {
+        json::array![]
+    } else {
+        contents.clone()
+    };
+    let sanitized = super::html_sanitize::contents_sanitize(&mut contents);
+
+    let mut subsection = page_json
+        .subsection_by_id(id)
+        .ok_or(format!("No subsection of id {}", id))?;
+    *subsection.contents_mut() = contents;
+
+    Ok(sanitized.into())
+}
+
+/// Save the page with a new rev and return the rev.
+/// The rev is checked again on save, the page may be changed by others
+/// after rev_check of the request.
+fn page_save(page: &mut Page) -> Result<usize, String> {
+    super::pages_save_rev_checked(&mut [page])?;
+    page.rev()
+        .map_err(|_| format!("Failed to get rev of {}", page.page_path()))
+}
@@ -0,0 +1,296 @@
+use super::page_json;
+use super::page_json::page_data::{ContentType, PageData, Subsection};
+use super::Page;
+use std::collections::{HashMap, HashSet};
+use tracing::{error, info};
+
+// Move a subsection with its children from a page to another page.
+//
+// json_post on the page the subsection is in:
+// {"rev": 3, "id": 5, "dest_url": "/other/other.html", "dest_rev": 8, "parent": 0, "index": 0}
+//
+// dest_rev is checked if given, parent is the root of the destination if not given,
+// index is the position in children of parent, the last if not given.
+// Subsections get new ids in the destination, hrefs in those are converted
+// to work on the destination page, and links left in the origin page
+// to anchors of those go to the destination page.
+// Attachments of the origin page those refer to are copied to the destination.
+//
+// res: {"res": "subsection_moved", "ids": {"5": 12, "6": 13}, "rev": 4, "dest_rev": 9}
+
+pub fn subsection_move(
+    org_page: &mut Page,
+    org_url: &url::Url,
+    json_post: &json::JsonValue,
+) -> Result<json::JsonValue, String> {
+    let stor_root = org_page.stor_root().to_string();
+    let id = page_json::to_usize(&json_post["id"]).map_err(|_| "Failed to get id".to_string())?;
+    if id == 0 {
+        return Err("The root subsection can not be moved".to_string());
+    }
+    let dest_href = json_post["dest_url"]
+        .as_str()
+        .ok_or("Failed to get dest_url".to_string())?;
+    let dest_url = org_url
+        .join(dest_href)
+        .map_err(|e| format!("Failed to join dest_url: {}, {}", dest_href, e))?;
+    if dest_url.path() == org_url.path() {
+        return Err("dest_url is the same page, use subsection_reorder".to_string());
+    }
+    let dest_parent_id = page_json::to_usize(&json_post["parent"]).unwrap_or(0);
+    let index = json_post["index"].as_usize();
+
+    let mut dest_page = Page::new(&stor_root, dest_url.path());
+    rev_check(org_page, &json_post["rev"])?;
+    if !json_post["dest_rev"].is_null() {
+        rev_check(&mut dest_page, &json_post["dest_rev"])?;
+    }
+
+    let org_data = org_page
+        .json()
+        .ok_or(format!("Failed to get page_json: {}", org_url.path()))?
+        .page_data()
+        .map_err(|e| format!("{}: {}", org_url.path(), e))?;
+    let dest_json = dest_page
+        .json_mut()
+        .ok_or(format!("Failed to get page_json: {}", dest_url.path()))?;
+    if dest_json.moved_to().is_some() {
+        return Err(format!("The page was moved: {}", dest_url.path()));
+    }
+    let mut dest_data = dest_json
+        .page_data()
+        .map_err(|e| format!("{}: {}", dest_url.path(), e))?;
+
+    let (id_map, anchors) = subtree_copy(
+        &org_data,
+        org_url,
+        &id,
+        &mut dest_data,
+        &dest_url,
+        &dest_parent_id,
+        index,
+    )?;
+    // Attachments the subsections refer to are copied along.
+    let ids: Vec<usize> = id_map.values().copied().collect();
+    if let Err(e) =
+        super::attachment::attachments_carry(org_url, &mut dest_page, &mut dest_data, &ids)
+    {
+        error!("{}", e);
+    }
+    dest_page
+        .json_mut()
+        .ok_or(format!("Failed to get page_json: {}", dest_url.path()))?
+        .page_data_replace(&dest_data);
+
+    let org_json = org_page
+        .json_mut()
+        .ok_or(format!("Failed to get page_json: {}", org_url.path()))?;
+    org_json.subsection_delete(&id)?;
+
+    // Links left in the origin to "#abc" moved go to "/other/other.html#abc".
+    let dest_link = super::href_on(org_url, dest_url.as_str())
+        .map(|(link, _is_child)| link)
+        .ok_or(format!("Failed to get href of {}", dest_url.path()))?;
+    let mut org_data = org_json
+        .page_data()
+        .map_err(|e| format!("{}: {}", org_url.path(), e))?;
+    subsections_href_convert(&mut org_data, &mut |org_href: &str| {
+        let href_url = org_url.join(org_href).ok()?;
+        if href_url.path() != org_url.path() {
+            return None;
+        }
+        let anchor = anchors.get(&format!("#{}", href_url.fragment()?))?;
+        Some(format!("{}{}", dest_link, anchor))
+    });
+    org_json.page_data_replace(&org_data);
+
+    // Save the destination first, the subsection is in both pages for a moment
+    // rather than in neither of those. Revs are checked again on save,
+    // either page may be changed by others while the move is built.
+    if let Err(e) = super::pages_save_rev_checked(&mut [&mut dest_page, org_page]) {
+        error!("{}", e);
+        return Err(format!("{}, the move was undone", e));
+    }
+
+    let rev = org_page.rev().unwrap_or_default();
+    let dest_rev = dest_page.rev().unwrap_or_default();
+    info!(
+        "subsection {} of {} moved to {} as {}",
+        id,
+        org_url.path(),
+        dest_url.path(),
+        id_map[&id]
+    );
+
+    let mut ids = json::object! {};
+    for (id_org, id_new) in id_map.iter() {
+        ids[id_org.to_string().as_str()] = (*id_new).into();
+    }
+    Ok(json::object! {
+        "res": "subsection_moved",
+        "ids": ids,
+        "rev": rev,
+        "dest_rev": dest_rev,
+    })
+}
+
+/// Org id to new id.
+pub(super) type IdMap = HashMap<usize, usize>;
+/// Org anchor to new anchor, eg: "#abc" to "#abc-12"
+pub(super) type Anchors = HashMap<String, String>;
+
+/// Copy the subsection of id with its descendants in org_data
+/// under dest_parent_id of dest_data at index with new ids.
+/// hrefs are converted from org_url to dest_url,
+/// anchors used in dest_data already are renamed with the new id.
+/// Return new ids of org ids and anchors of org to those in dest_data.
+pub(super) fn subtree_copy(
+    org_data: &PageData,
+    org_url: &url::Url,
+    id: &usize,
+    dest_data: &mut PageData,
+    dest_url: &url::Url,
+    dest_parent_id: &usize,
+    index: Option<usize>,
+) -> Result<(IdMap, Anchors), String> {
+    let subsections_org: Vec<&Subsection> = org_data
+        .subsection
+        .descendants(id)
+        .iter()
+        .filter_map(|id| org_data.subsection.get(id))
+        .collect();
+    if subsections_org.is_empty() {
+        return Err(format!("No subsection of id {}", id));
+    }
+    if dest_data.subsection.get(dest_parent_id).is_none() {
+        return Err(format!(
+            "No subsection of id {} in {}",
+            dest_parent_id,
+            dest_url.path()
+        ));
+    }
+
+    // New ids in the destination.
+    let mut id_map = IdMap::new();
+    for subsection in subsections_org.iter() {
+        let id_new = dest_data
+            .subsection
+            .id_new()
+            .ok_or(format!("Failed to get a new id: {}", dest_url.path()))?;
+        id_map.insert(subsection.id, id_new);
+    }
+
+    let hrefs_dest: HashSet<&str> = dest_data
+        .subsection
+        .data
+        .iter()
+        .map(|subsection| subsection.href.as_str())
+        .collect();
+
+    // Anchors moving together, links to those stay in the page.
+    // Anchors used in the destination already are renamed with the new id.
+    let anchors: Anchors = subsections_org
+        .iter()
+        .filter_map(|subsection| {
+            let href = subsection.href.as_str();
+            if !href.starts_with('#') || href.len() == 1 {
+                return None;
+            }
+            let anchor = if hrefs_dest.contains(href) {
+                format!("{}-{}", href, id_map[&subsection.id])
+            } else {
+                href.to_string()
+            };
+            Some((href.to_string(), anchor))
+        })
+        .collect();
+
+    let mut convert = |org_href: &str| -> Option<String> {
+        if let Some(anchor) = anchors.get(org_href) {
+            return Some(anchor.clone());
+        }
+        let href_url = org_url.join(org_href).ok()?;
+        super::href_on(dest_url, href_url.as_str()).map(|(href, _is_child)| href)
+    };
+
+    for subsection_org in subsections_org.iter() {
+        let mut subsection = (*subsection_org).clone();
+        subsection.id = id_map[&subsection_org.id];
+        subsection.parent = id_map
+            .get(&subsection_org.parent)
+            .copied()
+            .unwrap_or(*dest_parent_id);
+        subsection.child = subsection_org
+            .child
+            .iter()
+            .filter_map(|child| id_map.get(child).copied())
+            .collect();
+        subsection_href_convert(&mut subsection, &mut convert);
+        dest_data.subsection.data.push(subsection);
+    }
+
+    let id_new = id_map[id];
+    if let Some(subsection) = dest_data.subsection.get_mut(&id_new) {
+        subsection.parent = *dest_parent_id;
+    }
+    if let Some(parent) = dest_data.subsection.get_mut(dest_parent_id) {
+        let index = index.unwrap_or(parent.child.len()).min(parent.child.len());
+        parent.child.insert(index, id_new);
+    }
+
+    Ok((id_map, anchors))
+}
+
+/// rev_post should be the same as rev of the page not moved.
+pub(super) fn rev_check(page: &mut Page, rev_post: &json::JsonValue) -> Result<(), String> {
+    let page_path = page.page_path().to_string();
+    let page_json = page
+        .json()
+        .ok_or(format!("Failed to get page_json: {}", page_path))?;
+    if page_json.moved_to().is_some() {
+        return Err(format!("The page was moved: {}", page_path));
+    }
+    let rev = page_json
+        .rev()
+        .ok_or(format!("Failed to get rev of {}", page_path))?;
+    let rev_post =
+        page_json::to_usize(rev_post).map_err(|_| format!("Failed to get rev of {}", page_path))?;
+    if rev != rev_post {
+        return Err(format!(
+            "rev not match {} : {} on {}",
+            rev, rev_post, page_path
+        ));
+    }
+    Ok(())
+}
+
+/// Convert href of the subsection and hrefs in its contents by convert.
+/// If convert returns None, the href is left as it is.
+pub(super) fn subsection_href_convert(
+    subsection: &mut Subsection,
+    convert: &mut dyn FnMut(&str) -> Option<String>,
+) {
+    if !subsection.href.is_empty() {
+        if let Some(href) = convert(&subsection.href) {
+            subsection.href = href;
+        }
+    }
+
+    for content in subsection.content.iter_mut() {
+        let mut value = super::page_move::content_href_convert(&content.value, convert);
+        if content.content_type == ContentType::Markdown {
+            value = super::page_move::markdown_href_convert(&value, convert);
+        }
+        content.value = value;
+    }
+}
+
+/// Convert hrefs of all subsections of page_data by convert.
+pub(super) fn subsections_href_convert(
+    page_data: &mut PageData,
+    convert: &mut dyn FnMut(&str) -> Option<String>,
+) {
+    for subsection in page_data.subsection.data.iter_mut() {
+        subsection_href_convert(subsection, convert);
+    }
+}